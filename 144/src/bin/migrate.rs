@@ -0,0 +1,24 @@
+use std::env;
+use i144::repo::migrate::migrate;
+
+/// Creates missing collections and payload indexes and applies pending migrations.
+/// `--force` recreates collections whose vector size changed, deleting their points.
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Initialize logger
+    env_logger::init();
+
+    let force = env::args().any(|arg| arg == "--force");
+    if force {
+        println!("--force: collections with the wrong vector size will be dropped and recreated");
+    }
+
+    let report = migrate(force).await?;
+
+    println!("Schema at version {}", report.version);
+    println!("Created collections: {:?}", report.created);
+    println!("Recreated collections: {:?}", report.recreated);
+    println!("Applied migrations: {:?}", report.applied);
+
+    Ok(())
+}
@@ -13,7 +13,7 @@ pub const REAL: &'static str = "r";
 pub const I_ID: &'static str = "b4ea369a-d21e-40b4-afe7-4e84a4a7cd91";
 
 // Bible verse processing constants
-pub const BATCH_SIZE_BIBLE: usize = 10;
+pub const BATCH_SIZE_BIBLE: usize = 100;
 pub const QDRANT_TIMEOUT_SECS: u64 = 30;
 
 pub static SECRETS: Lazy<Mutex<SecretStore>> =
@@ -4,6 +4,7 @@ use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 use tokio::time::{timeout, Duration};
 use crate::util::{AppResult, AppError, embed, id};
+use crate::util::embed::embed_batch;
 use crate::util::qdrant::{qdrant_path, qdrant_put};
 use crate::constants::{COLLECTION, BATCH_SIZE_BIBLE, QDRANT_TIMEOUT_SECS};
 use once_cell::sync::Lazy;
@@ -135,7 +136,7 @@ async fn get_embedding_progress() -> AppResult<Option<EmbeddingProgress>> {
 async fn save_embedding_progress(progress: &EmbeddingProgress) -> AppResult<()> {
     let point = json!({
         "id": EMBEDDING_PROGRESS_ID,
-        "vector": vec![0.0; 768], // Dummy vector for progress tracking (768 dimensions for Gemini)
+        "vector": vec![0.0; embed::dimension().await?], // Dummy vector for progress tracking
         "payload": progress
     });
     
@@ -243,22 +244,22 @@ pub async fn process_bible_verses(file_path: &str) -> AppResult<()> {
         let mut points = Vec::new();
         let mut last_verse = None;
 
-        for verse in chunk {
+        // Sanitize text before embedding
+        let texts: Vec<String> = chunk.iter().map(|verse| sanitize_input(&verse.t)).collect();
+
+        // Embed the whole batch in one request, with timeout
+        let embeddings = timeout(
+            Duration::from_secs(60),
+            embed_batch(texts.clone())
+        ).await
+        .map_err(|_| AppError::new_plain("Embedding generation timed out"))?
+        .map_err(|e| AppError::new(&format!("failed to generate embeddings for batch {}", batch_index), e))?;
+
+        for ((verse, sanitized_text), embedding) in chunk.iter().zip(texts).zip(embeddings) {
             // Create a readable reference
             let reference = format!("{} {}:{}", verse.b, verse.c, verse.v);
             log::info!("Processing Bible verse: {}", reference);
 
-            // Sanitize text before embedding
-            let sanitized_text = sanitize_input(&verse.t);
-
-            // Generate embedding using the embed utility with timeout
-            let embedding = timeout(
-                Duration::from_secs(30),
-                embed(sanitized_text.clone())
-            ).await
-            .map_err(|_| AppError::new_plain("Embedding generation timed out"))?
-            .map_err(|e| AppError::new("failed to generate embedding for verse", e))?;
-
             // Create metadata with multitenancy support (s="v" for verses)
             let metadata = VerseMetadata {
                 book: verse.b,
@@ -1,4 +1,5 @@
 pub mod constants;
 pub mod util;
+pub mod repo;
 pub mod routes;
 pub mod gemini_embed;
\ No newline at end of file
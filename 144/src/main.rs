@@ -1,5 +1,6 @@
 use i144::constants::SECRETS;
 use i144::routes;
+use i144::util;
 // use futures_util::StreamExt;
 use i144::routes::a::add::AddRequest;
 use i144::routes::arbitrage::arbitrage;
@@ -19,14 +20,12 @@ async fn warp(
 
     // let (tx, mut rx) = mpsc::channel::<String>(100);
 
-    // #[cfg(debug_assertions)]
-    // {
-    //     if let Err(e) = setup().await {
-    //         println!("setup error: {:#?}", e);
-    //     } else {
-    //         println!("setup done");
-    //     }
-    // }
+    drop(secrets_);
+
+    // Create missing collections and indexes; a schema mismatch needs `bin/migrate --force`
+    if let Err(e) = i144::repo::migrate::migrate(false).await {
+        eprintln!("Error migrating Qdrant: {}", e);
+    }
 
     // Setup a task to periodically clean up old signaling data
     task::spawn(async {
@@ -38,10 +37,19 @@ async fn warp(
         }
     });
 
+    // Pick up the chat sessions and queue from before the restart
+    if let Err(e) = routes::chat::matching::restore().await {
+        eprintln!("Error restoring chat sessions: {}", e);
+    }
+
+    // Pair users waiting for a chat in the background
+    routes::chat::matchmaker::spawn();
+
     let cors = warp::cors()
         .allow_any_origin()
         .allow_methods(vec!["GET", "POST", "PUT", "DELETE"])
-        .allow_headers(vec!["Content-Type"]);
+        .allow_headers(vec!["Content-Type", "Authorization"])
+        .expose_headers(vec![routes::chat::BEFORE_CURSOR, routes::chat::AFTER_CURSOR]);
 
     Ok(warp::path("a")
         .and(warp::path::end())
@@ -88,17 +96,18 @@ async fn warp(
             task::spawn(arbitrage());
             warp::reply()
         }))
-        .or(routes::setup::route())
+        .or(routes::setup::route().recover(util::recover))
         .or(routes::bible::routes())
         .or(routes::verses::routes())
-        .or(routes::voicechat::routes().with(cors.clone()))
-        .or(routes::chat::routes().with(cors.clone()))
-        .or(routes::user::routes().with(cors.clone()))
-        .or(routes::zone::routes().with(cors.clone()))
-        .or(routes::auth::routes().with(cors.clone()))
-        .or(routes::product::routes().with(cors.clone()))
-        .or(routes::service::routes().with(cors.clone()))
-        .or(routes::item::routes().with(cors.clone()))
+        .or(routes::voicechat::routes().recover(util::recover).with(cors.clone()))
+        .or(routes::chat::routes().recover(util::recover).with(cors.clone()))
+        .or(routes::user::routes().recover(util::recover).with(cors.clone()))
+        .or(routes::zone::routes().recover(util::recover).with(cors.clone()))
+        .or(routes::auth::routes().recover(util::recover).with(cors.clone()))
+        .or(routes::product::routes().recover(util::recover).with(cors.clone()))
+        .or(routes::service::routes().recover(util::recover).with(cors.clone()))
+        .or(routes::item::routes().recover(util::recover).with(cors.clone()))
+        .or(routes::moderation::routes().recover(util::recover).with(cors.clone()))
         .or(routes::chatgroup::routes().with(cors.clone()))
         .boxed()
         .into())
@@ -0,0 +1,126 @@
+//! Typed Qdrant filters, serialized to the REST filter format.
+
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Filter {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub must: Vec<Condition>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub should: Vec<Condition>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub must_not: Vec<Condition>,
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn must(mut self, condition: Condition) -> Self {
+        self.must.push(condition);
+        self
+    }
+
+    pub fn should(mut self, condition: Condition) -> Self {
+        self.should.push(condition);
+        self
+    }
+
+    pub fn must_not(mut self, condition: Condition) -> Self {
+        self.must_not.push(condition);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Condition {
+    /// payload `key` equals `value`, or contains it when the payload value is an array
+    Match { key: String, value: Value },
+    /// payload `key` equals any of `values`
+    MatchAny { key: String, values: Vec<Value> },
+    Range { key: String, range: Range },
+    HasId(Vec<String>),
+    /// payload `key` is explicitly null (a missing key is `is_empty`)
+    IsNull(String),
+    /// full-text match: the text in payload `key` has every word of `text`. Needs a text index.
+    Text { key: String, text: String },
+    Nested(Filter),
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Range {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gt: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gte: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lt: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lte: Option<f64>,
+}
+
+impl Range {
+    pub fn contains(&self, x: f64) -> bool {
+        self.gt.is_none_or(|v| x > v)
+            && self.gte.is_none_or(|v| x >= v)
+            && self.lt.is_none_or(|v| x < v)
+            && self.lte.is_none_or(|v| x <= v)
+    }
+}
+
+impl Condition {
+    pub fn matches(key: &str, value: impl Into<Value>) -> Self {
+        Condition::Match { key: key.to_string(), value: value.into() }
+    }
+
+    pub fn any<V: Into<Value>>(key: &str, values: impl IntoIterator<Item = V>) -> Self {
+        Condition::MatchAny {
+            key: key.to_string(),
+            values: values.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    pub fn range(key: &str, range: Range) -> Self {
+        Condition::Range { key: key.to_string(), range }
+    }
+
+    pub fn has_id<S: ToString>(ids: impl IntoIterator<Item = S>) -> Self {
+        Condition::HasId(ids.into_iter().map(|id| id.to_string()).collect())
+    }
+
+    pub fn is_null(key: &str) -> Self {
+        Condition::IsNull(key.to_string())
+    }
+
+    pub fn text(key: &str, text: &str) -> Self {
+        Condition::Text { key: key.to_string(), text: text.to_string() }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Condition::Match { key, value } => json!({"key": key, "match": {"value": value}}),
+            Condition::MatchAny { key, values } => json!({"key": key, "match": {"any": values}}),
+            Condition::Range { key, range } => json!({"key": key, "range": range}),
+            Condition::HasId(ids) => json!({"has_id": ids}),
+            Condition::IsNull(key) => json!({"is_null": {"key": key}}),
+            Condition::Text { key, text } => json!({"key": key, "match": {"text": text}}),
+            Condition::Nested(filter) => json!(filter),
+        }
+    }
+}
+
+impl Serialize for Condition {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_json().serialize(serializer)
+    }
+}
+
+/// The lowercased words of `text`, split the way Qdrant's default `word` tokenizer splits
+/// text it indexes: on anything that isn't a letter or digit
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
@@ -0,0 +1,41 @@
+//! Short payload keys used by points in collection `i`.
+//!
+//! This is the one list of them: model structs in `repo::models` rename their fields to
+//! these strings, and filters and patches are built from these constants.
+
+/// tenant id, one of the values in [`tenant`]
+pub const TENANT: &str = "s";
+pub const NAME: &str = "n";
+/// description of a zone, listing or user, text of a chat group
+pub const TEXT: &str = "t";
+/// location url, or the link of a chat group
+pub const LOCATION: &str = "l";
+/// image urls of zones and users, the image of a chat group
+pub const IMAGES: &str = "i";
+/// image urls of listings
+pub const LISTING_IMAGES: &str = "images";
+/// position `{lat, lng}`
+pub const POSITION: &str = "p";
+/// owner or creator id, or the username on user points
+pub const USER: &str = "u";
+/// zone id a user, listing or chat group belongs to
+pub const ZONE: &str = "z";
+pub const PRICE: &str = "c";
+/// member count of a zone
+pub const MEMBERS: &str = "m";
+/// free-form extra field of a chat group
+pub const EXTRA: &str = "a";
+pub const GOOGLE_ID: &str = "google_id";
+pub const EMAIL: &str = "email";
+pub const AGE: &str = "age";
+pub const GENDER: &str = "g";
+pub const LAST_LOGIN: &str = "last_login";
+
+/// values of the tenant key `s`
+pub mod tenant {
+    pub const ZONE: &str = "z";
+    pub const USER: &str = "u";
+    pub const PRODUCT: &str = "p";
+    pub const SERVICE: &str = "s";
+    pub const CHAT_GROUP: &str = "cg";
+}
@@ -0,0 +1,245 @@
+//! An in-process [`Store`] for tests.
+//!
+//! Points live in a map per collection, ordered by id like Qdrant's scroll. Filters are
+//! evaluated the way Qdrant does for the conditions in [`Condition`], and search ranks by
+//! cosine similarity.
+
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+
+use super::store::{Direction, OrderBy, Point, Selector, Store, StoreFuture};
+use super::filter::words;
+use super::{Condition, FieldSchema, Filter};
+use crate::util::AppResult;
+
+type Collection = BTreeMap<String, Point>;
+
+/// what `create_collection` and `create_index` declared for a collection
+#[derive(Default)]
+struct Schema {
+    size: usize,
+    indexes: BTreeMap<String, FieldSchema>,
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    collections: Mutex<HashMap<String, Collection>>,
+    schemas: Mutex<HashMap<String, Schema>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// runs `f` on the collection, creating it when it's missing
+    fn with<T>(&self, collection: &str, f: impl FnOnce(&mut Collection) -> T) -> T {
+        let mut collections = self.collections.lock().unwrap_or_else(|e| e.into_inner());
+        f(collections.entry(collection.to_string()).or_default())
+    }
+
+    fn schemas(&self) -> std::sync::MutexGuard<'_, HashMap<String, Schema>> {
+        self.schemas.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// the payload indexes created on `collection`
+    pub fn indexes(&self, collection: &str) -> BTreeMap<String, FieldSchema> {
+        self.schemas().get(collection).map(|s| s.indexes.clone()).unwrap_or_default()
+    }
+
+    fn ready<T: Send + 'static>(value: T) -> StoreFuture<'static, T> {
+        Box::pin(futures::future::ready(AppResult::Ok(value)))
+    }
+}
+
+/// the payload value at a dotted `key`, e.g. `p.lat`
+fn lookup<'a>(payload: &'a Map<String, Value>, key: &str) -> Option<&'a Value> {
+    let mut parts = key.split('.');
+    let mut value = payload.get(parts.next()?)?;
+    for part in parts {
+        value = value.get(part)?;
+    }
+    Some(value)
+}
+
+fn same(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// whether the payload value equals `expected`, or contains it when it's an array
+fn has_value(value: Option<&Value>, expected: &Value) -> bool {
+    match value {
+        Some(Value::Array(values)) => values.iter().any(|v| same(v, expected)),
+        Some(value) => same(value, expected),
+        None => false,
+    }
+}
+
+fn condition_matches(condition: &Condition, point: &Point) -> bool {
+    match condition {
+        Condition::Match { key, value } => has_value(lookup(&point.payload, key), value),
+        Condition::MatchAny { key, values } => {
+            let value = lookup(&point.payload, key);
+            values.iter().any(|expected| has_value(value, expected))
+        }
+        Condition::Range { key, range } => match lookup(&point.payload, key) {
+            Some(Value::Array(values)) => values.iter().filter_map(Value::as_f64).any(|x| range.contains(x)),
+            Some(value) => value.as_f64().is_some_and(|x| range.contains(x)),
+            None => false,
+        },
+        Condition::HasId(ids) => ids.contains(&point.id),
+        // like Qdrant, only an explicit null; a missing key is `is_empty`
+        Condition::IsNull(key) => lookup(&point.payload, key).is_some_and(Value::is_null),
+        Condition::Nested(filter) => filter_matches(filter, point),
+        Condition::Text { key, text } => match lookup(&point.payload, key).and_then(Value::as_str) {
+            Some(value) => {
+                let indexed: HashSet<String> = words(value).collect();
+                words(text).all(|word| indexed.contains(&word))
+            }
+            None => false,
+        },
+    }
+}
+
+pub fn filter_matches(filter: &Filter, point: &Point) -> bool {
+    filter.must.iter().all(|c| condition_matches(c, point))
+        && (filter.should.is_empty() || filter.should.iter().any(|c| condition_matches(c, point)))
+        && !filter.must_not.iter().any(|c| condition_matches(c, point))
+}
+
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+fn selected(selector: &Selector, point: &Point) -> bool {
+    match selector {
+        Selector::Ids(ids) => ids.contains(&point.id),
+        Selector::Filter(filter) => filter_matches(filter, point),
+    }
+}
+
+/// what reads return: the payload, and the vector only when asked for
+fn view(point: &Point, with_vector: bool) -> Point {
+    Point {
+        vector: if with_vector { point.vector.clone() } else { None },
+        ..point.clone()
+    }
+}
+
+impl Store for MemoryStore {
+    fn create_collection(&self, collection: &str, size: usize) -> StoreFuture<'_, ()> {
+        self.with(collection, |_| ());
+        self.schemas().entry(collection.to_string()).or_insert(Schema { size, ..Default::default() });
+        Self::ready(())
+    }
+
+    fn collection_size(&self, collection: &str) -> StoreFuture<'_, Option<usize>> {
+        Self::ready(self.schemas().get(collection).map(|s| s.size))
+    }
+
+    fn delete_collection(&self, collection: &str) -> StoreFuture<'_, ()> {
+        self.collections.lock().unwrap_or_else(|e| e.into_inner()).remove(collection);
+        self.schemas().remove(collection);
+        Self::ready(())
+    }
+
+    fn create_index(&self, collection: &str, field: &str, schema: FieldSchema) -> StoreFuture<'_, ()> {
+        if let Some(declared) = self.schemas().get_mut(collection) {
+            declared.indexes.entry(field.to_string()).or_insert(schema);
+        }
+        Self::ready(())
+    }
+
+    fn upsert(&self, collection: &str, points: Vec<Point>) -> StoreFuture<'_, ()> {
+        self.with(collection, |c| {
+            for point in points {
+                c.insert(point.id.clone(), Point { score: None, ..point });
+            }
+        });
+        Self::ready(())
+    }
+
+    fn retrieve(&self, collection: &str, ids: &[&str], with_vector: bool) -> StoreFuture<'_, Vec<Point>> {
+        let points = self.with(collection, |c| {
+            ids.iter().filter_map(|id| c.get(*id)).map(|p| view(p, with_vector)).collect()
+        });
+        Self::ready(points)
+    }
+
+    fn search(&self, collection: &str, vector: &[f32], filter: &Filter, limit: usize, offset: usize) -> StoreFuture<'_, Vec<Point>> {
+        let mut scored: Vec<Point> = self.with(collection, |c| {
+            c.values()
+                .filter(|p| filter_matches(filter, p))
+                .map(|p| Point {
+                    score: Some(cosine(vector, p.vector.as_deref().unwrap_or_default())),
+                    ..view(p, false)
+                })
+                .collect()
+        });
+        scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        Self::ready(scored.into_iter().skip(offset).take(limit).collect())
+    }
+
+    fn scroll(&self, collection: &str, filter: &Filter, limit: usize, offset: Option<String>) -> StoreFuture<'_, (Vec<Point>, Option<String>)> {
+        let page = self.with(collection, |c| {
+            let mut matching = c
+                .range(offset.unwrap_or_default()..)
+                .map(|(_, p)| p)
+                .filter(|p| filter_matches(filter, p));
+            let page: Vec<Point> = matching.by_ref().take(limit).map(|p| view(p, false)).collect();
+            (page, matching.next().map(|p| p.id.clone()))
+        });
+        Self::ready(page)
+    }
+
+    fn count(&self, collection: &str, filter: &Filter) -> StoreFuture<'_, u64> {
+        let count = self.with(collection, |c| c.values().filter(|p| filter_matches(filter, p)).count());
+        Self::ready(count as u64)
+    }
+
+    fn scroll_ordered(&self, collection: &str, filter: &Filter, order: &OrderBy, limit: usize) -> StoreFuture<'_, Vec<Point>> {
+        let mut matching: Vec<Point> = self.with(collection, |c| {
+            c.values()
+                .filter(|p| filter_matches(filter, p) && lookup(&p.payload, &order.key).is_some_and(Value::is_number))
+                .map(|p| view(p, false))
+                .collect()
+        });
+        let value = |p: &Point| lookup(&p.payload, &order.key).and_then(Value::as_f64).unwrap_or_default();
+        matching.sort_by(|a, b| match order.direction {
+            Direction::Asc => value(a).total_cmp(&value(b)),
+            Direction::Desc => value(b).total_cmp(&value(a)),
+        });
+        matching.truncate(limit);
+        Self::ready(matching)
+    }
+
+    fn set_payload(&self, collection: &str, selector: Selector, payload: Map<String, Value>) -> StoreFuture<'_, ()> {
+        self.with(collection, |c| {
+            for point in c.values_mut().filter(|p| selected(&selector, p)) {
+                point.payload.extend(payload.clone());
+            }
+        });
+        Self::ready(())
+    }
+
+    fn update_vector(&self, collection: &str, id: &str, vector: Vec<f32>) -> StoreFuture<'_, ()> {
+        self.with(collection, |c| {
+            if let Some(point) = c.get_mut(id) {
+                point.vector = Some(vector);
+            }
+        });
+        Self::ready(())
+    }
+
+    fn delete(&self, collection: &str, selector: Selector) -> StoreFuture<'_, ()> {
+        self.with(collection, |c| c.retain(|_, p| !selected(&selector, p)));
+        Self::ready(())
+    }
+}
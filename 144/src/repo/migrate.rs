@@ -0,0 +1,143 @@
+//! Brings Qdrant up to date with [`schema`](super::schema).
+//!
+//! [`migrate`] makes sure every collection in [`COLLECTIONS`] exists with its payload
+//! indexes, then runs the [`MIGRATIONS`] newer than the version stored in the `migrations`
+//! collection, recording each as it finishes, so running it again changes nothing. It runs
+//! at startup and from `bin/migrate`.
+//!
+//! A collection whose vector size no longer matches, e.g. after changing `EMBEDDER`, is an
+//! error. Only with `force` is it dropped and recreated, losing its points.
+
+use futures::future::BoxFuture;
+use serde::Serialize;
+use serde_json::{json, Map};
+
+use super::schema::{CollectionSpec, VectorSize, COLLECTIONS, COUNTER_COLLECTION, MIGRATIONS_COLLECTION};
+use super::{store, Point};
+use crate::constants::I_ID;
+use crate::util::{embed, AppError, AppResult};
+
+/// point in `migrations` holding the applied version
+const VERSION_ID: &str = "00000000-0000-7000-8000-000000000001";
+
+/// a data change, run once, in `version` order
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    run: fn() -> BoxFuture<'static, AppResult<()>>,
+}
+
+pub const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "seed the id counter in r",
+    run: seed_counter,
+}];
+
+/// what a run changed
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub created: Vec<String>,
+    pub recreated: Vec<String>,
+    pub applied: Vec<u32>,
+    pub version: u32,
+}
+
+fn seed_counter() -> BoxFuture<'static, AppResult<()>> {
+    Box::pin(async {
+        if store().retrieve(COUNTER_COLLECTION, &[I_ID], false).await?.is_empty() {
+            let payload = json!({"i": 0});
+            store()
+                .upsert(COUNTER_COLLECTION, vec![Point {
+                    id: I_ID.to_string(),
+                    vector: Some(vec![0.0]),
+                    payload: payload.as_object().cloned().unwrap_or_default(),
+                    score: None,
+                }])
+                .await?;
+        }
+        Ok(())
+    })
+}
+
+async fn vector_size(spec: &CollectionSpec) -> AppResult<usize> {
+    match spec.vectors {
+        VectorSize::Embedding => embed::dimension().await,
+        VectorSize::Fixed(size) => Ok(size),
+    }
+}
+
+/// the schema version recorded by the last run, 0 before the first
+pub async fn current_version() -> AppResult<u32> {
+    Ok(store()
+        .retrieve(MIGRATIONS_COLLECTION, &[VERSION_ID], false)
+        .await?
+        .first()
+        .and_then(|point| point.payload.get("version")?.as_u64())
+        .unwrap_or(0) as u32)
+}
+
+async fn record_version(version: u32) -> AppResult<()> {
+    let mut payload = Map::new();
+    payload.insert("version".to_string(), json!(version));
+    payload.insert("updated".to_string(), json!(chrono::Utc::now().to_rfc3339()));
+    store()
+        .upsert(MIGRATIONS_COLLECTION, vec![Point {
+            id: VERSION_ID.to_string(),
+            vector: Some(vec![0.0]),
+            payload,
+            score: None,
+        }])
+        .await
+}
+
+/// creates missing collections and indexes, then applies pending migrations
+pub async fn migrate(force: bool) -> AppResult<Report> {
+    let mut report = Report::default();
+
+    let mut mismatched = Vec::new();
+    for spec in COLLECTIONS {
+        let size = vector_size(spec).await?;
+        match store().collection_size(spec.name).await? {
+            None => {
+                store().create_collection(spec.name, size).await?;
+                report.created.push(spec.name.to_string());
+            }
+            Some(existing) if existing != size => {
+                if !force {
+                    mismatched.push(format!("{} has {} dimensions, not {}", spec.name, existing, size));
+                    continue;
+                }
+                log::warn!("Recreating collection {} with {} dimensions, its points are deleted", spec.name, size);
+                store().delete_collection(spec.name).await?;
+                store().create_collection(spec.name, size).await?;
+                report.recreated.push(spec.name.to_string());
+            }
+            Some(_) => {}
+        }
+        for (field, schema) in spec.indexes {
+            store().create_index(spec.name, field, *schema).await?;
+        }
+    }
+    if !mismatched.is_empty() {
+        return Err(AppError::new_plain(&format!(
+            "collections don't match the schema ({}); migrate with --force to recreate them, deleting their points",
+            mismatched.join(", ")
+        )));
+    }
+
+    let applied = current_version().await?;
+    report.version = applied;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > applied) {
+        log::info!("Applying migration {}: {}", migration.version, migration.description);
+        (migration.run)().await?;
+        record_version(migration.version).await?;
+        report.applied.push(migration.version);
+        report.version = migration.version;
+    }
+
+    log::info!(
+        "Schema at version {} (created {:?}, recreated {:?})",
+        report.version, report.created, report.recreated
+    );
+    Ok(report)
+}
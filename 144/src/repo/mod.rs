@@ -0,0 +1,193 @@
+//! Typed access to Qdrant points.
+//!
+//! Each payload type implements [`Model`], which names its collection and tenant ids.
+//! The functions here add the tenant condition to every read, write `s` on every upsert,
+//! and (de)serialize payloads through the model's serde renames, so routes never touch
+//! raw payload keys. Points are read and written through the global [`store()`].
+
+pub mod filter;
+pub mod keys;
+pub mod memory;
+pub mod migrate;
+pub mod models;
+pub mod schema;
+pub mod store;
+
+pub use filter::{Condition, Filter, Range};
+pub use schema::FieldSchema;
+pub use store::{set_store, store, Direction, OrderBy, Point, Selector, Store};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{Map, Value};
+use warp::http::StatusCode;
+
+use crate::constants::COLLECTION;
+use crate::util::{AppError, AppResult};
+
+pub trait Model: Serialize + DeserializeOwned + Send + Sync {
+    /// used in errors, e.g. "Zone not found"
+    const NAME: &'static str;
+    const COLLECTION: &'static str = COLLECTION;
+    /// tenant ids (`s`) points of this model can have, empty for collections without tenants
+    const TENANTS: &'static [&'static str];
+
+    /// tenant id to store for this value
+    fn tenant(&self) -> Option<&'static str> {
+        Self::TENANTS.first().copied()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Record<T> {
+    pub id: String,
+    pub payload: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+}
+
+fn not_found<T: Model>() -> AppError {
+    AppError::new_status(&format!("{} not found", T::NAME), StatusCode::NOT_FOUND)
+}
+
+/// `filter` limited to the model's tenants
+fn scoped<T: Model>(filter: Filter) -> Filter {
+    match T::TENANTS {
+        [] => filter,
+        [tenant] => filter.must(Condition::matches(keys::TENANT, *tenant)),
+        tenants => filter.must(Condition::any(keys::TENANT, tenants.iter().copied())),
+    }
+}
+
+fn in_tenant<T: Model>(point: &Point) -> bool {
+    T::TENANTS.is_empty()
+        || point.payload
+            .get(keys::TENANT)
+            .and_then(Value::as_str)
+            .is_some_and(|s| T::TENANTS.contains(&s))
+}
+
+/// parses a point, `None` if it belongs to another tenant
+fn parse_point<T: Model>(point: Point) -> AppResult<Option<Record<T>>> {
+    if !in_tenant::<T>(&point) {
+        return Ok(None);
+    }
+    let payload = serde_json::from_value(Value::Object(point.payload))
+        .map_err(|e| AppError::new(&format!("parsing {} payload", T::NAME), e))?;
+    Ok(Some(Record { id: point.id, payload, score: point.score }))
+}
+
+/// parses a list of points, skipping (and logging) ones that don't fit the model
+fn parse_points<T: Model>(points: Vec<Point>) -> Vec<Record<T>> {
+    points
+        .into_iter()
+        .filter_map(|point| {
+            let id = point.id.clone();
+            parse_point::<T>(point).unwrap_or_else(|e| {
+                log::warn!("skipping point {}: {}", id, e);
+                None
+            })
+        })
+        .collect()
+}
+
+/// serializes a patch or payload to a json object
+fn to_map(value: &impl Serialize, name: &str) -> AppResult<Map<String, Value>> {
+    match serde_json::to_value(value) {
+        Ok(Value::Object(map)) => Ok(map),
+        Ok(_) => Err(AppError::new_plain(&format!("{} is not a json object", name))),
+        Err(e) => Err(AppError::new(&format!("serializing {}", name), e)),
+    }
+}
+
+pub async fn get_many<T: Model>(ids: &[&str]) -> AppResult<Vec<Record<T>>> {
+    let mut records = Vec::new();
+    for point in store().retrieve(T::COLLECTION, ids, false).await? {
+        records.extend(parse_point::<T>(point)?);
+    }
+    Ok(records)
+}
+
+/// the model with this id, `None` if the point is missing or belongs to another tenant
+pub async fn get<T: Model>(id: &str) -> AppResult<Option<T>> {
+    Ok(get_many::<T>(&[id])
+        .await?
+        .into_iter()
+        .next()
+        .map(|record| record.payload))
+}
+
+/// like [`get`], but a missing point is a 404 error
+pub async fn find<T: Model>(id: &str) -> AppResult<T> {
+    get::<T>(id).await?.ok_or_else(not_found::<T>)
+}
+
+pub async fn get_vector<T: Model>(id: &str) -> AppResult<Vec<f32>> {
+    store()
+        .retrieve(T::COLLECTION, &[id], true)
+        .await?
+        .into_iter()
+        .find(in_tenant::<T>)
+        .and_then(|point| point.vector)
+        .ok_or_else(not_found::<T>)
+}
+
+pub async fn upsert<T: Model>(id: &str, vector: Vec<f32>, value: &T) -> AppResult<()> {
+    let mut payload = to_map(value, T::NAME)?;
+    if let Some(tenant) = value.tenant() {
+        payload.insert(keys::TENANT.to_string(), tenant.into());
+    }
+    store()
+        .upsert(T::COLLECTION, vec![Point { id: id.to_string(), vector: Some(vector), payload, score: None }])
+        .await
+}
+
+pub async fn update_vector<T: Model>(id: &str, vector: Vec<f32>) -> AppResult<()> {
+    store().update_vector(T::COLLECTION, id, vector).await
+}
+
+/// nearest points to `vector` that match `filter`
+pub async fn search<T: Model>(vector: &[f32], filter: Filter, limit: usize) -> AppResult<Vec<Record<T>>> {
+    search_from::<T>(vector, filter, limit, 0).await
+}
+
+/// [`search`] skipping the first `offset` results
+pub async fn search_from<T: Model>(vector: &[f32], filter: Filter, limit: usize, offset: usize) -> AppResult<Vec<Record<T>>> {
+    let points = store()
+        .search(T::COLLECTION, vector, &scoped::<T>(filter), limit, offset)
+        .await?;
+    Ok(parse_points(points))
+}
+
+/// a page of points matching `filter`, and the offset of the next page
+pub async fn scroll<T: Model>(filter: Filter, limit: usize, offset: Option<String>) -> AppResult<(Vec<Record<T>>, Option<String>)> {
+    let (points, next) = store()
+        .scroll(T::COLLECTION, &scoped::<T>(filter), limit, offset)
+        .await?;
+    Ok((parse_points(points), next))
+}
+
+pub async fn count<T: Model>(filter: Filter) -> AppResult<u64> {
+    store().count(T::COLLECTION, &scoped::<T>(filter)).await
+}
+
+/// merges `patch` into the payload of the given points
+pub async fn set_payload<T: Model>(ids: &[&str], patch: &impl Serialize) -> AppResult<()> {
+    let patch = to_map(patch, T::NAME)?;
+    if patch.is_empty() {
+        return Ok(());
+    }
+    store().set_payload(T::COLLECTION, Selector::ids(ids), patch).await
+}
+
+/// merges `patch` into the payload of every point of the model matching `filter`
+pub async fn set_payload_where<T: Model>(filter: Filter, patch: &impl Serialize) -> AppResult<()> {
+    let patch = to_map(patch, T::NAME)?;
+    if patch.is_empty() {
+        return Ok(());
+    }
+    store().set_payload(T::COLLECTION, Selector::Filter(scoped::<T>(filter)), patch).await
+}
+
+pub async fn delete<T: Model>(ids: &[&str]) -> AppResult<()> {
+    store().delete(T::COLLECTION, Selector::ids(ids)).await
+}
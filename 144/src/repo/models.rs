@@ -0,0 +1,239 @@
+//! Payload models stored in collection `i`. Field renames must match `repo::keys`.
+
+use serde::{Deserialize, Serialize};
+
+use super::keys::tenant;
+use super::Model;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Position {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Zone {
+    #[serde(rename = "n")]
+    pub name: String,
+    #[serde(rename = "t")]
+    pub description: String,
+    #[serde(rename = "l", default)]
+    pub location_url: String,
+    #[serde(rename = "i", default)]
+    pub images: Vec<String>,
+    #[serde(rename = "p")]
+    pub position: Position,
+    /// id of the user who created the zone
+    #[serde(rename = "u", default)]
+    pub creator: Option<String>,
+    /// number of users whose zone is this one
+    #[serde(rename = "m", default)]
+    pub members: u64,
+}
+
+impl Model for Zone {
+    const NAME: &'static str = "Zone";
+    const TENANTS: &'static [&'static str] = &[tenant::ZONE];
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ZonePatch {
+    #[serde(rename = "n", skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "t", skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(rename = "l", skip_serializing_if = "Option::is_none")]
+    pub location_url: Option<String>,
+    #[serde(rename = "i", skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<String>>,
+    #[serde(rename = "p", skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+    #[serde(rename = "m", skip_serializing_if = "Option::is_none")]
+    pub members: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct User {
+    #[serde(rename = "n", default)]
+    pub name: String,
+    #[serde(rename = "t", default)]
+    pub about: String,
+    /// Google Maps url of the user's location
+    #[serde(rename = "l", default)]
+    pub location_url: String,
+    #[serde(rename = "i", default)]
+    pub images: Vec<String>,
+    #[serde(rename = "u", default)]
+    pub username: String,
+    #[serde(rename = "z", default)]
+    pub zone: Option<String>,
+    #[serde(rename = "p", default)]
+    pub position: Option<Position>,
+    #[serde(rename = "google_id", default, skip_serializing_if = "Option::is_none")]
+    pub google_id: Option<String>,
+    #[serde(rename = "email", default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(rename = "age", default, skip_serializing_if = "Option::is_none")]
+    pub age: Option<i32>,
+    #[serde(rename = "g", default, skip_serializing_if = "Option::is_none")]
+    pub gender: Option<String>,
+    /// rfc3339 time of the last login
+    #[serde(rename = "last_login", default, skip_serializing_if = "Option::is_none")]
+    pub last_login: Option<String>,
+}
+
+impl Model for User {
+    const NAME: &'static str = "User";
+    const TENANTS: &'static [&'static str] = &[tenant::USER];
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct UserPatch {
+    /// `Some(None)` clears the zone
+    #[serde(rename = "z", skip_serializing_if = "Option::is_none")]
+    pub zone: Option<Option<String>>,
+    #[serde(rename = "last_login", skip_serializing_if = "Option::is_none")]
+    pub last_login: Option<String>,
+}
+
+/// What a marketplace listing sells, stored as its tenant id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListingKind {
+    Product,
+    Service,
+}
+
+impl ListingKind {
+    pub const ALL: [ListingKind; 2] = [ListingKind::Product, ListingKind::Service];
+
+    /// tenant id stored in `s`
+    pub fn code(self) -> &'static str {
+        match self {
+            ListingKind::Product => tenant::PRODUCT,
+            ListingKind::Service => tenant::SERVICE,
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.code() == code)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ListingKind::Product => "product",
+            ListingKind::Service => "service",
+        }
+    }
+
+    /// key that holds search results on the kind's own routes, e.g. `products`
+    pub fn plural(self) -> &'static str {
+        match self {
+            ListingKind::Product => "products",
+            ListingKind::Service => "services",
+        }
+    }
+}
+
+/// A product or service
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Listing {
+    #[serde(rename = "t")]
+    pub description: String,
+    #[serde(rename = "c")]
+    pub price: f64,
+    /// owner's user id
+    #[serde(rename = "u")]
+    pub user: String,
+    /// inherited from the owner
+    #[serde(rename = "z", default)]
+    pub zone: Option<String>,
+    #[serde(rename = "images", default)]
+    pub images: Vec<String>,
+    /// inherited from the owner
+    #[serde(rename = "l", default)]
+    pub location_url: String,
+    /// inherited from the owner
+    #[serde(rename = "p", default)]
+    pub position: Option<Position>,
+    #[serde(rename = "s", with = "kind_code")]
+    pub kind: ListingKind,
+}
+
+impl Model for Listing {
+    const NAME: &'static str = "Item";
+    const TENANTS: &'static [&'static str] = &[tenant::PRODUCT, tenant::SERVICE];
+
+    fn tenant(&self) -> Option<&'static str> {
+        Some(self.kind.code())
+    }
+}
+
+/// (de)serializes a `ListingKind` as its tenant id
+mod kind_code {
+    use super::ListingKind;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(kind: &ListingKind, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(kind.code())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<ListingKind, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        ListingKind::from_code(&code).ok_or_else(|| D::Error::custom(format!("not a listing tenant id: {}", code)))
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ListingPatch {
+    #[serde(rename = "t", skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(rename = "c", skip_serializing_if = "Option::is_none")]
+    pub price: Option<f64>,
+    #[serde(rename = "images", skip_serializing_if = "Option::is_none")]
+    pub images: Option<Vec<String>>,
+    /// `Some(None)` clears the zone
+    #[serde(rename = "z", skip_serializing_if = "Option::is_none")]
+    pub zone: Option<Option<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatGroup {
+    #[serde(rename = "n")]
+    pub name: String,
+    #[serde(rename = "t")]
+    pub text: String,
+    #[serde(rename = "l")]
+    pub link: String,
+    #[serde(rename = "i", default)]
+    pub image: Option<String>,
+    /// owner's user id
+    #[serde(rename = "u")]
+    pub user: String,
+    #[serde(rename = "z", default)]
+    pub zone: Option<String>,
+    #[serde(rename = "a", default)]
+    pub extra: Option<String>,
+}
+
+impl Model for ChatGroup {
+    const NAME: &'static str = "Chat group";
+    const TENANTS: &'static [&'static str] = &[tenant::CHAT_GROUP];
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ChatGroupPatch {
+    #[serde(rename = "n", skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "t", skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(rename = "l", skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>,
+    #[serde(rename = "i", skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// `Some(None)` clears the zone
+    #[serde(rename = "z", skip_serializing_if = "Option::is_none")]
+    pub zone: Option<Option<String>>,
+    #[serde(rename = "a", skip_serializing_if = "Option::is_none")]
+    pub extra: Option<String>,
+}
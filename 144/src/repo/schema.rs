@@ -0,0 +1,153 @@
+//! Every Qdrant collection the app uses, with its vector size and payload indexes.
+//!
+//! [`migrate`](super::migrate) creates whatever is missing from this list. Add a
+//! collection or index here rather than creating it from a route.
+
+use serde::{Deserialize, Serialize};
+
+use super::keys;
+use crate::constants::{COLLECTION, REAL};
+use crate::routes::chat::storage::{CHAT_COLLECTION, READS_COLLECTION, SESSIONS_COLLECTION as CHAT_SESSIONS_COLLECTION};
+use crate::routes::moderation::{blocks::BLOCKS_COLLECTION, reports::REPORTS_COLLECTION};
+use crate::util::embed::EMBEDDING_CACHE_COLLECTION;
+use crate::util::safety::FLAGGED_COLLECTION;
+use crate::util::session::SESSIONS_COLLECTION;
+
+/// holds the id counter point
+pub const COUNTER_COLLECTION: &str = REAL;
+pub const CHAT_USERS_COLLECTION: &str = "chat_users";
+pub const VOICE_CHAT_USERS_COLLECTION: &str = "voice_chat_users";
+/// holds the applied schema version
+pub const MIGRATIONS_COLLECTION: &str = "migrations";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldSchema {
+    Keyword,
+    Integer,
+    Float,
+    Bool,
+    Datetime,
+    Text,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorSize {
+    /// the configured embedder's dimension
+    Embedding,
+    /// collections that only hold payloads still need a vector, these use a size 1 one
+    Fixed(usize),
+}
+
+#[derive(Debug)]
+pub struct CollectionSpec {
+    pub name: &'static str,
+    pub vectors: VectorSize,
+    pub indexes: &'static [(&'static str, FieldSchema)],
+}
+
+pub const COLLECTIONS: &[CollectionSpec] = &[
+    CollectionSpec {
+        name: COLLECTION,
+        vectors: VectorSize::Embedding,
+        indexes: &[
+            (keys::TENANT, FieldSchema::Keyword),
+            (keys::USER, FieldSchema::Keyword),
+            (keys::ZONE, FieldSchema::Keyword),
+            (keys::MEMBERS, FieldSchema::Integer),
+            (keys::GOOGLE_ID, FieldSchema::Keyword),
+            (keys::EMAIL, FieldSchema::Keyword),
+            (keys::PRICE, FieldSchema::Float),
+        ],
+    },
+    CollectionSpec {
+        name: COUNTER_COLLECTION,
+        vectors: VectorSize::Fixed(1),
+        indexes: &[],
+    },
+    CollectionSpec {
+        name: CHAT_COLLECTION,
+        vectors: VectorSize::Embedding,
+        indexes: &[
+            ("session_id", FieldSchema::Keyword),
+            ("sender_id", FieldSchema::Keyword),
+            ("timestamp", FieldSchema::Integer),
+            ("deleted", FieldSchema::Bool),
+            ("message", FieldSchema::Text),
+        ],
+    },
+    CollectionSpec {
+        name: READS_COLLECTION,
+        vectors: VectorSize::Fixed(1),
+        indexes: &[
+            ("session_id", FieldSchema::Keyword),
+            ("user_id", FieldSchema::Keyword),
+        ],
+    },
+    CollectionSpec {
+        name: CHAT_SESSIONS_COLLECTION,
+        vectors: VectorSize::Fixed(1),
+        indexes: &[
+            ("user1_id", FieldSchema::Keyword),
+            ("user2_id", FieldSchema::Keyword),
+            ("active", FieldSchema::Bool),
+            ("outcome", FieldSchema::Keyword),
+        ],
+    },
+    CollectionSpec {
+        name: SESSIONS_COLLECTION,
+        vectors: VectorSize::Fixed(1),
+        indexes: &[(keys::USER, FieldSchema::Keyword)],
+    },
+    CollectionSpec {
+        name: CHAT_USERS_COLLECTION,
+        vectors: VectorSize::Embedding,
+        indexes: &[
+            ("age_range.min", FieldSchema::Integer),
+            ("age_range.max", FieldSchema::Integer),
+            ("waiting", FieldSchema::Bool),
+        ],
+    },
+    CollectionSpec {
+        name: VOICE_CHAT_USERS_COLLECTION,
+        vectors: VectorSize::Embedding,
+        indexes: &[],
+    },
+    CollectionSpec {
+        name: EMBEDDING_CACHE_COLLECTION,
+        vectors: VectorSize::Embedding,
+        indexes: &[],
+    },
+    CollectionSpec {
+        name: BLOCKS_COLLECTION,
+        vectors: VectorSize::Fixed(1),
+        indexes: &[
+            ("blocker_id", FieldSchema::Keyword),
+            ("blocked_id", FieldSchema::Keyword),
+        ],
+    },
+    CollectionSpec {
+        name: REPORTS_COLLECTION,
+        vectors: VectorSize::Fixed(1),
+        indexes: &[
+            ("status", FieldSchema::Keyword),
+            ("reported_id", FieldSchema::Keyword),
+            ("created_at", FieldSchema::Integer),
+        ],
+    },
+    CollectionSpec {
+        name: FLAGGED_COLLECTION,
+        vectors: VectorSize::Fixed(1),
+        indexes: &[
+            ("kind", FieldSchema::Keyword),
+            ("author_id", FieldSchema::Keyword),
+            ("reviewed", FieldSchema::Bool),
+            ("created_at", FieldSchema::Integer),
+        ],
+    },
+    CollectionSpec {
+        name: MIGRATIONS_COLLECTION,
+        vectors: VectorSize::Fixed(1),
+        indexes: &[],
+    },
+];
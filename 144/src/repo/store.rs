@@ -0,0 +1,287 @@
+//! The point storage behind [`repo`](super).
+//!
+//! [`Store`] is the set of Qdrant operations the app uses. [`RestStore`] sends them to the
+//! Qdrant REST API; [`MemoryStore`](super::memory::MemoryStore) keeps points in process so
+//! routes can be tested without a network. The store in use is global and can be replaced
+//! with [`set_store`].
+
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Map, Value};
+use std::sync::{Arc, RwLock};
+
+use super::{FieldSchema, Filter};
+use crate::util::{AppError, AppResult, qdrant::{qdrant_delete, qdrant_get, qdrant_path, qdrant_post, qdrant_put}};
+
+pub type StoreFuture<'a, T> = BoxFuture<'a, AppResult<T>>;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    #[serde(deserialize_with = "id_string")]
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vector: Option<Vec<f32>>,
+    #[serde(default)]
+    pub payload: Map<String, Value>,
+    /// similarity to the query, only set on search results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+}
+
+/// Qdrant ids are uuids or integers, both are kept as strings here
+fn id_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(match Value::deserialize(deserializer)? {
+        Value::String(s) => s,
+        other => other.to_string(),
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+/// order for [`Store::scroll_ordered`], by a numeric payload field with a range index
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderBy {
+    pub key: String,
+    pub direction: Direction,
+}
+
+impl OrderBy {
+    pub fn new(key: &str, direction: Direction) -> Self {
+        OrderBy { key: key.to_string(), direction }
+    }
+}
+
+/// the points a payload update or delete applies to
+#[derive(Debug, Clone)]
+pub enum Selector {
+    Ids(Vec<String>),
+    Filter(Filter),
+}
+
+impl Selector {
+    pub fn ids<S: ToString>(ids: impl IntoIterator<Item = S>) -> Self {
+        Selector::Ids(ids.into_iter().map(|id| id.to_string()).collect())
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Selector::Ids(ids) => json!({"points": ids}),
+            Selector::Filter(filter) => json!({"filter": filter}),
+        }
+    }
+}
+
+pub trait Store: Send + Sync {
+    /// creates a cosine collection of `size`-dimensional vectors, if it doesn't exist
+    fn create_collection(&self, collection: &str, size: usize) -> StoreFuture<'_, ()>;
+
+    /// vector size of the collection, `None` when it doesn't exist
+    fn collection_size(&self, collection: &str) -> StoreFuture<'_, Option<usize>>;
+
+    /// drops the collection and every point in it
+    fn delete_collection(&self, collection: &str) -> StoreFuture<'_, ()>;
+
+    /// payload index on `field`; creating one that already exists does nothing
+    fn create_index(&self, collection: &str, field: &str, schema: FieldSchema) -> StoreFuture<'_, ()>;
+
+    fn upsert(&self, collection: &str, points: Vec<Point>) -> StoreFuture<'_, ()>;
+
+    /// the points with these ids that exist
+    fn retrieve(&self, collection: &str, ids: &[&str], with_vector: bool) -> StoreFuture<'_, Vec<Point>>;
+
+    /// points matching `filter`, most similar to `vector` first
+    fn search(&self, collection: &str, vector: &[f32], filter: &Filter, limit: usize, offset: usize) -> StoreFuture<'_, Vec<Point>>;
+
+    /// a page of points matching `filter` in id order, and the id the next page starts at
+    fn scroll(&self, collection: &str, filter: &Filter, limit: usize, offset: Option<String>) -> StoreFuture<'_, (Vec<Point>, Option<String>)>;
+
+    /// the first `limit` points matching `filter` in `order`. Points with equal values come
+    /// in no particular order.
+    fn scroll_ordered(&self, collection: &str, filter: &Filter, order: &OrderBy, limit: usize) -> StoreFuture<'_, Vec<Point>>;
+
+    fn count(&self, collection: &str, filter: &Filter) -> StoreFuture<'_, u64>;
+
+    /// merges `payload` into the payload of the selected points
+    fn set_payload(&self, collection: &str, selector: Selector, payload: Map<String, Value>) -> StoreFuture<'_, ()>;
+
+    fn update_vector(&self, collection: &str, id: &str, vector: Vec<f32>) -> StoreFuture<'_, ()>;
+
+    fn delete(&self, collection: &str, selector: Selector) -> StoreFuture<'_, ()>;
+}
+
+static STORE: Lazy<RwLock<Arc<dyn Store>>> = Lazy::new(|| RwLock::new(Arc::new(RestStore)));
+
+/// the store `repo` reads and writes through
+pub fn store() -> Arc<dyn Store> {
+    STORE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// replaces the store for the whole process, e.g. with a `MemoryStore` in tests
+pub fn set_store(store: Arc<dyn Store>) {
+    *STORE.write().unwrap_or_else(|e| e.into_inner()) = store;
+}
+
+/// Qdrant over its REST API, at `QDRANT_URL`
+pub struct RestStore;
+
+async fn path(collection: &str, rest: &str) -> AppResult<String> {
+    qdrant_path(&format!("collections/{}/{}", collection, rest)).await
+}
+
+fn points(value: &Value) -> AppResult<Vec<Point>> {
+    serde_json::from_value(value.clone()).map_err(|e| AppError::new("parsing qdrant points", e))
+}
+
+impl Store for RestStore {
+    fn create_collection(&self, collection: &str, size: usize) -> StoreFuture<'_, ()> {
+        let collection = collection.to_string();
+        Box::pin(async move {
+            let created = qdrant_put(
+                &qdrant_path(&format!("collections/{}?wait=true", collection)).await?,
+                json!({"vectors": {"size": size, "distance": "Cosine"}}),
+            )
+            .await;
+            match created {
+                Err(e) if !e.to_string().contains("already exists") => Err(e),
+                _ => Ok(()),
+            }
+        })
+    }
+
+    fn collection_size(&self, collection: &str) -> StoreFuture<'_, Option<usize>> {
+        let collection = collection.to_string();
+        Box::pin(async move {
+            let exists = qdrant_get(&path(&collection, "exists").await?).await?;
+            if !exists["result"]["exists"].as_bool().unwrap_or(false) {
+                return Ok(None);
+            }
+            let info = qdrant_get(&qdrant_path(&format!("collections/{}", collection)).await?).await?;
+            info["result"]["config"]["params"]["vectors"]["size"]
+                .as_u64()
+                .map(|size| Some(size as usize))
+                .ok_or_else(|| AppError::new_plain(&format!("no vector size for collection {}", collection)))
+        })
+    }
+
+    fn delete_collection(&self, collection: &str) -> StoreFuture<'_, ()> {
+        let collection = collection.to_string();
+        Box::pin(async move {
+            qdrant_delete(&qdrant_path(&format!("collections/{}?wait=true", collection)).await?).await?;
+            Ok(())
+        })
+    }
+
+    fn create_index(&self, collection: &str, field: &str, schema: FieldSchema) -> StoreFuture<'_, ()> {
+        let collection = collection.to_string();
+        let body = json!({"field_name": field, "field_schema": schema});
+        Box::pin(async move {
+            match qdrant_put(&path(&collection, "index?wait=true").await?, body).await {
+                Err(e) if !e.to_string().contains("already exists") => Err(e),
+                _ => Ok(()),
+            }
+        })
+    }
+
+    fn upsert(&self, collection: &str, points: Vec<Point>) -> StoreFuture<'_, ()> {
+        let collection = collection.to_string();
+        Box::pin(async move {
+            qdrant_put(&path(&collection, "points?wait=true").await?, json!({"points": points})).await?;
+            Ok(())
+        })
+    }
+
+    fn retrieve(&self, collection: &str, ids: &[&str], with_vector: bool) -> StoreFuture<'_, Vec<Point>> {
+        let collection = collection.to_string();
+        let body = json!({"ids": ids, "with_payload": true, "with_vector": with_vector});
+        Box::pin(async move {
+            let res = qdrant_post(&path(&collection, "points").await?, body).await?;
+            points(&res["result"])
+        })
+    }
+
+    fn search(&self, collection: &str, vector: &[f32], filter: &Filter, limit: usize, offset: usize) -> StoreFuture<'_, Vec<Point>> {
+        let collection = collection.to_string();
+        let body = json!({
+            "vector": vector,
+            "filter": filter,
+            "limit": limit,
+            "offset": offset,
+            "with_payload": true
+        });
+        Box::pin(async move {
+            let res = qdrant_post(&path(&collection, "points/search").await?, body).await?;
+            points(&res["result"])
+        })
+    }
+
+    fn scroll(&self, collection: &str, filter: &Filter, limit: usize, offset: Option<String>) -> StoreFuture<'_, (Vec<Point>, Option<String>)> {
+        let collection = collection.to_string();
+        let mut body = json!({"filter": filter, "limit": limit, "with_payload": true});
+        if let Some(offset) = offset {
+            body["offset"] = json!(offset);
+        }
+        Box::pin(async move {
+            let res = qdrant_post(&path(&collection, "points/scroll").await?, body).await?;
+            let next = match &res["result"]["next_page_offset"] {
+                Value::Null => None,
+                Value::String(s) => Some(s.clone()),
+                other => Some(other.to_string()),
+            };
+            Ok((points(&res["result"]["points"])?, next))
+        })
+    }
+
+    fn scroll_ordered(&self, collection: &str, filter: &Filter, order: &OrderBy, limit: usize) -> StoreFuture<'_, Vec<Point>> {
+        let collection = collection.to_string();
+        let body = json!({"filter": filter, "limit": limit, "order_by": order, "with_payload": true});
+        Box::pin(async move {
+            let res = qdrant_post(&path(&collection, "points/scroll").await?, body).await?;
+            points(&res["result"]["points"])
+        })
+    }
+
+    fn count(&self, collection: &str, filter: &Filter) -> StoreFuture<'_, u64> {
+        let collection = collection.to_string();
+        let body = json!({"filter": filter, "exact": true});
+        Box::pin(async move {
+            let res = qdrant_post(&path(&collection, "points/count").await?, body).await?;
+            res["result"]["count"]
+                .as_u64()
+                .ok_or_else(|| AppError::new_plain("no count in qdrant count response"))
+        })
+    }
+
+    fn set_payload(&self, collection: &str, selector: Selector, payload: Map<String, Value>) -> StoreFuture<'_, ()> {
+        let collection = collection.to_string();
+        let mut body = selector.to_json();
+        body["payload"] = Value::Object(payload);
+        Box::pin(async move {
+            qdrant_post(&path(&collection, "points/payload?wait=true").await?, body).await?;
+            Ok(())
+        })
+    }
+
+    fn update_vector(&self, collection: &str, id: &str, vector: Vec<f32>) -> StoreFuture<'_, ()> {
+        let collection = collection.to_string();
+        let body = json!({"points": [{"id": id, "vector": vector}]});
+        Box::pin(async move {
+            qdrant_put(&path(&collection, "points/vectors?wait=true").await?, body).await?;
+            Ok(())
+        })
+    }
+
+    fn delete(&self, collection: &str, selector: Selector) -> StoreFuture<'_, ()> {
+        let collection = collection.to_string();
+        let body = selector.to_json();
+        Box::pin(async move {
+            qdrant_post(&path(&collection, "points/delete?wait=true").await?, body).await?;
+            Ok(())
+        })
+    }
+}
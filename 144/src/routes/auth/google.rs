@@ -7,15 +7,11 @@ use oauth2::{
 };
 use serde_json::json;
 use crate::{
-    util::{AppResult, AppError, id, qdrant::{qdrant_path, qdrant_post, qdrant_put}},
+    repo::{self, keys, models::{Position, User, UserPatch}, Condition},
+    util::{AppResult, AppError, embed, id, session},
     constants::SECRETS,
 };
 use std::time::Instant;
-use once_cell::sync::Lazy;
-use std::sync::Once;
-
-// Initialize Google auth setup once
-static INIT: Lazy<Once> = Lazy::new(|| Once::new());
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GoogleUser {
@@ -32,17 +28,6 @@ pub struct AuthCallback {
 }
 
 pub fn routes() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
-    // Ensure indexes exist
-    INIT.call_once(|| {
-        tokio::spawn(async {
-            if let Err(e) = create_required_indexes().await {
-                log::error!("Failed to create indexes for Google auth: {:#?}", e);
-            } else {
-                log::info!("Google auth indexes initialized successfully");
-            }
-        });
-    });
-    
     log::info!("Initializing Google auth routes");
     login_route()
         .or(callback_route())
@@ -124,79 +109,6 @@ pub async fn google_callback(callback: AuthCallback) -> impl Reply {
                 elapsed
             );
             
-            // Optimized verification using the index
-            log::info!("Performing final verification");
-            match qdrant_path("collections/i/points/scroll").await {
-                Ok(path) => {
-                    let verify_result = qdrant_post(
-                        &path,
-                        json!({
-                            "filter": {
-                                "must": [
-                                    {
-                                        "key": "google_id",
-                                        "match": { "value": google_id }
-                                    },
-                                    {
-                                        "key": "s",
-                                        "match": { "value": "u" }
-                                    }
-                                ]
-                            },
-                            "with_payload": true,
-                            "limit": 1
-                        })
-                    ).await;
-                    
-                    match verify_result {
-                        Ok(result) => {
-                            if let Some(points) = result["result"]["points"].as_array() {
-                                if points.is_empty() {
-                                    log::error!("VERIFICATION FAILED: User with Google ID {} not found!", 
-                                              if google_id.len() > 6 { format!("{}***", &google_id[0..6]) } else { google_id.to_string() });
-                                } else {
-                                    log::info!("VERIFICATION SUCCESS: User found with Google ID");
-                                }
-                            }
-                        },
-                        Err(e) => {
-                            log::error!("VERIFICATION ERROR: {}", e);
-                            // Try fallback to ID query
-                            log::info!("Trying verification by ID...");
-                            let id_verify = qdrant_post(
-                                &path,
-                                json!({
-                                    "filter": {
-                                        "must": [
-                                            {
-                                                "key": "id",
-                                                "match": { "value": user_id }
-                                            }
-                                        ]
-                                    },
-                                    "with_payload": true,
-                                    "limit": 1
-                                })
-                            ).await;
-                            
-                            match id_verify {
-                                Ok(id_result) => {
-                                    if let Some(id_points) = id_result["result"]["points"].as_array() {
-                                        if !id_points.is_empty() {
-                                            log::info!("ID VERIFICATION SUCCESS: User found by ID");
-                                        } else {
-                                            log::error!("ID VERIFICATION FAILED: User not found by ID");
-                                        }
-                                    }
-                                },
-                                Err(e) => log::error!("ID verification error: {}", e)
-                            }
-                        }
-                    }
-                },
-                Err(e) => log::error!("Failed to construct path for verification: {}", e)
-            }
-            
             warp::reply::with_status(
                 warp::reply::json(&user_data),
                 warp::http::StatusCode::OK,
@@ -312,128 +224,22 @@ async fn handle_callback(callback: AuthCallback) -> AppResult<serde_json::Value>
     log::debug!("Database operation completed in {:?}", db_start.elapsed());
     
     // VERIFICATION: Query the database to verify the user exists
-    log::info!("Verifying user was properly saved to database");
-    let verify_start = Instant::now();
-    
-    // 1. Query specifically for this user by ID
-    let user_query_result = qdrant_post(
-        &qdrant_path("collections/i/points/scroll").await?,
-        json!({
-            "filter": {
-                "must": [
-                    {
-                        "key": "s",
-                        "match": { "value": "u" }
-                    }
-                ]
-            },
-            "with_payload": true,
-            "with_vectors": false,
-            "limit": 1,
-            "scroll_filter": {
-                "condition": {
-                    "key": "id",
-                    "match": { "value": user_id }
-                }
-            }
-        })
-    ).await;
-    
-    match user_query_result {
-        Ok(result) => {
-            if let Some(points) = result["result"]["points"].as_array() {
-                if points.is_empty() {
-                    log::error!("VERIFICATION FAILED: User with ID {} not found in database!", user_id);
-                } else {
-                    log::info!("VERIFICATION SUCCESS: User with ID {} found in database", user_id);
-                    // Log the user data for verification
-                    log::debug!("User data: {}", serde_json::to_string_pretty(&points[0]["payload"]).unwrap_or_default());
-                }
-            } else {
-                log::error!("VERIFICATION FAILED: Unexpected response format from database");
-            }
-        },
-        Err(e) => {
-            log::error!("VERIFICATION ERROR: Failed to query user: {:#?}", e);
-        }
-    }
-    
-    // 2. Query for all users to diagnose potential issues
-    let all_users_query = qdrant_post(
-        &qdrant_path("collections/i/points/scroll").await?,
-        json!({
-            "filter": {
-                "must": [
-                    {
-                        "key": "s",
-                        "match": { "value": "u" }
-                    }
-                ]
-            },
-            "with_payload": false,
-            "limit": 100
-        })
-    ).await;
-    
-    match all_users_query {
-        Ok(result) => {
-            if let Some(points) = result["result"]["points"].as_array() {
-                log::info!("VERIFICATION: Found total of {} users in database", points.len());
-                for (i, point) in points.iter().enumerate().take(10) {
-                    if let Some(id) = point["id"].as_str() {
-                        log::debug!("User #{}: ID {}", i+1, id);
-                    }
-                }
-            } else {
-                log::error!("VERIFICATION FAILED: Unexpected response format when querying all users");
-            }
-        },
-        Err(e) => {
-            log::error!("VERIFICATION ERROR: Failed to query all users: {:#?}", e);
-        }
+    match repo::get::<User>(&user_id).await {
+        Ok(Some(_)) => log::info!("VERIFICATION SUCCESS: User with ID {} found in database", user_id),
+        Ok(None) => log::error!("VERIFICATION FAILED: User with ID {} not found in database!", user_id),
+        Err(e) => log::error!("VERIFICATION ERROR: Failed to query user: {:#?}", e),
     }
-    
-    // 3. Query for all points in the collection to understand the data structure
-    let all_points_query = qdrant_post(
-        &qdrant_path("collections/i/points/scroll").await?,
-        json!({
-            "limit": 100,
-            "with_payload": true
-        })
-    ).await;
-    
-    match all_points_query {
-        Ok(result) => {
-            if let Some(points) = result["result"]["points"].as_array() {
-                log::info!("VERIFICATION: Found total of {} points in collection i", points.len());
-                
-                // Count points by tenant
-                let mut tenant_counts = std::collections::HashMap::new();
-                for point in points {
-                    if let Some(tenant) = point["payload"]["s"].as_str() {
-                        *tenant_counts.entry(tenant.to_string()).or_insert(0) += 1;
-                    }
-                }
-                
-                log::info!("VERIFICATION: Tenant distribution in collection:");
-                for (tenant, count) in tenant_counts {
-                    log::info!("  Tenant '{}': {} points", tenant, count);
-                }
-            } else {
-                log::error!("VERIFICATION FAILED: Unexpected response format when querying all points");
-            }
-        },
-        Err(e) => {
-            log::error!("VERIFICATION ERROR: Failed to query all points: {:#?}", e);
-        }
-    }
-    
-    log::info!("Verification completed in {:?}", verify_start.elapsed());
+
     log::info!("Complete OAuth flow processed in {:?}", start_time.elapsed());
     
+    let tokens = session::issue(&user_id).await?;
+    
     Ok(json!({
         "user_id": user_id,
-        "user": user_info
+        "user": user_info,
+        "access_token": tokens.access_token,
+        "refresh_token": tokens.refresh_token,
+        "expires_in": tokens.expires_in
     }))
 }
 
@@ -556,100 +362,29 @@ async fn find_user_by_google_id(google_id: &str) -> AppResult<Option<String>> {
     log::debug!("Searching for existing user with Google ID: {}", masked_id);
     let start_time = Instant::now();
     
-    // Build the query using the index
-    let query = json!({
-        "filter": {
-            "must": [
-                {
-                    "key": "google_id",
-                    "match": {
-                        "value": google_id
-                    }
-                },
-                {
-                    "key": "s",
-                    "match": {
-                        "value": "u"  // tenant id for users
-                    }
-                }
-            ]
-        },
-        "limit": 1,
-        "with_payload": true
-    });
-    
-    log::debug!("Query for user: {}", serde_json::to_string_pretty(&query).unwrap_or_default());
-    
-    // Query Qdrant to find user with matching Google ID
-    let query_path = match qdrant_path("collections/i/points/scroll").await {
-        Ok(path) => path,
-        Err(e) => {
-            log::error!("Failed to construct Qdrant path: {:#?}", e);
-            return Err(AppError::new("Failed to construct Qdrant path", e));
-        }
-    };
-    
-    log::debug!("Executing query to path: {}", query_path);
-    
-    let search_result = qdrant_post(&query_path, query)
-        .await
-        .map_err(|e| {
-            log::error!("Database search error for Google ID {}: {:#?}", masked_id, e);
-            AppError::new(&format!("Failed to search for user with Google ID: {}", masked_id), e)
-        })?;
-    
+    let (users, _) = repo::scroll::<User>(
+        repo::Filter::new().must(Condition::matches(keys::GOOGLE_ID, google_id)),
+        1,
+        None,
+    )
+    .await
+    .map_err(|e| {
+        log::error!("Database search error for Google ID {}: {:#?}", masked_id, e);
+        AppError::new(&format!("Failed to search for user with Google ID: {}", masked_id), e)
+    })?;
+
     log::debug!("Database search completed in {:?}", start_time.elapsed());
-    
-    // Debug log the response structure
-    if log::log_enabled!(log::Level::Debug) {
-        let response_keys = if let Some(obj) = search_result.as_object() {
-            obj.keys().map(|k| k.to_string()).collect::<Vec<String>>().join(", ")
-        } else {
-            "not an object".to_string()
-        };
-        
-        log::debug!("Response top-level keys: {}", response_keys);
-        
-        if let Some(result) = search_result.get("result") {
-            let result_keys = if let Some(obj) = result.as_object() {
-                obj.keys().map(|k| k.to_string()).collect::<Vec<String>>().join(", ")
-            } else {
-                "not an object".to_string()
-            };
-            log::debug!("Response 'result' keys: {}", result_keys);
+
+    match users.into_iter().next() {
+        Some(user) => {
+            log::info!("Found existing user: {} for Google ID: {}", user.id, masked_id);
+            Ok(Some(user.id))
         }
-    }
-    
-    // Check if we found a user
-    if let Some(points) = search_result.get("result").and_then(|r| r.get("points")).and_then(|p| p.as_array()) {
-        log::debug!("Search returned {} points", points.len());
-        
-        if !points.is_empty() {
-            // Extract the user ID of the existing user
-            if let Some(user_id) = points[0].get("id").and_then(|id| id.as_str()) {
-                let found_id = user_id.to_string();
-                log::info!("Found existing user: {} for Google ID: {}", found_id, masked_id);
-                
-                // Log payload details
-                if let Some(payload) = points[0].get("payload") {
-                    log::debug!("User payload: {}", serde_json::to_string_pretty(payload).unwrap_or_default());
-                }
-                
-                return Ok(Some(found_id));
-            } else {
-                log::error!("Invalid user ID format in response");
-                return Err(AppError::new_plain("Invalid user ID format in response"));
-            }
+        None => {
+            log::debug!("No existing user found for Google ID: {}", masked_id);
+            Ok(None)
         }
-    } else if !search_result.get("result").is_some() {
-        log::error!("Unexpected response format: 'result' field missing");
-        log::debug!("Raw response: {}", serde_json::to_string(&search_result).unwrap_or_default());
-        return Err(AppError::new_plain("Unexpected response format from database"));
     }
-    
-    log::debug!("No existing user found for Google ID: {}", masked_id);
-    // No user found
-    Ok(None)
 }
 
 // Renamed from store_user to create_user to be more explicit
@@ -674,102 +409,32 @@ async fn create_user(user_info: &GoogleUser) -> AppResult<String> {
     let user_id = id();
     log::debug!("Generated new user ID: {}", user_id);
     
-    // Create a dummy vector with correct dimensions (768 for Gemini embeddings)
-    let dummy_vector: Vec<f32> = vec![0.0; 768];
-    
+    // Create a dummy vector with the configured embedder's dimensions
+    let dummy_vector: Vec<f32> = vec![0.0; embed::dimension().await?];
+
     // Prepare username from email
     let username = user_info.email.split('@').next().unwrap_or(&user_info.email);
-    
-    log::debug!("Storing user in database with payload - Name: {}, Username: {}", 
+
+    log::debug!("Storing user in database with payload - Name: {}, Username: {}",
                user_info.name, username);
-    
-    // Prepare the user point payload - Include ids field as required by Qdrant API
-    let request_body = json!({
-        "points": [
-            {
-                "id": user_id,
-                "vector": dummy_vector,
-                "payload": {
-                    "google_id": user_info.id,
-                    "email": user_info.email,
-                    "n": user_info.name,  // name
-                    "i": [user_info.picture],  // images
-                    "t": "",  // description (empty for now)
-                    "l": "",  // location (empty for now)
-                    "u": username,  // username from email
-                    "z": null,  // zone (none initially)
-                    "p": {"lat": 0.0, "lng": 0.0},  // position (default for now)
-                    "s": "u"  // tenant id for users
-                }
-            }
-        ]
-    });
-    
-    // Log the exact payload being sent (with sensitive info masked)
-    let debug_payload = json!({
-        "ids": [user_id],
-        "points": [
-            {
-                "id": user_id,
-                "vector": "... dummy vector ...",
-                "payload": {
-                    "google_id": masked_id,
-                    "email": masked_email,
-                    "n": user_info.name,
-                    "i": [user_info.picture],
-                    "t": "",
-                    "l": "",
-                    "u": username,
-                    "z": null,
-                    "p": {"lat": 0.0, "lng": 0.0},
-                    "s": "u"
-                }
-            }
-        ]
-    });
-    
-    log::debug!("User point payload: {}", serde_json::to_string_pretty(&debug_payload).unwrap_or_default());
-    
-    // Perform the database operation with enhanced error handling
-    let db_operation_start = Instant::now();
-    
-    let result = qdrant_put(
-        &qdrant_path("collections/i/points?wait=true").await?,
-        request_body
-    ).await;
 
-    log::debug!("---add user result: {:#?}", result);    
-    
-    match &result {
-        Ok(response) => {
-            log::debug!("Qdrant insert response: {}", serde_json::to_string(response).unwrap_or_default());
-            log::info!("User successfully inserted into database");
-        },
-        Err(e) => {
-            log::error!("CRITICAL: Failed to store user in database: {:#?}", e);
-            log::error!("Error context: User ID: {}, Google ID: {}", user_id, masked_id);
-            
-            // Check if the collection exists
-            log::debug!("Checking if collection 'i' exists...");
-            match qdrant_post(
-                &qdrant_path("collections").await?,
-                json!({})
-            ).await {
-                Ok(collections) => {
-                    log::debug!("Collections response: {}", serde_json::to_string(&collections).unwrap_or_default());
-                },
-                Err(e) => {
-                    log::error!("Failed to check collections: {:#?}", e);
-                }
-            }
-        }
-    }
-    
-    // Propagate any error from the database operation
-    result.map_err(|e| {
+    let user = User {
+        name: user_info.name.clone(),
+        images: vec![user_info.picture.clone()],
+        username: username.to_string(),
+        position: Some(Position { lat: 0.0, lng: 0.0 }), // default for now
+        google_id: Some(user_info.id.clone()),
+        email: Some(user_info.email.clone()),
+        ..Default::default()
+    };
+
+    let db_operation_start = Instant::now();
+    repo::upsert(&user_id, dummy_vector, &user).await.map_err(|e| {
+        log::error!("CRITICAL: Failed to store user in database: {:#?}", e);
+        log::error!("Error context: User ID: {}, Google ID: {}", user_id, masked_id);
         AppError::new("Failed to store user in database", e)
     })?;
-    
+
     log::debug!("Database operation completed in {:?}", db_operation_start.elapsed());
     log::info!("User created successfully in {:?} - ID: {}", start_time.elapsed(), user_id);
 
@@ -799,7 +464,7 @@ async fn create_user(user_info: &GoogleUser) -> AppResult<String> {
 }
 
 // Function to find an existing user or create a new one
-async fn find_or_create_user(user_info: &GoogleUser) -> AppResult<String> {
+pub async fn find_or_create_user(user_info: &GoogleUser) -> AppResult<String> {
     let masked_id = if user_info.id.len() > 6 {
         format!("{}***", &user_info.id[0..6])
     } else {
@@ -821,17 +486,11 @@ async fn find_or_create_user(user_info: &GoogleUser) -> AppResult<String> {
             // Update the user's last login time (could be extended for other fields)
             log::debug!("Updating user's last login timestamp");
             
-            // Add wait=true for the update operation
-            let update_path = qdrant_path(&format!("collections/i/points/{}/payload?wait=true", existing_user_id)).await?;
-            let update_result = qdrant_post(
-                &update_path,
-                json!({
-                    "payload": {
-                        "last_login": chrono::Utc::now().to_rfc3339()
-                    }
-                })
+            let update_result = repo::set_payload::<User>(
+                &[existing_user_id.as_str()],
+                &UserPatch { last_login: Some(chrono::Utc::now().to_rfc3339()), ..Default::default() },
             ).await;
-            
+
             if let Err(e) = update_result {
                 log::warn!("Failed to update user's last login timestamp: {:#?}", e);
                 // Continue anyway as this is not critical
@@ -879,109 +538,4 @@ async fn find_or_create_user(user_info: &GoogleUser) -> AppResult<String> {
     
     create_result
 }
-
-// Create required indexes for Qdrant
-async fn create_required_indexes() -> AppResult<()> {
-    log::info!("Creating required indexes for Google authentication");
-    let start_time = Instant::now();
-    
-    // Check if collection exists
-    let collection_check = qdrant_post(
-        &qdrant_path("collections/i").await?,
-        json!({})
-    ).await;
-    
-    match collection_check {
-        Ok(_) => log::info!("Collection 'i' exists"),
-        Err(e) => {
-            log::info!("Collection 'i' doesn't exist or error checking: {}", e);
-            
-            // Create collection if it doesn't exist - add wait=true
-            let create_result = qdrant_put(
-                &qdrant_path("collections/i?wait=true").await?,
-                json!({
-                    "vectors": {
-                        "size": 768,
-                        "distance": "Cosine"
-                    }
-                })
-            ).await;
-            
-            match create_result {
-                Ok(_) => log::info!("Created collection 'i'"),
-                Err(e) => {
-                    log::error!("Failed to create collection: {}", e);
-                    // Continue anyway to try creating indexes
-                }
-            }
-        }
-    }
-    
-    // Create index for google_id - add wait=true
-    log::info!("Creating index for google_id");
-    let google_id_result = qdrant_put(
-        &qdrant_path("collections/i/index?wait=true").await?,
-        json!({
-            "field_name": "google_id",
-            "field_schema": "keyword"
-        })
-    ).await;
-    
-    match google_id_result {
-        Ok(_) => log::info!("Successfully created google_id index"),
-        Err(e) => {
-            if e.to_string().contains("already exists") {
-                log::info!("Index for google_id already exists");
-            } else {
-                log::error!("Failed to create google_id index: {}", e);
-                // Continue to create other indexes
-            }
-        }
-    }
-    
-    // Create index for s (tenant) - add wait=true
-    log::info!("Creating index for tenant field 's'");
-    let tenant_result = qdrant_put(
-        &qdrant_path("collections/i/index?wait=true").await?,
-        json!({
-            "field_name": "s",
-            "field_schema": "keyword"
-        })
-    ).await;
-    
-    match tenant_result {
-        Ok(_) => log::info!("Successfully created tenant 's' index"),
-        Err(e) => {
-            if e.to_string().contains("already exists") {
-                log::info!("Index for tenant 's' already exists");
-            } else {
-                log::error!("Failed to create tenant index: {}", e);
-                // Continue to create other indexes
-            }
-        }
-    }
-    
-    // Create index for id field - add wait=true
-    log::info!("Creating index for id field");
-    let id_result = qdrant_put(
-        &qdrant_path("collections/i/index?wait=true").await?,
-        json!({
-            "field_name": "id",
-            "field_schema": "keyword"
-        })
-    ).await;
-    
-    match id_result {
-        Ok(_) => log::info!("Successfully created id index"),
-        Err(e) => {
-            if e.to_string().contains("already exists") {
-                log::info!("Index for id already exists");
-            } else {
-                log::error!("Failed to create id index: {}", e);
-            }
-        }
-    }
-    
-    log::info!("Index creation completed in {:?}", start_time.elapsed());
-    Ok(())
-} 
\ No newline at end of file
+ 
\ No newline at end of file
@@ -1,8 +1,9 @@
 pub mod login;
 pub mod google;
+pub mod session;
 
 use warp::Filter;
 
 pub fn routes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    google::routes()
+    google::routes().or(session::routes())
 } 
\ No newline at end of file
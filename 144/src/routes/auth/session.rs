@@ -0,0 +1,49 @@
+use serde::Deserialize;
+use serde_json::json;
+use warp::{Filter, Rejection, Reply};
+
+use crate::util::{session, with_session, AppResult};
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+pub fn routes() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    refresh_route().or(logout_route())
+}
+
+/// POST /auth/refresh with `{refresh_token}`, returns a new token pair
+pub fn refresh_route() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path!("auth" / "refresh")
+        .and(warp::post())
+        .and(warp::body::json())
+        .then(r_refresh)
+}
+
+/// POST /auth/logout, revokes the session of the bearer token
+pub fn logout_route() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path!("auth" / "logout")
+        .and(warp::post())
+        .and(with_session())
+        .then(r_logout)
+}
+
+pub async fn r_refresh(request: RefreshRequest) -> impl Reply {
+    session::refresh(&request.refresh_token).await.map_or_else(
+        |e| e.reply(),
+        |v| warp::reply::with_status(warp::reply::json(&v), warp::http::StatusCode::OK),
+    )
+}
+
+pub async fn r_logout(claims: session::Claims) -> impl Reply {
+    f_logout(claims).await.map_or_else(
+        |e| e.reply(),
+        |v| warp::reply::with_status(warp::reply::json(&v), warp::http::StatusCode::OK),
+    )
+}
+
+async fn f_logout(claims: session::Claims) -> AppResult<serde_json::Value> {
+    session::revoke(&claims).await?;
+    Ok(json!({"success": true}))
+}
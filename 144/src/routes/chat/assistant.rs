@@ -0,0 +1,103 @@
+//! LLM help for chat sessions, through [`util::groq::llm`](crate::util::groq::llm).
+//!
+//! Both features are opt-in with the `icebreakers` and `summaries` flags of `/chat/match`,
+//! and both use what the two users shared, so they only run when both opted in. A new
+//! session gets [`icebreakers`] from the users' descriptions and interests. When a session
+//! ends, [`summarize`] writes a short summary and topic tags to its stored record.
+
+use serde_json::Value;
+
+use super::storage;
+use super::types::{ChatSession, MessageType, UserProfile};
+use crate::util::groq::{llm, reply_json, Message};
+use crate::util::AppResult;
+
+pub const MAX_ICEBREAKERS: usize = 3;
+
+/// the most recent messages a summary is written from
+const SUMMARY_MESSAGES: usize = 200;
+
+const MAX_TOPICS: usize = 5;
+
+const ICEBREAKER_PROMPT: &str = "Two strangers were just matched in an anonymous chat. From what each wrote about \
+themselves, suggest three short, friendly opening lines either of them could send, drawing on what they have in \
+common. Don't mention that you read their profiles. Reply with only a JSON array of strings.";
+
+const SUMMARY_PROMPT: &str = "Summarize this chat between two strangers, A and B, in one or two neutral sentences, \
+without names, contact details or quotes. Then give up to five one or two word topic tags in lowercase. Reply with \
+only JSON like {\"summary\": \"...\", \"topics\": [\"...\"]}.";
+
+fn system(content: &str) -> Message {
+    Message { role: "system".to_string(), content: content.to_string() }
+}
+
+fn user(content: String) -> Message {
+    Message { role: "user".to_string(), content }
+}
+
+fn profile(label: &str, profile: &UserProfile) -> String {
+    format!("{}: {}\nInterests: {}", label, profile.description.trim(), profile.interests.join(", "))
+}
+
+/// Opening lines for `a` and `b`
+pub async fn icebreakers(a: &UserProfile, b: &UserProfile) -> AppResult<Vec<String>> {
+    let prompt = format!("{}\n\n{}", profile("First", a), profile("Second", b));
+    let reply = llm().chat(vec![system(ICEBREAKER_PROMPT), user(prompt)]).await?;
+    let suggestions = match reply_json(&reply, '[', ']')? {
+        Value::Array(lines) => lines
+            .iter()
+            .filter_map(Value::as_str)
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .take(MAX_ICEBREAKERS)
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    };
+    Ok(suggestions)
+}
+
+/// Writes a summary and topics to the ended session's record, returning the updated
+/// session, or `None` when there was too little conversation to summarize
+pub async fn summarize(session: &ChatSession) -> AppResult<Option<ChatSession>> {
+    let page = storage::get_session_messages(&session.id, None, SUMMARY_MESSAGES).await?;
+    let transcript: Vec<String> = page
+        .messages
+        .iter()
+        .filter(|m| m.message_type == MessageType::Text && m.deleted_at.is_none())
+        .filter_map(|m| {
+            let speaker = if m.sender_id == session.user1_id {
+                "A"
+            } else if m.sender_id == session.user2_id {
+                "B"
+            } else {
+                return None;
+            };
+            Some(format!("{}: {}", speaker, m.message))
+        })
+        .collect();
+    // a greeting each way isn't a conversation
+    if transcript.len() < 3 {
+        return Ok(None);
+    }
+
+    let reply = llm().chat(vec![system(SUMMARY_PROMPT), user(transcript.join("\n"))]).await?;
+    let parsed = reply_json(&reply, '{', '}')?;
+    let Some(summary) = parsed["summary"].as_str().map(str::trim).filter(|s| !s.is_empty()) else {
+        return Ok(None);
+    };
+
+    let mut session = session.clone();
+    session.summary = Some(summary.to_string());
+    session.topics = parsed["topics"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(|topic| topic.trim().to_lowercase())
+        .filter(|topic| !topic.is_empty())
+        .take(MAX_TOPICS)
+        .collect();
+    storage::save_session(&session).await?;
+    Ok(Some(session))
+}
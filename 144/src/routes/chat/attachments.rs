@@ -0,0 +1,100 @@
+//! Files sent in chat.
+//!
+//! An upload goes to the attachment bucket (see [`util::objects`](crate::util::objects))
+//! under `chat/<session id>/<id>` and becomes a [`MessageType::Attachment`] message in the
+//! session. Only files up to [`MAX_BYTES`] with a type in [`ALLOWED_TYPES`] are taken. The
+//! bucket is private, so participants of the session fetch a file through a presigned URL
+//! that expires after [`URL_TTL`].
+
+use std::time::Duration;
+use warp::http::StatusCode;
+
+use super::matching::ACTIVE_SESSIONS;
+use super::storage::{self, not_participant};
+use super::types::{ChatMessage, MessageType};
+use crate::util::{self, objects::objects, AppError, AppResult};
+
+pub const MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+pub const ALLOWED_TYPES: &[&str] = &[
+    "image/jpeg",
+    "image/png",
+    "image/gif",
+    "image/webp",
+    "application/pdf",
+    "text/plain",
+    "audio/mpeg",
+    "audio/ogg",
+    "audio/webm",
+    "video/mp4",
+    "video/webm",
+];
+
+pub const URL_TTL: Duration = Duration::from_secs(300);
+
+/// `image/PNG; charset=x` -> `image/png`
+fn mime_type(content_type: &str) -> String {
+    content_type.split(';').next().unwrap_or_default().trim().to_lowercase()
+}
+
+/// Stores the file and sends it to the user's active session as an attachment message
+pub async fn upload(
+    user_id: &str,
+    session_id: &str,
+    file_name: Option<String>,
+    content_type: &str,
+    body: Vec<u8>,
+) -> AppResult<ChatMessage> {
+    let active = ACTIVE_SESSIONS
+        .lock()
+        .await
+        .get(session_id)
+        .is_some_and(|session| session.active && session.has_participant(user_id));
+    if !active {
+        return Err(not_participant());
+    }
+
+    let mime_type = mime_type(content_type);
+    if !ALLOWED_TYPES.contains(&mime_type.as_str()) {
+        return Err(AppError::new_status(
+            &format!("Files of type {} can't be sent", mime_type),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        ));
+    }
+    let size = body.len() as u64;
+    if size == 0 {
+        return Err(AppError::new_status("The file is empty", StatusCode::BAD_REQUEST));
+    }
+    if size > MAX_BYTES {
+        return Err(AppError::new_status(
+            &format!("Files can be at most {} MB", MAX_BYTES / 1024 / 1024),
+            StatusCode::PAYLOAD_TOO_LARGE,
+        ));
+    }
+
+    let key = format!("chat/{}/{}", session_id, util::id());
+    objects().await?.put(&key, body, &mime_type).await?;
+
+    let message = ChatMessage::new(
+        session_id.to_string(),
+        user_id.to_string(),
+        file_name.unwrap_or_default(),
+        MessageType::Attachment { key, mime_type, size },
+    );
+    storage::save_message(message).await
+}
+
+/// A short-lived URL to the file of an attachment message, for participants of its session
+pub async fn url(user_id: &str, message_id: &str) -> AppResult<String> {
+    let message = storage::get_message(message_id).await?;
+    if !storage::is_participant(&message.session_id, user_id).await? {
+        return Err(not_participant());
+    }
+    if message.deleted_at.is_some() {
+        return Err(AppError::new_status("Message was deleted", StatusCode::GONE));
+    }
+    match &message.message_type {
+        MessageType::Attachment { key, .. } => objects().await?.presigned_get(key, URL_TTL),
+        _ => Err(AppError::new_status("Message has no attachment", StatusCode::NOT_FOUND)),
+    }
+}
@@ -1,7 +1,10 @@
-use super::types::{MatchRequest, MatchResponse, UserProfile, ChatSession};
-use crate::util::{AppResult, AppError, embed};
-use crate::util::qdrant::{qdrant_path, qdrant_put, qdrant_post};
-use crate::constants::SECRETS;
+use super::types::{AgeRange, MatchRequest, MatchResponse, UserProfile, ChatSession, ServerEvent, SessionOutcome};
+use crate::repo::{store, Condition, Filter, Point, Range, Selector};
+use super::{assistant, matchmaker, storage, websocket};
+use crate::util::{AppError, AppResult, embed};
+use warp::http::StatusCode;
+use crate::repo::schema::CHAT_USERS_COLLECTION;
+use crate::routes::moderation::blocks;
 use serde_json::{json, Value};
 use std::convert::Infallible;
 use std::sync::Arc;
@@ -11,45 +14,54 @@ use uuid::Uuid;
 
 // Global state for managing waiting users and active sessions
 lazy_static::lazy_static! {
-    static ref WAITING_USERS: Arc<Mutex<Vec<UserProfile>>> = Arc::new(Mutex::new(Vec::new()));
+    pub static ref WAITING_USERS: Arc<Mutex<Vec<UserProfile>>> = Arc::new(Mutex::new(Vec::new()));
     pub static ref ACTIVE_SESSIONS: Arc<Mutex<HashMap<String, ChatSession>>> = Arc::new(Mutex::new(HashMap::new()));
 }
 
-pub async fn find_match(request: MatchRequest) -> Result<impl warp::Reply, Infallible> {
-    match find_match_internal(request).await {
+pub async fn find_match(request: MatchRequest, user_id: String) -> Result<impl warp::Reply, Infallible> {
+    match find_match_internal(request, user_id).await {
         Ok(response) => Ok(warp::reply::with_status(
             warp::reply::json(&response),
             warp::http::StatusCode::OK,
         )),
         Err(e) => {
-            eprintln!("Error in find_match: {}", e);
+            log::error!("Error in find_match: {:?}", e);
+            let message = if e.status().is_server_error() {
+                "Error finding match".to_string()
+            } else {
+                e.to_string()
+            };
             let error_response = MatchResponse {
                 match_found: false,
                 session_id: None,
                 partner_id: None,
-                message: "Error finding match".to_string(),
+                message,
             };
             Ok(warp::reply::with_status(
                 warp::reply::json(&error_response),
-                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                e.status(),
             ))
         }
     }
 }
 
-async fn find_match_internal(request: MatchRequest) -> AppResult<MatchResponse> {
-    // Create user profile
+async fn find_match_internal(request: MatchRequest, user_id: String) -> AppResult<MatchResponse> {
+    // Create user profile for the signed in user, so chat sockets can be checked against it
+    let age_range = request
+        .age_range
+        .as_deref()
+        .filter(|range| !range.trim().is_empty())
+        .map(str::parse::<AgeRange>)
+        .transpose()?;
     let mut user = UserProfile::new(
+        user_id,
         request.description.clone(),
         request.interests.clone(),
-        request.age_range.clone(),
+        age_range,
     );
-
-    // Get Gemini API key
-    let secrets = SECRETS.lock().await;
-    let api_key = secrets
-        .get("GEMINI_API_KEY")
-        .ok_or_else(|| AppError::new_plain("GEMINI_API_KEY not found in secrets"))?;
+    user.blocked = blocks::block_set(&user.id).await?;
+    user.icebreakers = request.icebreakers;
+    user.summaries = request.summaries;
 
     // Create embedding for user description
     let embedding_text = format!("{} {}", request.description, request.interests.join(" "));
@@ -59,53 +71,45 @@ async fn find_match_internal(request: MatchRequest) -> AppResult<MatchResponse>
     // Store user in Qdrant for future matching
     store_user_in_qdrant(&user).await?;
 
-    // Try to find a match from waiting users first
-    let mut waiting_users = WAITING_USERS.lock().await;
-    
-    // Look for compatible user in waiting queue
-    for (index, waiting_user) in waiting_users.iter().enumerate() {
-        if let Some(waiting_embedding) = &waiting_user.embedding {
-            let similarity = calculate_cosine_similarity(&embedding, waiting_embedding);
-            
-            // If similarity is above threshold (e.g., 0.7), create a match
-            if similarity > 0.7 {
-                let matched_user = waiting_users.remove(index);
-                drop(waiting_users); // Release the lock
-                
-                // Create chat session
-                let session = ChatSession::new(user.id.clone(), matched_user.id.clone());
-                
-                // Store session
-                let mut sessions = ACTIVE_SESSIONS.lock().await;
-                sessions.insert(session.id.clone(), session.clone());
-                
-                return Ok(MatchResponse {
-                    match_found: true,
-                    session_id: Some(session.id),
-                    partner_id: Some(matched_user.id),
-                    message: "Match found! You can start chatting.".to_string(),
-                });
-            }
+    // Waiting users the store ranks closest within the age range, searched before the
+    // queue is locked
+    let candidates: Vec<String> = match search_compatible_users(&user).await {
+        Ok(found) => found.into_iter().map(|u| u.id).collect(),
+        Err(e) => {
+            log::error!("Error searching compatible chat users: {:?}", e);
+            Vec::new()
         }
-    }
+    };
+    let config = matchmaker::config().await;
+
+    let mut waiting_users = WAITING_USERS.lock().await;
     
-    // No immediate match found, try Qdrant search
-    if let Ok(qdrant_match) = search_compatible_users(&user).await {
-        if !qdrant_match.is_empty() {
-            // For now, just add to waiting queue
-            // In a production system, you'd want to notify the matched user
-            waiting_users.push(user);
-            
-            return Ok(MatchResponse {
-                match_found: false,
-                session_id: None,
-                partner_id: None,
-                message: "Looking for matches... Please wait.".to_string(),
-            });
-        }
+    // The closest candidate still queued who may be paired now, else the best of the queue
+    let now = chrono::Utc::now().timestamp();
+    let partner = {
+        let recent = matchmaker::RECENT_PAIRS.lock().await;
+        candidates
+            .iter()
+            .filter_map(|id| waiting_users.iter().position(|w| &w.id == id))
+            .find(|&index| matchmaker::score(&user, &waiting_users[index], &recent, config, now).is_some())
+            .or_else(|| matchmaker::best_partner(&user, &waiting_users, &recent, config, now))
+    };
+    if let Some(index) = partner {
+        let matched_user = waiting_users.remove(index);
+        drop(waiting_users); // Release the lock
+        
+        let session = open_session(&user, &matched_user).await;
+        
+        return Ok(MatchResponse {
+            match_found: true,
+            session_id: Some(session.id),
+            partner_id: Some(matched_user.id),
+            message: "Match found! You can start chatting.".to_string(),
+        });
     }
     
-    // No match found, add to waiting queue
+    // No match found, wait in the queue; the matchmaker pushes a later match over the chat socket
+    waiting_users.retain(|w| w.id != user.id);
     waiting_users.push(user);
     
     Ok(MatchResponse {
@@ -116,77 +120,280 @@ async fn find_match_internal(request: MatchRequest) -> AppResult<MatchResponse>
     })
 }
 
-async fn store_user_in_qdrant(user: &UserProfile) -> AppResult<()> {
-    let collection_name = "chat_users";
+/// Creates a session for two matched users and tells both of them over their chat sockets
+pub async fn open_session(a: &UserProfile, b: &UserProfile) -> ChatSession {
+    let mut session = ChatSession::new(a.id.clone(), b.id.clone());
+    session.summarize = a.summaries && b.summaries;
+    ACTIVE_SESSIONS.lock().await.insert(session.id.clone(), session.clone());
+    if let Err(e) = storage::save_session(&session).await {
+        log::error!("Error saving chat session {}: {:?}", session.id, e);
+    }
+    left_queue(&[&a.id, &b.id]).await;
     
-    // Create collection if it doesn't exist
-    let create_result = qdrant_put(
-        &qdrant_path(&format!("collections/{}?wait=true", collection_name)).await?,
-        json!({
-            "vectors": {
-                "size": 768,  // Gemini embedding dimension
-                "distance": "Cosine"
+    for (user, partner) in [(a, b), (b, a)] {
+        let found = ServerEvent::MatchFound {
+            session_id: session.id.clone(),
+            partner_id: partner.id.clone(),
+        };
+        if !websocket::notify(&user.id, &found).await {
+            log::info!("Matched user {} has no open chat socket", user.id);
+        }
+    }
+
+    if a.icebreakers && b.icebreakers {
+        let (a, b, session_id) = (a.clone(), b.clone(), session.id.clone());
+        tokio::spawn(async move {
+            match assistant::icebreakers(&a, &b).await {
+                Ok(suggestions) if !suggestions.is_empty() => {
+                    let event = ServerEvent::Icebreakers { session_id, suggestions };
+                    websocket::notify(&a.id, &event).await;
+                    websocket::notify(&b.id, &event).await;
+                }
+                Ok(_) => {}
+                Err(e) => log::error!("Error suggesting icebreakers: {:?}", e),
             }
-        }),
-    ).await;
+        });
+    }
     
-    // Ignore error if collection already exists
-    if let Err(e) = create_result {
-        if !e.to_string().contains("already exists") {
-            return Err(e);
+    session
+}
+
+/// Closes a session one of its participants ended. It leaves `ACTIVE_SESSIONS`, is stored
+/// with its duration and outcome, and the two users aren't matched again right away.
+pub async fn end_session(session_id: &str, user_id: &str, outcome: SessionOutcome) -> AppResult<ChatSession> {
+    let session = {
+        let mut sessions = ACTIVE_SESSIONS.lock().await;
+        match sessions.get(session_id) {
+            Some(session) if session.has_participant(user_id) => sessions.remove(session_id),
+            _ => None,
+        }
+    };
+    let Some(mut session) = session else {
+        if storage::is_participant(session_id, user_id).await? {
+            return Err(AppError::new_status("Session has already ended", StatusCode::GONE));
         }
+        return Err(storage::not_participant());
+    };
+
+    session.end(Some(user_id), outcome, chrono::Utc::now().timestamp());
+    close(&session).await;
+    Ok(session)
+}
+
+/// The user's active sessions, with their partner in each
+pub async fn sessions_of(user_id: &str) -> Vec<(String, String)> {
+    ACTIVE_SESSIONS
+        .lock()
+        .await
+        .values()
+        .filter(|session| session.has_participant(user_id))
+        .map(|session| (session.id.clone(), session.partner_of(user_id).to_string()))
+        .collect()
+}
+
+/// Ends sessions both participants left without ending them
+pub async fn abandon(session_ids: &[String]) {
+    let now = chrono::Utc::now().timestamp();
+    let abandoned: Vec<ChatSession> = {
+        let mut sessions = ACTIVE_SESSIONS.lock().await;
+        session_ids.iter().filter_map(|id| sessions.remove(id)).collect()
+    };
+    for mut session in abandoned {
+        session.end(None, SessionOutcome::Abandoned, now);
+        close(&session).await;
     }
-    
+}
+
+async fn close(session: &ChatSession) {
+    if let Err(e) = storage::save_session(session).await {
+        log::error!("Error saving ended chat session {}: {:?}", session.id, e);
+    }
+    matchmaker::record_ended(&session.user1_id, &session.user2_id).await;
+    log::info!(
+        "Chat session {} ended after {}s ({:?})",
+        session.id,
+        session.duration_secs.unwrap_or_default(),
+        session.outcome
+    );
+
+    if session.summarize {
+        let session = session.clone();
+        tokio::spawn(async move {
+            match assistant::summarize(&session).await {
+                Ok(Some(summarized)) => {
+                    let event = ServerEvent::SessionSummary {
+                        session_id: summarized.id.clone(),
+                        summary: summarized.summary.unwrap_or_default(),
+                        topics: summarized.topics,
+                    };
+                    websocket::notify(&session.user1_id, &event).await;
+                    websocket::notify(&session.user2_id, &event).await;
+                }
+                Ok(None) => {}
+                Err(e) => log::error!("Error summarizing chat session {}: {:?}", session.id, e),
+            }
+        });
+    }
+}
+
+/// Puts the user back into the waiting queue with the profile of their last `/chat/match`
+pub async fn requeue(user_id: &str) -> AppResult<()> {
+    let mut user = parse_users(store().retrieve(CHAT_USERS_COLLECTION, &[user_id], true).await?)
+        .pop()
+        .ok_or_else(|| AppError::new_status("Find a match with /chat/match first", StatusCode::NOT_FOUND))?;
+    user.created_at = chrono::Utc::now().timestamp();
+    user.blocked = blocks::block_set(user_id).await?;
+    store_user_in_qdrant(&user).await?;
+
+    let mut waiting_users = WAITING_USERS.lock().await;
+    waiting_users.retain(|w| w.id != user.id);
+    waiting_users.push(user);
+    Ok(())
+}
+
+/// Keeps two users who just blocked each other apart while they're queued
+pub async fn apply_block(a: &str, b: &str) {
+    for user in WAITING_USERS.lock().await.iter_mut() {
+        if user.id == a {
+            user.blocked.insert(b.to_string());
+        } else if user.id == b {
+            user.blocked.insert(a.to_string());
+        }
+    }
+}
+
+/// Takes the user out of the waiting queue, e.g. when their last chat socket closes
+pub async fn remove_waiting(user_id: &str) {
+    WAITING_USERS.lock().await.retain(|w| w.id != user_id);
+    left_queue(&[user_id]).await;
+}
+
+/// Records that these users are no longer waiting, so a restart doesn't queue them again
+pub(super) async fn left_queue(user_ids: &[&str]) {
+    let payload = json!({"waiting": false});
+    let result = store()
+        .set_payload(
+            CHAT_USERS_COLLECTION,
+            Selector::ids(user_ids),
+            payload.as_object().cloned().unwrap_or_default(),
+        )
+        .await;
+    if let Err(e) = result {
+        log::error!("Error taking {:?} out of the stored chat queue: {:?}", user_ids, e);
+    }
+}
+
+/// Reloads the sessions that were active and the users still inside the queue TTL when
+/// the process last stopped
+pub async fn restore() -> AppResult<()> {
+    let sessions = storage::load_active_sessions().await?;
+    let restored_sessions = sessions.len();
+    ACTIVE_SESSIONS
+        .lock()
+        .await
+        .extend(sessions.into_iter().map(|session| (session.id.clone(), session)));
+
+    let oldest = chrono::Utc::now().timestamp() - matchmaker::config().await.queue_ttl_secs;
+    let filter = Filter::new()
+        .must(Condition::matches("waiting", true))
+        .must(Condition::range("created_at", Range { gte: Some(oldest as f64), ..Default::default() }));
+    let mut ids = Vec::new();
+    let mut offset = None;
+    loop {
+        let (page, next) = store().scroll(CHAT_USERS_COLLECTION, &filter, 100, offset).await?;
+        ids.extend(page.into_iter().map(|point| point.id));
+        match next {
+            Some(next) => offset = Some(next),
+            None => break,
+        }
+    }
+    let points = store()
+        .retrieve(CHAT_USERS_COLLECTION, &ids.iter().map(String::as_str).collect::<Vec<_>>(), true)
+        .await?;
+    let mut restored_users = parse_users(points);
+    for user in &mut restored_users {
+        user.blocked = blocks::block_set(&user.id).await?;
+    }
+
+    let mut waiting_users = WAITING_USERS.lock().await;
+    for user in restored_users {
+        if !waiting_users.iter().any(|w| w.id == user.id) {
+            waiting_users.push(user);
+        }
+    }
+    log::info!(
+        "Restored {} chat sessions, {} users waiting",
+        restored_sessions,
+        waiting_users.len()
+    );
+    Ok(())
+}
+
+async fn store_user_in_qdrant(user: &UserProfile) -> AppResult<()> {
     // Store user point
     if let Some(embedding) = &user.embedding {
-        qdrant_put(
-            &qdrant_path(&format!("collections/{}/points?wait=true", collection_name)).await?,
-            json!({
-                "points": [{
-                    "id": user.id,
-                    "vector": embedding,
-                    "payload": {
-                        "description": user.description,
-                        "interests": user.interests,
-                        "age_range": user.age_range,
-                        "created_at": user.created_at
-                    }
-                }]
-            }),
-        ).await?;
+        let payload = json!({
+            "description": user.description,
+            "interests": user.interests,
+            "age_range": user.age_range,
+            "created_at": user.created_at,
+            "waiting": true,
+            "icebreakers": user.icebreakers,
+            "summaries": user.summaries
+        });
+        store()
+            .upsert(CHAT_USERS_COLLECTION, vec![Point {
+                id: user.id.clone(),
+                vector: Some(embedding.clone()),
+                payload: payload.as_object().cloned().unwrap_or_default(),
+                score: None,
+            }])
+            .await?;
     }
     
     Ok(())
 }
 
+/// Filter for stored chat users whose age range is compatible with `age_range`, as
+/// [`AgeRange::compatible`] decides for queued users
+pub fn age_filter(age_range: Option<&AgeRange>) -> Filter {
+    match age_range {
+        Some(range) => Filter::new()
+            .must(Condition::range("age_range.min", Range { lte: Some(range.max as f64), ..Default::default() }))
+            .must(Condition::range("age_range.max", Range { gte: Some(range.min as f64), ..Default::default() })),
+        None => Filter::new().must(Condition::is_null("age_range")),
+    }
+}
+
+/// Waiting chat users most similar to `user` that they may be matched with
 async fn search_compatible_users(user: &UserProfile) -> AppResult<Vec<UserProfile>> {
-    let collection_name = "chat_users";
-    
-    if let Some(embedding) = &user.embedding {
-        let search_result = qdrant_post(
-            &qdrant_path(&format!("collections/{}/points/query", collection_name)).await?,
-            json!({
-                "query": embedding,
-                "limit": 5,
-                "with_payload": true,
-                "filter": {
-                    "must_not": [{
-                        "key": "id",
-                        "match": { "value": user.id }
-                    }]
-                }
-            }),
-        ).await?;
-        
-        // Parse results (simplified for now)
-        // In a real implementation, you'd convert the Qdrant response to UserProfile structs
+    let Some(embedding) = &user.embedding else {
         return Ok(Vec::new());
-    }
+    };
+    
+    let filter = age_filter(user.age_range.as_ref())
+        .must(Condition::matches("waiting", true))
+        .must_not(Condition::has_id(std::iter::once(&user.id).chain(&user.blocked)));
+    let points = store().search(CHAT_USERS_COLLECTION, embedding, &filter, 5, 0).await?;
     
-    Ok(Vec::new())
+    Ok(parse_users(points))
+}
+
+/// Stored chat user points as profiles, with their embedding when the point has its vector
+fn parse_users(points: Vec<Point>) -> Vec<UserProfile> {
+    points
+        .into_iter()
+        .filter_map(|point| {
+            let mut payload = point.payload;
+            payload.insert("id".to_string(), point.id.into());
+            let mut user: UserProfile = serde_json::from_value(payload.into()).ok()?;
+            user.embedding = point.vector;
+            Some(user)
+        })
+        .collect()
 }
 
-fn calculate_cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+pub(super) fn calculate_cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     if a.len() != b.len() {
         return 0.0;
     }
@@ -0,0 +1,266 @@
+//! Pairs users waiting in the chat queue.
+//!
+//! `/chat/match` tries the queue once when a user arrives; after that the worker started by
+//! [`spawn`] rescans it on an interval. A pair's score mixes the cosine similarity of their
+//! descriptions with the Jaccard overlap of their interests, and only pairs whose age
+//! ranges are [compatible](AgeRange::compatible) are scored at all. The score a pair needs
+//! drops the longer either of them has waited, entries older than the queue TTL are dropped
+//! with a `queue_expired` event, and two users whose session just ended aren't paired again
+//! until the cooldown passes. Users who blocked each other are never paired.
+//!
+//! The numbers come from [`MatchConfig`], which `MATCH_*` secrets override.
+
+use once_cell::sync::Lazy;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::{Mutex, OnceCell};
+use tokio::time::Duration;
+
+use super::matching::{self, calculate_cosine_similarity, WAITING_USERS};
+use super::types::{AgeRange, ServerEvent, UserProfile};
+use super::websocket;
+use crate::constants::SECRETS;
+
+#[derive(Debug, Clone)]
+pub struct MatchConfig {
+    /// share of the score from interest overlap, the rest is description similarity
+    pub interest_weight: f32,
+    /// score a pair needs as soon as they queue
+    pub threshold: f32,
+    /// lowest the threshold relaxes to
+    pub min_threshold: f32,
+    /// how much the threshold drops per second waited
+    pub relax_per_sec: f32,
+    /// how long an entry stays in the queue
+    pub queue_ttl_secs: i64,
+    /// how long two users who just chatted are kept apart
+    pub rematch_cooldown_secs: i64,
+    /// how often the worker scans the queue
+    pub interval_secs: u64,
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        Self {
+            interest_weight: 0.3,
+            threshold: 0.7,
+            min_threshold: 0.3,
+            relax_per_sec: 0.002,
+            queue_ttl_secs: 600,
+            rematch_cooldown_secs: 3600,
+            interval_secs: 5,
+        }
+    }
+}
+
+impl MatchConfig {
+    /// the defaults, with any `MATCH_INTEREST_WEIGHT`, `MATCH_THRESHOLD`, `MATCH_MIN_THRESHOLD`,
+    /// `MATCH_RELAX_PER_SEC`, `MATCH_QUEUE_TTL_SECS`, `MATCH_REMATCH_COOLDOWN_SECS` or
+    /// `MATCH_INTERVAL_SECS` secret applied
+    pub async fn from_secrets() -> Self {
+        let secrets = SECRETS.lock().await;
+        let mut config = Self::default();
+        fn set<T: FromStr>(value: Option<String>, field: &mut T) {
+            if let Some(parsed) = value.and_then(|v| v.trim().parse().ok()) {
+                *field = parsed;
+            }
+        }
+        set(secrets.get("MATCH_INTEREST_WEIGHT"), &mut config.interest_weight);
+        set(secrets.get("MATCH_THRESHOLD"), &mut config.threshold);
+        set(secrets.get("MATCH_MIN_THRESHOLD"), &mut config.min_threshold);
+        set(secrets.get("MATCH_RELAX_PER_SEC"), &mut config.relax_per_sec);
+        set(secrets.get("MATCH_QUEUE_TTL_SECS"), &mut config.queue_ttl_secs);
+        set(secrets.get("MATCH_REMATCH_COOLDOWN_SECS"), &mut config.rematch_cooldown_secs);
+        set(secrets.get("MATCH_INTERVAL_SECS"), &mut config.interval_secs);
+        config.interest_weight = config.interest_weight.clamp(0.0, 1.0);
+        config
+    }
+
+    /// score needed after waiting `waited_secs`
+    pub fn threshold_after(&self, waited_secs: i64) -> f32 {
+        (self.threshold - self.relax_per_sec * waited_secs.max(0) as f32).max(self.min_threshold)
+    }
+}
+
+/// when each pair of users last ended a session together
+#[derive(Debug, Default)]
+pub struct RecentPairs {
+    ended: HashMap<(String, String), i64>,
+}
+
+fn pair_key(a: &str, b: &str) -> (String, String) {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+impl RecentPairs {
+    pub fn record(&mut self, a: &str, b: &str, now: i64) {
+        self.ended.insert(pair_key(a, b), now);
+    }
+
+    /// whether `a` and `b` ended a session within the last `cooldown_secs`
+    pub fn blocked(&self, a: &str, b: &str, now: i64, cooldown_secs: i64) -> bool {
+        self.ended
+            .get(&pair_key(a, b))
+            .is_some_and(|ended| now - ended < cooldown_secs)
+    }
+
+    pub fn prune(&mut self, now: i64, cooldown_secs: i64) {
+        self.ended.retain(|_, ended| now - *ended < cooldown_secs);
+    }
+}
+
+/// what one scan of the queue decided
+#[derive(Debug, Default)]
+pub struct Round {
+    pub pairs: Vec<(UserProfile, UserProfile)>,
+    pub expired: Vec<UserProfile>,
+}
+
+/// cosine similarity of two queued users' descriptions, 0 when either has no embedding
+pub fn similarity(a: &UserProfile, b: &UserProfile) -> f32 {
+    match (&a.embedding, &b.embedding) {
+        (Some(a), Some(b)) => calculate_cosine_similarity(a, b),
+        _ => 0.0,
+    }
+}
+
+/// Jaccard index of two interest lists, ignoring case and surrounding space; `None` when
+/// neither lists any
+pub fn interest_overlap(a: &[String], b: &[String]) -> Option<f32> {
+    let set = |interests: &[String]| -> HashSet<String> {
+        interests
+            .iter()
+            .map(|i| i.trim().to_lowercase())
+            .filter(|i| !i.is_empty())
+            .collect()
+    };
+    let (a, b) = (set(a), set(b));
+    let union = a.union(&b).count();
+    (union > 0).then(|| a.intersection(&b).count() as f32 / union as f32)
+}
+
+/// how well two users fit, mixing description similarity and interest overlap. Users who
+/// both left interests out are scored on their descriptions alone.
+pub fn match_score(a: &UserProfile, b: &UserProfile, config: &MatchConfig) -> f32 {
+    let similarity = similarity(a, b);
+    match interest_overlap(&a.interests, &b.interests) {
+        Some(overlap) => (1.0 - config.interest_weight) * similarity + config.interest_weight * overlap,
+        None => similarity,
+    }
+}
+
+/// the score of `a` and `b` if they may be paired now
+pub fn score(a: &UserProfile, b: &UserProfile, recent: &RecentPairs, config: &MatchConfig, now: i64) -> Option<f32> {
+    if a.id == b.id
+        || a.blocked.contains(&b.id)
+        || b.blocked.contains(&a.id)
+        || !AgeRange::compatible(a.age_range.as_ref(), b.age_range.as_ref())
+        || recent.blocked(&a.id, &b.id, now, config.rematch_cooldown_secs)
+    {
+        return None;
+    }
+    let waited = (now - a.created_at).max(now - b.created_at);
+    let score = match_score(a, b, config);
+    (score >= config.threshold_after(waited)).then_some(score)
+}
+
+/// index of the queued user who fits `user` best, if any may be paired with them now
+pub fn best_partner(
+    user: &UserProfile,
+    queue: &[UserProfile],
+    recent: &RecentPairs,
+    config: &MatchConfig,
+    now: i64,
+) -> Option<usize> {
+    queue
+        .iter()
+        .enumerate()
+        .filter_map(|(index, waiting)| Some((index, score(user, waiting, recent, config, now)?)))
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(index, _)| index)
+}
+
+/// Takes expired entries and the best disjoint pairs out of the queue, most similar first
+pub fn pick(queue: &mut Vec<UserProfile>, recent: &RecentPairs, config: &MatchConfig, now: i64) -> Round {
+    let (expired, waiting): (Vec<_>, Vec<_>) = queue
+        .drain(..)
+        .partition(|user| now - user.created_at > config.queue_ttl_secs);
+
+    let mut candidates = Vec::new();
+    for i in 0..waiting.len() {
+        for j in i + 1..waiting.len() {
+            if let Some(similarity) = score(&waiting[i], &waiting[j], recent, config, now) {
+                candidates.push((similarity, i, j));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut slots: Vec<Option<UserProfile>> = waiting.into_iter().map(Some).collect();
+    let mut pairs = Vec::new();
+    for (_, i, j) in candidates {
+        if slots[i].is_some() && slots[j].is_some() {
+            if let (Some(a), Some(b)) = (slots[i].take(), slots[j].take()) {
+                pairs.push((a, b));
+            }
+        }
+    }
+    queue.extend(slots.into_iter().flatten());
+
+    Round { pairs, expired }
+}
+
+static CONFIG: OnceCell<MatchConfig> = OnceCell::const_new();
+
+/// the matching config, read from the secrets on first use
+pub async fn config() -> &'static MatchConfig {
+    CONFIG.get_or_init(MatchConfig::from_secrets).await
+}
+
+pub static RECENT_PAIRS: Lazy<Arc<Mutex<RecentPairs>>> =
+    Lazy::new(|| Arc::new(Mutex::new(RecentPairs::default())));
+
+/// Remembers that `a` and `b` ended a session, so they aren't matched again right away
+pub async fn record_ended(a: &str, b: &str) {
+    RECENT_PAIRS.lock().await.record(a, b, chrono::Utc::now().timestamp());
+}
+
+/// Scans the queue once, opening sessions for new pairs and telling expired users
+pub async fn run_once() {
+    let config = config().await;
+    let now = chrono::Utc::now().timestamp();
+    let round = {
+        let mut queue = WAITING_USERS.lock().await;
+        let mut recent = RECENT_PAIRS.lock().await;
+        recent.prune(now, config.rematch_cooldown_secs);
+        pick(&mut queue, &recent, config, now)
+    };
+
+    if !round.expired.is_empty() {
+        let expired: Vec<&str> = round.expired.iter().map(|user| user.id.as_str()).collect();
+        matching::left_queue(&expired).await;
+    }
+    for user in round.expired {
+        websocket::notify(&user.id, &ServerEvent::QueueExpired).await;
+    }
+    for (a, b) in round.pairs {
+        log::info!("Matchmaker paired {} and {}", a.id, b.id);
+        matching::open_session(&a, &b).await;
+    }
+}
+
+/// Starts the background matchmaker
+pub fn spawn() {
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(Duration::from_secs(config().await.interval_secs.max(1)));
+        loop {
+            interval.tick().await;
+            run_once().await;
+        }
+    });
+}
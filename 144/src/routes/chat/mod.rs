@@ -1,11 +1,27 @@
+pub mod assistant;
+pub mod attachments;
 pub mod matching;
+pub mod matchmaker;
+pub mod registry;
 pub mod websocket;
 pub mod types;
 pub mod storage;
 
 use warp::Filter;
 use warp::filters::cors::cors;
-use crate::util::with_auth;
+use warp::http::header::{HeaderValue, SEC_WEBSOCKET_PROTOCOL};
+use warp::Reply;
+use bytes::Buf;
+use futures_util::StreamExt;
+use warp::http::StatusCode;
+use warp::multipart::FormData;
+use crate::util::{with_auth, with_ws_auth, AppError, WS_AUTH_PROTOCOL};
+use types::{MessagePage, MessageSearch, PageQuery};
+
+/// response header with the cursor for the previous (older) page of messages
+pub const BEFORE_CURSOR: &str = "x-before-cursor";
+/// response header with the cursor for the next (newer) page of messages
+pub const AFTER_CURSOR: &str = "x-after-cursor";
 
 pub fn routes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     match_route()
@@ -14,6 +30,9 @@ pub fn routes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Reje
         .or(search_messages_route())
         .or(mark_read_route())
         .or(unread_messages_route())
+        .or(unread_counts_route())
+        .or(upload_attachment_route())
+        .or(attachment_url_route())
 }
 
 fn match_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
@@ -21,6 +40,7 @@ fn match_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rej
         .and(warp::path("match"))
         .and(warp::post())
         .and(warp::body::json())
+        .and(with_auth())
         .and_then(matching::find_match)
 }
 
@@ -28,8 +48,19 @@ fn websocket_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp:
     warp::path("chat")
         .and(warp::path("ws"))
         .and(warp::ws())
-        .map(|ws: warp::ws::Ws| {
-            ws.on_upgrade(websocket::handle_websocket)
+        .and(with_ws_auth())
+        .and(warp::header::optional::<String>("sec-websocket-protocol"))
+        .map(|ws: warp::ws::Ws, user_id: String, protocols: Option<String>| {
+            let mut reply = ws
+                .on_upgrade(move |socket| websocket::handle_websocket(socket, user_id))
+                .into_response();
+            // a browser closes the socket unless the server accepts the subprotocol it offered
+            if protocols.is_some_and(|p| p.split(',').any(|p| p.trim() == WS_AUTH_PROTOCOL)) {
+                reply
+                    .headers_mut()
+                    .insert(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static(WS_AUTH_PROTOCOL));
+            }
+            reply
         })
 }
 
@@ -38,57 +69,60 @@ fn websocket_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp:
 fn get_messages_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("chat" / "messages" / String)
         .and(warp::get())
+        .and(warp::query::<PageQuery>())
         .and(with_auth())
         .and_then(
-            |session_id: String, user_id: String| async move {
-                let messages = storage::get_session_messages(&session_id).await
-                    .map_err(|e| {
-                        log::error!("Error fetching messages: {:?}", e);
-                        warp::reject::custom(crate::util::AppError::new_rejection("Failed to fetch messages"))
-                    })?;
-                
-                // Mark all messages as read by this user
-                let unread_message_ids = messages.iter()
-                    .filter(|m| !m.read_by.contains(&user_id))
-                    .map(|m| m.id.clone())
-                    .collect::<Vec<_>>();
+            |session_id: String, query: PageQuery, user_id: String| async move {
+                if !storage::is_participant(&session_id, &user_id).await.map_err(warp::reject::custom)? {
+                    return Err(warp::reject::custom(storage::not_participant()));
+                }
+                let cursor = query.cursor().map_err(warp::reject::custom)?;
+                let page = storage::get_session_messages(&session_id, cursor, query.limit()).await
+                    .map_err(warp::reject::custom)?;
                 
-                if !unread_message_ids.is_empty() {
-                    storage::mark_messages_as_read(&user_id, &unread_message_ids).await
+                // Mark the session read up to the newest message on the page
+                if let Some(newest) = page.messages.last().filter(|m| !m.read_by.contains(&user_id)) {
+                    storage::mark_messages_as_read(&user_id, std::slice::from_ref(&newest.id)).await
                         .map_err(|e| {
                             log::error!("Error marking messages as read: {:?}", e);
                             warp::reject::custom(crate::util::AppError::new_rejection("Failed to mark messages as read"))
                         })?;
                 }
                 
-                Ok::<_, warp::Rejection>(warp::reply::json(&messages))
+                Ok::<_, warp::Rejection>(page_reply(page))
             }
         )
 }
 
+/// The page's messages as a JSON array, with its cursors in the `X-Before-Cursor` and
+/// `X-After-Cursor` headers
+fn page_reply(page: MessagePage) -> warp::reply::Response {
+    let mut reply = warp::reply::json(&page.messages).into_response();
+    for (header, cursor) in [(BEFORE_CURSOR, page.before), (AFTER_CURSOR, page.after)] {
+        if let Some(value) = cursor.and_then(|c| HeaderValue::from_str(&c).ok()) {
+            reply.headers_mut().insert(header, value);
+        }
+    }
+    reply
+}
+
 fn search_messages_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("chat" / "search")
         .and(warp::post())
         .and(warp::body::json())
         .and(with_auth())
         .and_then(
-            |search: serde_json::Value, user_id: String| async move {
-                let query = search["query"].as_str()
-                    .ok_or_else(|| warp::reject::custom(crate::util::AppError::new_rejection("Missing query parameter")))?;
-                
-                let limit = search["limit"].as_u64().unwrap_or(20) as usize;
-                
-                let messages = storage::search_messages(&user_id, query, limit).await
-                    .map_err(|e| {
-                        log::error!("Error searching messages: {:?}", e);
-                        warp::reject::custom(crate::util::AppError::new_rejection("Failed to search messages"))
-                    })?;
+            |search: MessageSearch, user_id: String| async move {
+                let hits = storage::search_messages(&user_id, &search).await
+                    .map_err(warp::reject::custom)?;
                 
-                Ok::<_, warp::Rejection>(warp::reply::json(&messages))
+                Ok::<_, warp::Rejection>(warp::reply::json(&hits))
             }
         )
 }
 
+/// `{"session_id": ...}` marks the whole session read, `{"message_ids": [...]}` marks
+/// the session of each message read up to it
 fn mark_read_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("chat" / "mark-read")
         .and(warp::post())
@@ -96,6 +130,12 @@ fn mark_read_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp:
         .and(with_auth())
         .and_then(
             |body: serde_json::Value, user_id: String| async move {
+                if let Some(session_id) = body["session_id"].as_str() {
+                    storage::mark_session_read(&user_id, session_id).await
+                        .map_err(warp::reject::custom)?;
+                    return Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({ "success": true })));
+                }
+
                 let message_ids = body["message_ids"].as_array()
                     .ok_or_else(|| warp::reject::custom(crate::util::AppError::new_rejection("Missing message_ids parameter")))?
                     .iter()
@@ -115,17 +155,89 @@ fn mark_read_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp:
 
 fn unread_messages_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("chat" / "unread")
+        .and(warp::get())
+        .and(warp::query::<PageQuery>())
+        .and(with_auth())
+        .and_then(
+            |query: PageQuery, user_id: String| async move {
+                let cursor = query.cursor().map_err(warp::reject::custom)?;
+                let page = storage::get_unread_messages(&user_id, cursor, query.limit()).await
+                    .map_err(warp::reject::custom)?;
+                
+                Ok::<_, warp::Rejection>(page_reply(page))
+            }
+        )
+}
+
+fn unread_counts_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("chat" / "unread" / "counts")
         .and(warp::get())
         .and(with_auth())
         .and_then(
             |user_id: String| async move {
-                let messages = storage::get_unread_messages(&user_id).await
-                    .map_err(|e| {
-                        log::error!("Error fetching unread messages: {:?}", e);
-                        warp::reject::custom(crate::util::AppError::new_rejection("Failed to fetch unread messages"))
-                    })?;
+                let counts = storage::get_unread_counts(&user_id).await
+                    .map_err(warp::reject::custom)?;
+                
+                Ok::<_, warp::Rejection>(warp::reply::json(&counts))
+            }
+        )
+}
+
+/// multipart upload with the file in a part named `file`
+fn upload_attachment_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("chat" / "attachments" / String)
+        .and(warp::post())
+        .and(with_auth())
+        .and(warp::multipart::form().max_length(attachments::MAX_BYTES + 64 * 1024))
+        .and_then(
+            |session_id: String, user_id: String, form: FormData| async move {
+                let (file_name, content_type, body) = read_file(form).await
+                    .map_err(warp::reject::custom)?;
+                let message = attachments::upload(&user_id, &session_id, file_name, &content_type, body).await
+                    .map_err(warp::reject::custom)?;
+
+                if let Err(e) = websocket::broadcast_to_session(&session_id, message.clone()).await {
+                    log::error!("Error broadcasting attachment: {:?}", e);
+                }
                 
-                Ok::<_, warp::Rejection>(warp::reply::json(&messages))
+                Ok::<_, warp::Rejection>(warp::reply::json(&message))
             }
         )
-} 
\ No newline at end of file
+}
+
+/// file name, content type and bytes of the `file` part
+async fn read_file(mut form: FormData) -> Result<(Option<String>, String, Vec<u8>), AppError> {
+    let invalid = |e: warp::Error| AppError::new_status(&format!("Invalid upload: {}", e), StatusCode::BAD_REQUEST);
+    while let Some(part) = form.next().await {
+        let part = part.map_err(invalid)?;
+        if part.name() != "file" {
+            continue;
+        }
+        let file_name = part.filename().map(str::to_string);
+        let content_type = part.content_type().unwrap_or("application/octet-stream").to_string();
+        let mut body = Vec::new();
+        let mut stream = part.stream();
+        while let Some(chunk) = stream.next().await {
+            body.extend_from_slice(chunk.map_err(invalid)?.chunk());
+        }
+        return Ok((file_name, content_type, body));
+    }
+    Err(AppError::new_status("Missing file", StatusCode::BAD_REQUEST))
+}
+
+fn attachment_url_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("chat" / "attachments" / String)
+        .and(warp::get())
+        .and(with_auth())
+        .and_then(
+            |message_id: String, user_id: String| async move {
+                let url = attachments::url(&user_id, &message_id).await
+                    .map_err(warp::reject::custom)?;
+                
+                Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({
+                    "url": url,
+                    "expires_in": attachments::URL_TTL.as_secs(),
+                })))
+            }
+        )
+}
@@ -0,0 +1,153 @@
+//! Which sockets belong to which user, and to which participant of which chat session.
+//!
+//! A connection is registered under its authenticated user when it opens, so events like
+//! `match_found` can reach every socket of a user. It joins a session on `join_session`,
+//! after the user is checked against the session. Session events are only sent to connections
+//! that joined that session, and a connection whose channel is closed is dropped the first
+//! time a send to it fails.
+
+use std::collections::{HashMap, HashSet};
+use tokio::sync::mpsc::UnboundedSender;
+use warp::ws::Message;
+
+/// outbound half of a connection; a task per socket forwards it to the websocket
+pub type Outbox = UnboundedSender<Message>;
+
+struct Connection {
+    user_id: String,
+    outbox: Outbox,
+}
+
+#[derive(Default)]
+pub struct Registry {
+    /// connection id -> its user and outbox
+    connections: HashMap<String, Connection>,
+    /// user id -> the user's connections
+    users: HashMap<String, HashSet<String>>,
+    /// session id -> user id -> the user's connections in that session
+    sessions: HashMap<String, HashMap<String, HashSet<String>>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn connect(&mut self, connection_id: &str, user_id: &str, outbox: Outbox) {
+        let connection = Connection { user_id: user_id.to_string(), outbox };
+        self.connections.insert(connection_id.to_string(), connection);
+        self.users.entry(user_id.to_string()).or_default().insert(connection_id.to_string());
+    }
+
+    /// whether the user has a socket open
+    pub fn connected(&self, user_id: &str) -> bool {
+        self.users.contains_key(user_id)
+    }
+
+    /// adds the connection to the session as `user_id`; the caller checks the user belongs there
+    pub fn join(&mut self, session_id: &str, user_id: &str, connection_id: &str) {
+        if !self.connections.contains_key(connection_id) {
+            return;
+        }
+        self.sessions
+            .entry(session_id.to_string())
+            .or_default()
+            .entry(user_id.to_string())
+            .or_default()
+            .insert(connection_id.to_string());
+    }
+
+    /// whether this connection joined the session as `user_id`
+    pub fn joined(&self, session_id: &str, user_id: &str, connection_id: &str) -> bool {
+        self.sessions
+            .get(session_id)
+            .and_then(|users| users.get(user_id))
+            .is_some_and(|connections| connections.contains(connection_id))
+    }
+
+    /// removes the user's connections from the session
+    pub fn leave(&mut self, session_id: &str, user_id: &str) {
+        if let Some(users) = self.sessions.get_mut(session_id) {
+            users.remove(user_id);
+            if users.is_empty() {
+                self.sessions.remove(session_id);
+            }
+        }
+    }
+
+    /// forgets the connection, returning the (session id, user id) pairs it had joined
+    pub fn disconnect(&mut self, connection_id: &str) -> Vec<(String, String)> {
+        if let Some(connection) = self.connections.remove(connection_id) {
+            if let Some(connections) = self.users.get_mut(&connection.user_id) {
+                connections.remove(connection_id);
+                if connections.is_empty() {
+                    self.users.remove(&connection.user_id);
+                }
+            }
+        }
+        let mut left = Vec::new();
+        self.sessions.retain(|session_id, users| {
+            users.retain(|user_id, connections| {
+                if connections.remove(connection_id) {
+                    left.push((session_id.clone(), user_id.clone()));
+                }
+                !connections.is_empty()
+            });
+            !users.is_empty()
+        });
+        left
+    }
+
+    /// users with a connection in the session
+    pub fn members(&self, session_id: &str) -> Vec<String> {
+        self.sessions
+            .get(session_id)
+            .map(|users| users.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// sends to one connection, dropping it if it's closed
+    pub fn send_to(&mut self, connection_id: &str, message: &Message) -> bool {
+        let sent = self
+            .connections
+            .get(connection_id)
+            .is_some_and(|connection| connection.outbox.send(message.clone()).is_ok());
+        if !sent {
+            self.disconnect(connection_id);
+        }
+        sent
+    }
+
+    /// sends to every connection of the user, returning how many it reached
+    pub fn send_to_user(&mut self, user_id: &str, message: &Message) -> usize {
+        let targets: Vec<String> = self
+            .users
+            .get(user_id)
+            .map(|connections| connections.iter().cloned().collect())
+            .unwrap_or_default();
+        self.send_all(targets, message)
+    }
+
+    /// sends to every connection in the session, returning how many it reached.
+    /// Connections that can't be sent to are dropped.
+    pub fn send(&mut self, session_id: &str, message: &Message) -> usize {
+        let targets: Vec<String> = self
+            .sessions
+            .get(session_id)
+            .map(|users| users.values().flatten().cloned().collect())
+            .unwrap_or_default();
+        self.send_all(targets, message)
+    }
+
+    fn send_all(&mut self, targets: Vec<String>, message: &Message) -> usize {
+        let mut delivered = 0;
+        for connection_id in targets {
+            if self.send_to(&connection_id, message) {
+                delivered += 1;
+            } else {
+                log::info!("Dropped closed chat connection {}", connection_id);
+            }
+        }
+        delivered
+    }
+}
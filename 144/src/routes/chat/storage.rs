@@ -1,389 +1,633 @@
-use crate::util::{AppResult, AppError, embed::embed, qdrant::{qdrant_path, qdrant_post, qdrant_put, qdrant_get}};
-use super::types::{ChatMessage, MessageType};
+use crate::repo::{filter::words, store, Condition, Direction, Filter, OrderBy, Point, Range, Selector};
+use crate::util::{stable_id, AppResult, AppError, embed::{self, embed}, objects::objects};
+use super::types::{ChatMessage, ChatSession, Cursor, Fragment, MessagePage, MessageSearch, MessageType, SearchHit};
+use once_cell::sync::Lazy;
 use serde_json::json;
-use uuid::Uuid;
-use futures::future::join_all;
-
-const CHAT_COLLECTION: &str = "messages";
-
-/// Initialize the messages collection in Qdrant
-pub async fn init_messages_collection() -> AppResult<()> {
-    // Check if collection exists first
-    let collections_url = qdrant_path("collections").await?;
-    let collections = qdrant_get(&collections_url).await?;
-    
-    let collections_list = collections["result"]["collections"].as_array()
-        .ok_or_else(|| AppError::new_plain("Invalid response format"))?;
-    
-    let collection_exists = collections_list.iter()
-        .any(|c| c["name"].as_str().unwrap_or("") == CHAT_COLLECTION);
-    
-    if !collection_exists {
-        // Create the messages collection
-        let create_url = qdrant_path(&format!("collections/{}", CHAT_COLLECTION)).await?;
-        let create_body = json!({
-            "vectors": {
-                "size": 768,
-                "distance": "Cosine"
-            },
-            "optimizers_config": {
-                "indexed_only": false
-            },
-            "shard_number": 1,
-            "on_disk_payload": true
-        });
-        
-        qdrant_put(&create_url, create_body).await?;
-        
-        // Create necessary payload indexes
-        let index_url = qdrant_path(&format!("collections/{}/index", CHAT_COLLECTION)).await?;
-        
-        // Create index for session_id
-        let session_id_index = json!({
-            "field_name": "session_id",
-            "field_schema": "keyword"
-        });
-        qdrant_put(&index_url, session_id_index).await?;
-        
-        // Create index for sender_id
-        let sender_id_index = json!({
-            "field_name": "sender_id",
-            "field_schema": "keyword"
-        });
-        qdrant_put(&index_url, sender_id_index).await?;
-        
-        // Create index for timestamp
-        let timestamp_index = json!({
-            "field_name": "timestamp",
-            "field_schema": "integer"
-        });
-        qdrant_put(&index_url, timestamp_index).await?;
-        
-        // Create index for read_by (for unread messages)
-        let read_by_index = json!({
-            "field_name": "read_by",
-            "field_schema": "keyword"
-        });
-        qdrant_put(&index_url, read_by_index).await?;
-    }
-    
-    Ok(())
-}
+use std::collections::{hash_map::DefaultHasher, HashMap};
+use std::hash::{Hash, Hasher};
+use tokio::sync::Mutex;
+use warp::http::StatusCode;
+
+pub const CHAT_COLLECTION: &str = "messages";
+pub const SESSIONS_COLLECTION: &str = "sessions";
+/// one point per user and session: how far the user has read it
+pub const READS_COLLECTION: &str = "chat_reads";
 
 /// Save a message with embedding
 pub async fn save_message(mut message: ChatMessage) -> AppResult<ChatMessage> {
-    // Only generate embeddings for text messages
-    if message.message_type == MessageType::Text && message.embedding.is_none() {
+    // Only generate embeddings for text messages that are still there
+    if message.message_type == MessageType::Text && message.deleted_at.is_none() && message.embedding.is_none() {
         message.embedding = Some(embed(message.message.clone()).await?);
     }
-    
-    let points_url = qdrant_path(&format!("collections/{}/points", CHAT_COLLECTION)).await?;
-    
+
     let vector = match &message.embedding {
-        Some(embedding) => embedding.clone(),
-        None => vec![0.0; 768], // Default vector for system messages
+        Some(embedding) if message.deleted_at.is_none() => embedding.clone(),
+        _ => vec![0.0; embed::dimension().await?], // Default vector for system and deleted messages
     };
-    
-    let point = json!({
-        "id": message.id,
-        "vector": vector,
-        "payload": {
-            "session_id": message.session_id,
-            "sender_id": message.sender_id,
-            "message": message.message,
-            "timestamp": message.timestamp,
-            "message_type": serde_json::to_string(&message.message_type)
-                .map_err(|e| AppError::new("Failed to serialize message type", e))?,
-            "read_by": message.read_by
-        }
-    });
-    
-    let body = json!({
-        "points": [point]
+
+    let payload = json!({
+        "session_id": message.session_id,
+        "sender_id": message.sender_id,
+        "message": message.message,
+        "timestamp": message.timestamp,
+        "message_type": serde_json::to_string(&message.message_type)
+            .map_err(|e| AppError::new("Failed to serialize message type", e))?,
+        "edited_at": message.edited_at,
+        "deleted_at": message.deleted_at,
+        "deleted": message.deleted_at.is_some(),
+        "reactions": message.reactions
     });
-    
-    qdrant_put(&points_url, body).await?;
-    
+
+    store().upsert(CHAT_COLLECTION, vec![Point {
+        id: message.id.clone(),
+        vector: Some(vector),
+        payload: payload.as_object().cloned().unwrap_or_default(),
+        score: None,
+    }]).await?;
+
     Ok(message)
 }
 
-/// Get all messages for a session
-pub async fn get_session_messages(session_id: &str) -> AppResult<Vec<ChatMessage>> {
-    let search_url = qdrant_path(&format!("collections/{}/points/scroll", CHAT_COLLECTION)).await?;
-    
-    let body = json!({
-        "filter": {
-            "must": [
-                {
-                    "key": "session_id",
-                    "match": {
-                        "value": session_id
-                    }
-                }
-            ]
-        },
-        "limit": 100,
-        "with_vector": false,
-        "with_payload": true
-    });
-    
-    let response = qdrant_post(&search_url, body).await?;
-    
-    parse_message_results(response)
-}
-
-/// Search messages by content similarity
-pub async fn search_messages(user_id: &str, query: &str, limit: usize) -> AppResult<Vec<ChatMessage>> {
-    // Generate embedding for the search query
-    let query_embedding = embed(query.to_string()).await?;
-    
-    // First get all sessions the user is part of
-    let sessions = get_user_sessions(user_id).await?;
-    
+/// Serializes read-modify-write changes to a stored message, so two reactions at once
+/// don't overwrite each other. Striped by message id like the read marks below.
+static MESSAGE_CHANGES: Lazy<Vec<Mutex<()>>> = Lazy::new(|| (0..64).map(|_| Mutex::new(())).collect());
+
+/// Serializes moving a read mark, so an older mark read before a newer one was written
+/// can't overwrite it. Striped by the mark's id, so one user's session only waits on a
+/// few others.
+static READ_MARK_CHANGES: Lazy<Vec<Mutex<()>>> = Lazy::new(|| (0..64).map(|_| Mutex::new(())).collect());
+
+/// The lock guarding `id` out of a striped set
+fn stripe<'a>(locks: &'a [Mutex<()>], id: &str) -> &'a Mutex<()> {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    &locks[hasher.finish() as usize % locks.len()]
+}
+
+pub async fn get_message(message_id: &str) -> AppResult<ChatMessage> {
+    let points = store().retrieve(CHAT_COLLECTION, &[message_id], false).await?;
+    parse_messages(points)
+        .pop()
+        .ok_or_else(|| AppError::new_status("Message not found", StatusCode::NOT_FOUND))
+}
+
+fn deleted() -> AppError {
+    AppError::new_status("Message was deleted", StatusCode::GONE)
+}
+
+/// The message, if `user_id` sent it and it hasn't been deleted
+async fn own_message(user_id: &str, message_id: &str) -> AppResult<ChatMessage> {
+    let message = get_message(message_id).await?;
+    if message.sender_id != user_id {
+        return Err(AppError::new_status("Only the sender can change a message", StatusCode::FORBIDDEN));
+    }
+    if message.deleted_at.is_some() {
+        return Err(deleted());
+    }
+    Ok(message)
+}
+
+/// Replaces the text of the user's message, recomputing its embedding
+pub async fn edit_message(user_id: &str, message_id: &str, text: String) -> AppResult<ChatMessage> {
+    if text.trim().is_empty() {
+        return Err(AppError::new_status("Message can't be empty", StatusCode::BAD_REQUEST));
+    }
+
+    let _changing = stripe(&MESSAGE_CHANGES, message_id).lock().await;
+    let mut message = own_message(user_id, message_id).await?;
+    if message.message_type != MessageType::Text {
+        return Err(AppError::new_status("Only text messages can be edited", StatusCode::BAD_REQUEST));
+    }
+    message.message = text;
+    message.edited_at = Some(chrono::Utc::now().timestamp());
+    message.embedding = None;
+    save_message(message).await
+}
+
+/// Deletes the user's message for everyone, leaving a tombstone without text or reactions.
+/// An attachment's file is removed from the bucket.
+pub async fn delete_message(user_id: &str, message_id: &str) -> AppResult<ChatMessage> {
+    let changing = stripe(&MESSAGE_CHANGES, message_id).lock().await;
+    let mut message = own_message(user_id, message_id).await?;
+    if !matches!(message.message_type, MessageType::Text | MessageType::Attachment { .. }) {
+        return Err(AppError::new_status("Only text and attachments can be deleted", StatusCode::BAD_REQUEST));
+    }
+    message.message.clear();
+    message.deleted_at = Some(chrono::Utc::now().timestamp());
+    message.embedding = None;
+    message.reactions.clear();
+    let message = save_message(message).await?;
+    drop(changing);
+
+    // The tombstone is saved, so nothing points at the file anymore; removing it
+    // doesn't need to hold up other changes to the message
+    if let MessageType::Attachment { key, .. } = &message.message_type {
+        objects().await?.delete(key).await?;
+    }
+    Ok(message)
+}
+
+/// Adds or removes the user's `emoji` reaction on a message in one of their sessions
+pub async fn react(user_id: &str, message_id: &str, emoji: &str, add: bool) -> AppResult<ChatMessage> {
+    let emoji = emoji.trim();
+    if emoji.is_empty() || emoji.chars().count() > 16 || emoji.chars().any(char::is_alphanumeric) {
+        return Err(AppError::new_status("Reactions have to be an emoji", StatusCode::BAD_REQUEST));
+    }
+
+    let _changing = stripe(&MESSAGE_CHANGES, message_id).lock().await;
+    let mut message = get_message(message_id).await?;
+    if !is_participant(&message.session_id, user_id).await? {
+        return Err(not_participant());
+    }
+    if message.deleted_at.is_some() {
+        return Err(deleted());
+    }
+
+    let users = message.reactions.entry(emoji.to_string()).or_default();
+    users.retain(|id| id != user_id);
+    if add {
+        users.push(user_id.to_string());
+    }
+    message.reactions.retain(|_, users| !users.is_empty());
+
+    let payload = json!({"reactions": message.reactions});
+    store().set_payload(
+        CHAT_COLLECTION,
+        Selector::ids([&message.id]),
+        payload.as_object().cloned().unwrap_or_default(),
+    ).await?;
+    Ok(message)
+}
+
+/// A page of a session's messages, the latest ones when there's no cursor
+pub async fn get_session_messages(session_id: &str, cursor: Option<Cursor>, limit: usize) -> AppResult<MessagePage> {
+    let filter = Filter::new().must(Condition::matches("session_id", session_id));
+    let mut page = page(filter, cursor, limit).await?;
+    page.messages = with_read_by(page.messages).await?;
+    Ok(page)
+}
+
+fn at_time(filter: &Filter, range: Range) -> Filter {
+    filter.clone().must(Condition::range("timestamp", range))
+}
+
+/// The page of messages matching `filter` next to `cursor`, ordered by (timestamp, id).
+///
+/// Qdrant orders by timestamp only and breaks ties arbitrarily, and many messages share a
+/// second. So besides the ordered scroll this reads every message at the cursor's timestamp
+/// and at the page's last timestamp, and orders those by id too.
+async fn page(filter: Filter, cursor: Option<Cursor>, limit: usize) -> AppResult<MessagePage> {
+    let (direction, bound) = match &cursor {
+        None => (Direction::Desc, None),
+        Some(Cursor::Before(id)) => (Direction::Desc, Some(cursor_key(id).await?)),
+        Some(Cursor::After(id)) => (Direction::Asc, Some(cursor_key(id).await?)),
+    };
+
+    let past_bound = match (direction, &bound) {
+        (_, None) => filter.clone(),
+        (Direction::Desc, Some((t, _))) => at_time(&filter, Range { lt: Some(*t as f64), ..Default::default() }),
+        (Direction::Asc, Some((t, _))) => at_time(&filter, Range { gt: Some(*t as f64), ..Default::default() }),
+    };
+    let order = OrderBy::new("timestamp", direction);
+    let mut points = store().scroll_ordered(CHAT_COLLECTION, &past_bound, &order, limit + 1).await?;
+
+    let mut tied = Vec::new();
+    if let Some((t, _)) = &bound {
+        tied.push(*t);
+    }
+    if points.len() > limit {
+        if let Some(t) = points.last().and_then(|p| p.payload.get("timestamp")?.as_i64()) {
+            tied.push(t);
+        }
+    }
+    for t in tied {
+        let same_second = Range { gte: Some(t as f64), lte: Some(t as f64), ..Default::default() };
+        points.extend(scroll_all(CHAT_COLLECTION, &at_time(&filter, same_second)).await?);
+    }
+
+    let mut messages: Vec<ChatMessage> = parse_messages(points)
+        .into_iter()
+        .map(|m| (m.id.clone(), m))
+        .collect::<HashMap<_, _>>()
+        .into_values()
+        .filter(|m| match (&bound, direction) {
+            (None, _) => true,
+            (Some(b), Direction::Desc) => (m.timestamp, &m.id) < (b.0, &b.1),
+            (Some(b), Direction::Asc) => (m.timestamp, &m.id) > (b.0, &b.1),
+        })
+        .collect();
+    messages.sort_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id)));
+    if direction == Direction::Desc {
+        messages.reverse();
+    }
+    let more = messages.len() > limit;
+    messages.truncate(limit);
+    if direction == Direction::Desc {
+        messages.reverse();
+    }
+
+    // the cursor itself is on the other side, so there's more there whenever it was given
+    let first = messages.first().map(|m| m.id.clone());
+    let last = messages.last().map(|m| m.id.clone());
+    let (before, after) = match direction {
+        Direction::Desc => (first.filter(|_| more), last.filter(|_| bound.is_some())),
+        Direction::Asc => (first.filter(|_| bound.is_some()), last.filter(|_| more)),
+    };
+    Ok(MessagePage { messages, before, after })
+}
+
+/// (timestamp, id) of the message a cursor names
+async fn cursor_key(id: &str) -> AppResult<(i64, String)> {
+    store()
+        .retrieve(CHAT_COLLECTION, &[id], false)
+        .await?
+        .first()
+        .and_then(|point| point.payload.get("timestamp")?.as_i64())
+        .map(|timestamp| (timestamp, id.to_string()))
+        .ok_or_else(|| AppError::new_status("Unknown message cursor", StatusCode::BAD_REQUEST))
+}
+
+/// how many candidates each side of a hybrid search ranks before they're fused
+const SEARCH_POOL: usize = 100;
+
+/// the usual reciprocal rank fusion constant; larger values flatten the top ranks
+const RRF_K: f32 = 60.0;
+
+/// Hybrid search over the messages of the user's sessions.
+///
+/// Keyword hits come from the full-text index on `message`: messages with more of the
+/// query's words rank higher, then ones with the whole query as a phrase, then newer ones.
+/// Semantic hits come from the embeddings. The two rankings are merged with reciprocal
+/// rank fusion, so a message both find comes first, and an exact word still surfaces when
+/// the embedding misses it.
+pub async fn search_messages(user_id: &str, search: &MessageSearch) -> AppResult<Vec<SearchHit>> {
+    let terms: Vec<String> = words(&search.query).collect();
+    if terms.is_empty() {
+        return Err(AppError::new_status("Search for at least one word", StatusCode::BAD_REQUEST));
+    }
+
+    let mut sessions = get_user_sessions(user_id).await?;
+    if let Some(session_id) = &search.session_id {
+        sessions.retain(|id| id == session_id);
+    }
     if sessions.is_empty() {
         return Ok(vec![]);
     }
-    
-    // Create filter for sessions
-    let session_filters = sessions.iter()
-        .map(|session_id| {
-            json!({
-                "key": "session_id",
-                "match": {
-                    "value": session_id
-                }
-            })
+
+    let mut filter = Filter::new()
+        .must(Condition::any("session_id", sessions))
+        .must_not(Condition::matches("deleted", true));
+    if let Some(sender_id) = &search.sender_id {
+        filter = filter.must(Condition::matches("sender_id", sender_id.as_str()));
+    }
+    if search.from.is_some() || search.to.is_some() {
+        let range = Range {
+            gte: search.from.map(|t| t as f64),
+            lte: search.to.map(|t| t as f64),
+            ..Default::default()
+        };
+        filter = filter.must(Condition::range("timestamp", range));
+    }
+
+    // Keyword side: any message with one of the words
+    let any_term = terms.iter().fold(Filter::new(), |any, term| any.should(Condition::text("message", term)));
+    let newest = OrderBy::new("timestamp", Direction::Desc);
+    let keyword_points = store()
+        .scroll_ordered(CHAT_COLLECTION, &filter.clone().must(Condition::Nested(any_term)), &newest, SEARCH_POOL)
+        .await?;
+    let mut keyword_hits: Vec<(usize, bool, ChatMessage)> = parse_messages(keyword_points)
+        .into_iter()
+        .map(|message| {
+            let found: Vec<String> = words(&message.message).collect();
+            let matched = terms.iter().filter(|term| found.contains(term)).count();
+            (matched, found.windows(terms.len()).any(|window| window == terms), message)
         })
-        .collect::<Vec<_>>();
-    
-    let search_url = qdrant_path(&format!("collections/{}/points/search", CHAT_COLLECTION)).await?;
-    
-    let body = json!({
-        "vector": query_embedding,
-        "filter": {
-            "must": [
-                {
-                    "should": session_filters
-                }
-            ]
-        },
-        "limit": limit,
-        "with_vector": false,
-        "with_payload": true
+        .collect();
+    keyword_hits.sort_by(|a, b| {
+        (b.0, b.1, b.2.timestamp).cmp(&(a.0, a.1, a.2.timestamp))
     });
-    
-    let response = qdrant_post(&search_url, body).await?;
-    
-    parse_message_results(response)
+
+    // Semantic side
+    let query_embedding = embed(search.query.clone()).await?;
+    let semantic_points = store().search(CHAT_COLLECTION, &query_embedding, &filter, SEARCH_POOL, 0).await?;
+
+    let mut fused: HashMap<String, (f32, ChatMessage)> = HashMap::new();
+    let keyword_ranked = keyword_hits.into_iter().map(|(_, _, message)| message);
+    for ranked in [keyword_ranked.collect::<Vec<_>>(), parse_messages(semantic_points)] {
+        for (rank, message) in ranked.into_iter().enumerate() {
+            fused.entry(message.id.clone()).or_insert((0.0, message)).0 += 1.0 / (RRF_K + rank as f32 + 1.0);
+        }
+    }
+    let mut hits: Vec<(f32, ChatMessage)> = fused.into_values().collect();
+    hits.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| b.1.timestamp.cmp(&a.1.timestamp)));
+    hits.truncate(search.limit());
+
+    let (scores, messages): (Vec<f32>, Vec<ChatMessage>) = hits.into_iter().unzip();
+    Ok(with_read_by(messages)
+        .await?
+        .into_iter()
+        .zip(scores)
+        .map(|(message, score)| SearchHit {
+            highlight: highlight(&message.message, &terms),
+            message,
+            score,
+        })
+        .collect())
 }
 
-/// Get unread messages for a user
-pub async fn get_unread_messages(user_id: &str) -> AppResult<Vec<ChatMessage>> {
-    // First get all sessions the user is part of
+/// `text` split into the words that are in `terms` and the text between them
+pub fn highlight(text: &str, terms: &[String]) -> Vec<Fragment> {
+    let mut fragments: Vec<Fragment> = Vec::new();
+    let mut push = |text: &str, matched: bool| match fragments.last_mut() {
+        Some(last) if last.matched == matched => last.text.push_str(text),
+        _ if !text.is_empty() => fragments.push(Fragment { text: text.to_string(), matched }),
+        _ => {}
+    };
+    let mut rest = text;
+    while let Some(start) = rest.find(char::is_alphanumeric) {
+        let end = rest[start..].find(|c: char| !c.is_alphanumeric()).map_or(rest.len(), |len| start + len);
+        let word = &rest[start..end];
+        push(&rest[..start], false);
+        push(word, terms.contains(&word.to_lowercase()));
+        rest = &rest[end..];
+    }
+    push(rest, false);
+    fragments
+}
+
+/// How far a user has read a session: every message up to and including this one, in
+/// (timestamp, id) order
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct ReadMark {
+    timestamp: i64,
+    message_id: String,
+}
+
+impl ReadMark {
+    fn of(message: &ChatMessage) -> Self {
+        ReadMark { timestamp: message.timestamp, message_id: message.id.clone() }
+    }
+
+    fn covers(&self, message: &ChatMessage) -> bool {
+        (message.timestamp, &message.id) <= (self.timestamp, &self.message_id)
+    }
+}
+
+/// (session id, user id, mark) for the stored marks matching `filter`
+async fn read_marks(filter: &Filter) -> AppResult<Vec<(String, String, ReadMark)>> {
+    Ok(scroll_all(READS_COLLECTION, filter).await?.into_iter().filter_map(|point| {
+        let payload = &point.payload;
+        let mark = ReadMark {
+            timestamp: payload.get("timestamp")?.as_i64()?,
+            message_id: payload.get("message_id")?.as_str()?.to_string(),
+        };
+        Some((
+            payload.get("session_id")?.as_str()?.to_string(),
+            payload.get("user_id")?.as_str()?.to_string(),
+            mark,
+        ))
+    }).collect())
+}
+
+/// The user's marks by session
+async fn user_read_marks(user_id: &str) -> AppResult<HashMap<String, ReadMark>> {
+    let filter = Filter::new().must(Condition::matches("user_id", user_id));
+    Ok(read_marks(&filter).await?.into_iter().map(|(session_id, _, mark)| (session_id, mark)).collect())
+}
+
+/// Moves the user's mark in the session forward to `mark`; an older mark changes nothing
+async fn advance_read_mark(session_id: &str, user_id: &str, mark: ReadMark) -> AppResult<()> {
+    let id = stable_id(&[session_id, user_id]);
+    let _changing = stripe(&READ_MARK_CHANGES, &id).lock().await;
+    let filter = Filter::new().must(Condition::has_id([&id]));
+    if read_marks(&filter).await?.into_iter().any(|(_, _, current)| current >= mark) {
+        return Ok(());
+    }
+
+    let payload = json!({
+        "session_id": session_id,
+        "user_id": user_id,
+        "timestamp": mark.timestamp,
+        "message_id": mark.message_id
+    });
+    store().upsert(READS_COLLECTION, vec![Point {
+        id,
+        vector: Some(vec![0.0]),
+        payload: payload.as_object().cloned().unwrap_or_default(),
+        score: None,
+    }]).await
+}
+
+/// Sets `read_by` on each message: its sender and every user whose mark covers it
+async fn with_read_by(mut messages: Vec<ChatMessage>) -> AppResult<Vec<ChatMessage>> {
+    let mut sessions: Vec<String> = messages.iter().map(|m| m.session_id.clone()).collect();
+    sessions.sort();
+    sessions.dedup();
+    if sessions.is_empty() {
+        return Ok(messages);
+    }
+
+    let marks = read_marks(&Filter::new().must(Condition::any("session_id", sessions))).await?;
+    for message in &mut messages {
+        message.read_by = vec![message.sender_id.clone()];
+        for (session_id, user_id, mark) in &marks {
+            if *session_id == message.session_id && *user_id != message.sender_id && mark.covers(message) {
+                message.read_by.push(user_id.clone());
+            }
+        }
+    }
+    Ok(messages)
+}
+
+/// Messages in the session that someone else sent after the user's mark
+async fn unread_filter(session_id: &str, user_id: &str, mark: Option<&ReadMark>) -> AppResult<Filter> {
+    let session = Filter::new().must(Condition::matches("session_id", session_id));
+    let mut filter = session.clone().must_not(Condition::matches("sender_id", user_id));
+    if let Some(mark) = mark {
+        filter = at_time(&filter, Range { gte: Some(mark.timestamp as f64), ..Default::default() });
+
+        // messages in the mark's second up to the mark itself are read
+        let same_second = Range { gte: Some(mark.timestamp as f64), lte: Some(mark.timestamp as f64), ..Default::default() };
+        let read: Vec<String> = scroll_all(CHAT_COLLECTION, &at_time(&session, same_second))
+            .await?
+            .into_iter()
+            .map(|point| point.id)
+            .filter(|id| *id <= mark.message_id)
+            .collect();
+        if !read.is_empty() {
+            filter = filter.must_not(Condition::has_id(read));
+        }
+    }
+    Ok(filter)
+}
+
+/// Get a page of unread messages for a user, paged like [`get_session_messages`]
+pub async fn get_unread_messages(user_id: &str, cursor: Option<Cursor>, limit: usize) -> AppResult<MessagePage> {
     let sessions = get_user_sessions(user_id).await?;
-    
     if sessions.is_empty() {
-        return Ok(vec![]);
+        return Ok(MessagePage::default());
     }
-    
-    // Create filter for sessions
-    let session_filters = sessions.iter()
-        .map(|session_id| {
-            json!({
-                "key": "session_id",
-                "match": {
-                    "value": session_id
-                }
-            })
-        })
-        .collect::<Vec<_>>();
-    
-    let search_url = qdrant_path(&format!("collections/{}/points/scroll", CHAT_COLLECTION)).await?;
-    
-    let body = json!({
-        "filter": {
-            "must": [
-                {
-                    "should": session_filters
-                },
-                {
-                    "key": "sender_id",
-                    "match": {
-                        "value": {"$ne": user_id}
-                    }
-                },
-                {
-                    "key": "read_by",
-                    "match": {
-                        "value": {"$ne": user_id}
-                    }
-                }
-            ]
-        },
-        "limit": 100,
-        "with_vector": false,
-        "with_payload": true
-    });
-    
-    let response = qdrant_post(&search_url, body).await?;
-    
-    parse_message_results(response)
+
+    let marks = user_read_marks(user_id).await?;
+    let mut filter = Filter::new();
+    for session_id in &sessions {
+        filter = filter.should(Condition::Nested(unread_filter(session_id, user_id, marks.get(session_id)).await?));
+    }
+
+    let mut page = page(filter, cursor, limit).await?;
+    page.messages = with_read_by(page.messages).await?;
+    Ok(page)
+}
+
+/// How many unread messages the user has in each of their sessions
+pub async fn get_unread_counts(user_id: &str) -> AppResult<HashMap<String, u64>> {
+    let marks = user_read_marks(user_id).await?;
+    let mut counts = HashMap::new();
+    for session_id in get_user_sessions(user_id).await? {
+        let filter = unread_filter(&session_id, user_id, marks.get(&session_id)).await?;
+        counts.insert(session_id, store().count(CHAT_COLLECTION, &filter).await?);
+    }
+    Ok(counts)
 }
 
-/// Mark messages as read by a user
+/// Mark messages as read by a user, along with everything before them in their sessions.
+/// Messages in sessions the user isn't part of are ignored.
 pub async fn mark_messages_as_read(user_id: &str, message_ids: &[String]) -> AppResult<()> {
     if message_ids.is_empty() {
         return Ok(());
     }
-    
-    let futures = message_ids.iter().map(|message_id| {
-        mark_single_message_as_read(user_id, message_id)
-    });
-    
-    // Execute all updates in parallel
-    let results = join_all(futures).await;
-    
-    // Check for errors
-    for result in results {
-        if let Err(e) = result {
-            return Err(e);
+
+    let messages = store()
+        .retrieve(CHAT_COLLECTION, &message_ids.iter().map(String::as_str).collect::<Vec<_>>(), false)
+        .await?;
+
+    // the latest of the messages in each session
+    let mut latest: HashMap<String, ReadMark> = HashMap::new();
+    for message in parse_messages(messages) {
+        let mark = ReadMark::of(&message);
+        let entry = latest.entry(message.session_id).or_insert_with(|| mark.clone());
+        if mark > *entry {
+            *entry = mark;
         }
     }
-    
+
+    let sessions = get_user_sessions(user_id).await?;
+    for (session_id, mark) in latest {
+        if sessions.contains(&session_id) {
+            advance_read_mark(&session_id, user_id, mark).await?;
+        }
+    }
+
     Ok(())
 }
 
-async fn mark_single_message_as_read(user_id: &str, message_id: &str) -> AppResult<()> {
-    // First get the current message
-    let get_url = qdrant_path(&format!("collections/{}/points", CHAT_COLLECTION)).await?;
-    
-    let get_body = json!({
-        "ids": [message_id],
-        "with_payload": true
-    });
-    
-    let response = qdrant_post(&get_url, get_body).await?;
-    
-    let points = response["result"].as_array()
-        .ok_or_else(|| AppError::new_plain("Invalid response format"))?;
-    
-    if points.is_empty() {
-        return Err(AppError::new_plain("Message not found"));
-    }
-    
-    let point = &points[0];
-    let payload = &point["payload"];
-    
-    // Extract current read_by array
-    let mut read_by: Vec<String> = match payload["read_by"].as_array() {
-        Some(array) => array.iter()
-            .filter_map(|v| v.as_str().map(|s| s.to_string()))
-            .collect(),
-        None => vec![]
-    };
-    
-    // Add user_id if not already present
-    if !read_by.contains(&user_id.to_string()) {
-        read_by.push(user_id.to_string());
-        
-        // Update the message
-        let update_url = qdrant_path(&format!("collections/{}/points", CHAT_COLLECTION)).await?;
-        
-        let update_body = json!({
-            "points": [
-                {
-                    "id": message_id,
-                    "payload": {
-                        "read_by": read_by
-                    }
-                }
-            ]
-        });
-        
-        qdrant_put(&update_url, update_body).await?;
-    }
-    
-    Ok(())
+/// Marks everything in the session read by a participant, in one write
+pub async fn mark_session_read(user_id: &str, session_id: &str) -> AppResult<()> {
+    if !is_participant(session_id, user_id).await? {
+        return Err(not_participant());
+    }
+
+    let filter = Filter::new().must(Condition::matches("session_id", session_id));
+    match page(filter, None, 1).await?.messages.last() {
+        Some(latest) => advance_read_mark(session_id, user_id, ReadMark::of(latest)).await,
+        None => Ok(()),
+    }
+}
+
+/// Every point matching `filter`, page by page
+pub(crate) async fn scroll_all(collection: &str, filter: &Filter) -> AppResult<Vec<Point>> {
+    let mut points = Vec::new();
+    let mut offset = None;
+    loop {
+        let (page, next) = store().scroll(collection, filter, 100, offset).await?;
+        points.extend(page);
+        match next {
+            Some(next) => offset = Some(next),
+            None => return Ok(points),
+        }
+    }
+}
+
+/// Save a session, active or ended
+pub async fn save_session(session: &ChatSession) -> AppResult<()> {
+    let mut payload = serde_json::to_value(session)
+        .map_err(|e| AppError::new("Failed to serialize chat session", e))?
+        .as_object()
+        .cloned()
+        .unwrap_or_default();
+    payload.remove("id");
+
+    store().upsert(SESSIONS_COLLECTION, vec![Point {
+        id: session.id.clone(),
+        vector: Some(vec![0.0]),
+        payload,
+        score: None,
+    }]).await
+}
+
+/// Sessions that hadn't ended, e.g. when the process last stopped
+pub async fn load_active_sessions() -> AppResult<Vec<ChatSession>> {
+    let filter = Filter::new().must(Condition::matches("active", true));
+    Ok(parse_sessions(scroll_all(SESSIONS_COLLECTION, &filter).await?))
+}
+
+/// Whether the user was matched into the stored session, active or ended
+pub async fn is_participant(session_id: &str, user_id: &str) -> AppResult<bool> {
+    let points = store().retrieve(SESSIONS_COLLECTION, &[session_id], false).await?;
+    Ok(parse_sessions(points).iter().any(|session| session.has_participant(user_id)))
+}
+
+pub fn not_participant() -> AppError {
+    AppError::new_status("Not a participant of this session", StatusCode::FORBIDDEN)
 }
 
 /// Get all session IDs a user is part of
 async fn get_user_sessions(user_id: &str) -> AppResult<Vec<String>> {
-    let sessions_url = qdrant_path("collections/sessions/points/scroll").await?;
-    
-    let body = json!({
-        "filter": {
-            "should": [
-                {
-                    "key": "user1_id",
-                    "match": {
-                        "value": user_id
-                    }
-                },
-                {
-                    "key": "user2_id",
-                    "match": {
-                        "value": user_id
-                    }
-                }
-            ]
-        },
-        "limit": 100,
-        "with_vector": false,
-        "with_payload": true
-    });
-    
-    let response = qdrant_post(&sessions_url, body).await?;
-    
-    let points = response["result"]["points"].as_array()
-        .ok_or_else(|| AppError::new_plain("Invalid response format"))?;
-    
-    let session_ids = points.iter()
-        .filter_map(|p| p["id"].as_str().map(|s| s.to_string()))
-        .collect();
-    
-    Ok(session_ids)
+    let filter = Filter::new()
+        .should(Condition::matches("user1_id", user_id))
+        .should(Condition::matches("user2_id", user_id));
+
+    Ok(scroll_all(SESSIONS_COLLECTION, &filter).await?.into_iter().map(|p| p.id).collect())
 }
 
-/// Parse message results from Qdrant response
-fn parse_message_results(response: serde_json::Value) -> AppResult<Vec<ChatMessage>> {
-    let points = match response["result"].get("points").and_then(|p| p.as_array()) {
-        Some(points) => points,
-        None => response["result"].as_array()
-            .ok_or_else(|| AppError::new_plain("Invalid response format"))?,
-    };
-    
-    let messages = points.iter().filter_map(|point| {
-        let id = point["id"].as_str()?;
-        let payload = &point["payload"];
-        
-        let session_id = payload["session_id"].as_str()?;
-        let sender_id = payload["sender_id"].as_str()?;
-        let message_text = payload["message"].as_str()?;
-        let timestamp = payload["timestamp"].as_i64()?;
-        
+fn parse_sessions(points: Vec<Point>) -> Vec<ChatSession> {
+    points.into_iter().filter_map(|point| {
+        let mut payload = point.payload;
+        payload.insert("id".to_string(), point.id.into());
+        serde_json::from_value(payload.into()).ok()
+    }).collect()
+}
+
+/// Parse stored message points, skipping ones that aren't messages
+fn parse_messages(points: Vec<Point>) -> Vec<ChatMessage> {
+    points.into_iter().filter_map(|point| {
+        let payload = &point.payload;
+
+        let session_id = payload.get("session_id")?.as_str()?;
+        let sender_id = payload.get("sender_id")?.as_str()?;
+        let message_text = payload.get("message")?.as_str()?;
+        let timestamp = payload.get("timestamp")?.as_i64()?;
+
         // Parse message type
-        let message_type_str = payload["message_type"].as_str()?;
+        let message_type_str = payload.get("message_type")?.as_str()?;
         let message_type: MessageType = serde_json::from_str(message_type_str).ok()?;
-        
-        // Parse read_by array
-        let read_by = match payload["read_by"].as_array() {
-            Some(array) => array.iter()
-                .filter_map(|v| v.as_str().map(|s| s.to_string()))
-                .collect(),
-            None => vec![]
-        };
-        
+
+        let reactions = payload.get("reactions")
+            .and_then(|r| serde_json::from_value(r.clone()).ok())
+            .unwrap_or_default();
+
         Some(ChatMessage {
-            id: id.to_string(),
+            id: point.id.clone(),
             session_id: session_id.to_string(),
             sender_id: sender_id.to_string(),
             message: message_text.to_string(),
             timestamp,
             message_type,
             embedding: None, // We don't return the embedding to save bandwidth
-            read_by,
+            read_by: vec![sender_id.to_string()], // filled in by `with_read_by`
+            edited_at: payload.get("edited_at").and_then(|t| t.as_i64()),
+            deleted_at: payload.get("deleted_at").and_then(|t| t.as_i64()),
+            reactions,
         })
-    }).collect();
-    
-    Ok(messages)
-} 
\ No newline at end of file
+    }).collect()
+}
@@ -1,14 +1,79 @@
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
 use uuid::Uuid;
+use warp::http::StatusCode;
+
+use crate::util::AppError;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserProfile {
     pub id: String,
     pub description: String,
     pub interests: Vec<String>,
-    pub age_range: Option<String>,
+    pub age_range: Option<AgeRange>,
     pub embedding: Option<Vec<f32>>,
     pub created_at: i64,
+    /// users this one blocked or was blocked by, who are never matched with them
+    #[serde(skip)]
+    pub blocked: HashSet<String>,
+    #[serde(default)]
+    pub icebreakers: bool,
+    #[serde(default)]
+    pub summaries: bool,
+}
+
+/// An inclusive age bracket, written `18-25`, `30+` or `27`.
+///
+/// It's a hard constraint in matching: two users with brackets only meet when the brackets
+/// overlap, and a user who set one never meets a user who didn't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgeRange {
+    pub min: u8,
+    pub max: u8,
+}
+
+impl AgeRange {
+    /// upper bound of an open bracket like `30+`
+    pub const OLDEST: u8 = 120;
+
+    pub fn overlaps(&self, other: &AgeRange) -> bool {
+        self.min <= other.max && other.min <= self.max
+    }
+
+    /// whether users with these brackets may be matched
+    pub fn compatible(a: Option<&AgeRange>, b: Option<&AgeRange>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => a.overlaps(b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for AgeRange {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || AppError::new_status(
+            &format!("Invalid age_range {:?}, expected e.g. 18-25 or 30+", s),
+            StatusCode::BAD_REQUEST,
+        );
+        let age = |a: &str| a.trim().parse::<u8>().map_err(|_| invalid());
+        let s = s.trim();
+        let range = if let Some(min) = s.strip_suffix('+') {
+            AgeRange { min: age(min)?, max: Self::OLDEST }
+        } else if let Some((min, max)) = s.split_once('-') {
+            AgeRange { min: age(min)?, max: age(max)? }
+        } else {
+            let exact = age(s)?;
+            AgeRange { min: exact, max: exact }
+        };
+        if range.min > range.max || range.max > Self::OLDEST {
+            return Err(invalid());
+        }
+        Ok(range)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -16,6 +81,12 @@ pub struct MatchRequest {
     pub description: String,
     pub interests: Vec<String>,
     pub age_range: Option<String>,
+    /// suggest opening lines when matched with someone who opted in too
+    #[serde(default)]
+    pub icebreakers: bool,
+    /// summarize sessions with someone who opted in too once they end
+    #[serde(default)]
+    pub summaries: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -36,6 +107,95 @@ pub struct ChatMessage {
     pub embedding: Option<Vec<f32>>,
     pub id: String,
     pub read_by: Vec<String>,
+    /// when the sender last changed the text
+    #[serde(default)]
+    pub edited_at: Option<i64>,
+    /// set when the sender deleted it for everyone; the text and embedding are gone
+    #[serde(default)]
+    pub deleted_at: Option<i64>,
+    /// emoji -> users who reacted with it
+    #[serde(default)]
+    pub reactions: BTreeMap<String, Vec<String>>,
+}
+
+/// Where a page of messages starts: strictly before or after the message with this id,
+/// ordered by timestamp and then id
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cursor {
+    Before(String),
+    After(String),
+}
+
+/// `?before=<id>`, `?after=<id>` and `?limit=` on message lists
+#[derive(Debug, Default, Deserialize)]
+pub struct PageQuery {
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub limit: Option<usize>,
+}
+
+impl PageQuery {
+    pub const DEFAULT_LIMIT: usize = 50;
+    pub const MAX_LIMIT: usize = 200;
+
+    pub fn cursor(&self) -> Result<Option<Cursor>, AppError> {
+        match (&self.before, &self.after) {
+            (Some(_), Some(_)) => Err(AppError::new_status(
+                "Pass either before or after, not both",
+                StatusCode::BAD_REQUEST,
+            )),
+            (Some(id), None) => Ok(Some(Cursor::Before(id.clone()))),
+            (None, Some(id)) => Ok(Some(Cursor::After(id.clone()))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(Self::DEFAULT_LIMIT).clamp(1, Self::MAX_LIMIT)
+    }
+}
+
+/// Messages oldest first, with the cursors for the neighbouring pages when there are more
+#[derive(Debug, Default)]
+pub struct MessagePage {
+    pub messages: Vec<ChatMessage>,
+    /// pass as `before` for older messages
+    pub before: Option<String>,
+    /// pass as `after` for newer messages
+    pub after: Option<String>,
+}
+
+/// Body of `/chat/search`. `from` and `to` are unix seconds, both inclusive.
+#[derive(Debug, Default, Deserialize)]
+pub struct MessageSearch {
+    pub query: String,
+    pub limit: Option<usize>,
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub sender_id: Option<String>,
+    pub session_id: Option<String>,
+}
+
+impl MessageSearch {
+    pub fn limit(&self) -> usize {
+        self.limit.unwrap_or(20).clamp(1, 100)
+    }
+}
+
+/// A search result: the message with its fused score, and its text split into the parts
+/// that matched the query and the parts between them
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub message: ChatMessage,
+    pub score: f32,
+    pub highlight: Vec<Fragment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Fragment {
+    pub text: String,
+    pub matched: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -44,26 +204,162 @@ pub enum MessageType {
     System,
     UserDisconnected,
     UserConnected,
+    /// a file in the attachment bucket; the message text is its file name
+    Attachment {
+        key: String,
+        mime_type: String,
+        size: u64,
+    },
+}
+
+/// Frames a client sends over `/chat/ws`. The sender is the user the socket authenticated
+/// as; a `user_id`, if given, has to be that user.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientEvent {
+    Chat {
+        session_id: String,
+        message: String,
+        user_id: Option<String>,
+    },
+    JoinSession {
+        session_id: String,
+        user_id: Option<String>,
+    },
+    LeaveSession {
+        session_id: String,
+        user_id: Option<String>,
+    },
+    /// close the session for both users, going back into the queue if `requeue` is set
+    EndSession {
+        session_id: String,
+        #[serde(default)]
+        requeue: bool,
+    },
+    /// close the session and go straight back into the queue
+    Next {
+        session_id: String,
+    },
+    /// go back into the queue with the profile of the user's last `/chat/match`, e.g.
+    /// after their partner ended the session
+    Requeue,
+    /// replace the text of one of the user's own messages
+    EditMessage {
+        message_id: String,
+        message: String,
+    },
+    /// delete one of the user's own messages for everyone
+    DeleteMessage {
+        message_id: String,
+    },
+    React {
+        message_id: String,
+        emoji: String,
+    },
+    Unreact {
+        message_id: String,
+        emoji: String,
+    },
+}
+
+/// Frames the server sends over `/chat/ws`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerEvent {
+    /// a chat or system message in one of the socket's sessions
+    Message(ChatMessage),
+    /// `/chat/match` paired this user with `partner_id` in a new session
+    MatchFound { session_id: String, partner_id: String },
+    /// the user waited in the queue past its TTL without a match
+    QueueExpired,
+    /// the session was closed, by `ended_by` or for both when they were gone
+    SessionEnded {
+        session_id: String,
+        ended_by: Option<String>,
+        outcome: SessionOutcome,
+    },
+    /// the user is back in the matchmaking queue
+    Requeued,
+    /// opening lines for a new session
+    Icebreakers { session_id: String, suggestions: Vec<String> },
+    /// what an ended session was about
+    SessionSummary { session_id: String, summary: String, topics: Vec<String> },
+    MessageEdited {
+        session_id: String,
+        message_id: String,
+        message: String,
+        edited_at: i64,
+    },
+    MessageDeleted {
+        session_id: String,
+        message_id: String,
+        deleted_at: i64,
+    },
+    /// the full set of reactions on the message after a change
+    ReactionsChanged {
+        session_id: String,
+        message_id: String,
+        reactions: BTreeMap<String, Vec<String>>,
+    },
+    /// a frame from this socket went through, but the safety filter flagged or masked it
+    Warning { message: String },
+    /// a frame from this socket was rejected
+    Error { status: u16, message: String },
 }
 
-#[derive(Debug, Clone)]
+/// Two matched users' conversation. Stored in the chat `sessions` collection, active or
+/// ended, and active ones are reloaded at startup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatSession {
     pub id: String,
     pub user1_id: String,
     pub user2_id: String,
     pub created_at: i64,
+    pub ended_at: Option<i64>,
     pub active: bool,
+    #[serde(default)]
+    pub ended_by: Option<String>,
+    #[serde(default)]
+    pub outcome: Option<SessionOutcome>,
+    /// seconds from the match to the end
+    #[serde(default)]
+    pub duration_secs: Option<i64>,
+    /// both users opted into a summary of the session
+    #[serde(default)]
+    pub summarize: bool,
+    /// what the session was about, written after it ends
+    #[serde(default)]
+    pub summary: Option<String>,
+    #[serde(default)]
+    pub topics: Vec<String>,
+}
+
+/// How a chat session ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionOutcome {
+    /// a participant ended it
+    Ended,
+    /// a participant skipped to the next match
+    Skipped,
+    /// both participants disconnected
+    Abandoned,
+    /// a participant blocked the other
+    Blocked,
 }
 
 impl UserProfile {
-    pub fn new(description: String, interests: Vec<String>, age_range: Option<String>) -> Self {
+    pub fn new(id: String, description: String, interests: Vec<String>, age_range: Option<AgeRange>) -> Self {
         Self {
-            id: Uuid::now_v7().to_string(),
+            id,
             description,
             interests,
             age_range,
             embedding: None,
             created_at: chrono::Utc::now().timestamp(),
+            blocked: HashSet::new(),
+            icebreakers: false,
+            summaries: false,
         }
     }
 }
@@ -75,9 +371,33 @@ impl ChatSession {
             user1_id,
             user2_id,
             created_at: chrono::Utc::now().timestamp(),
+            ended_at: None,
             active: true,
+            ended_by: None,
+            outcome: None,
+            duration_secs: None,
+            summarize: false,
+            summary: None,
+            topics: Vec::new(),
         }
     }
+
+    pub fn has_participant(&self, user_id: &str) -> bool {
+        self.user1_id == user_id || self.user2_id == user_id
+    }
+
+    /// the other participant
+    pub fn partner_of(&self, user_id: &str) -> &str {
+        if self.user1_id == user_id { &self.user2_id } else { &self.user1_id }
+    }
+
+    pub fn end(&mut self, ended_by: Option<&str>, outcome: SessionOutcome, now: i64) {
+        self.active = false;
+        self.ended_at = Some(now);
+        self.ended_by = ended_by.map(str::to_string);
+        self.outcome = Some(outcome);
+        self.duration_secs = Some((now - self.created_at).max(0));
+    }
 }
 
 impl ChatMessage {
@@ -96,6 +416,9 @@ impl ChatMessage {
             message_type,
             embedding: None,
             read_by: vec![sender_id], // Sender has read their own message
+            edited_at: None,
+            deleted_at: None,
+            reactions: BTreeMap::new(),
         }
     }
 } 
\ No newline at end of file
@@ -1,169 +1,338 @@
-use super::types::{ChatMessage, MessageType};
-use super::matching::ACTIVE_SESSIONS;
-use super::storage;
+use super::types::{ChatMessage, ChatSession, ClientEvent, MessageType, ServerEvent, SessionOutcome};
+use super::matching::{self, ACTIVE_SESSIONS};
+use super::matchmaker;
+use super::registry::Registry;
+use super::storage::{self, not_participant};
+use crate::util::safety::{self, ContentKind, Verdict};
+use crate::util::AppError;
+use warp::http::StatusCode;
 use warp::ws::{Message, WebSocket};
 use futures_util::{SinkExt, StreamExt};
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use std::collections::HashMap;
+use tokio::sync::{mpsc, Mutex};
 use uuid::Uuid;
 use serde_json;
 
-// Global state for managing WebSocket connections
+// Global state for routing messages to the sockets in each chat session
 lazy_static::lazy_static! {
-    static ref CONNECTIONS: Arc<Mutex<HashMap<String, Arc<Mutex<futures_util::stream::SplitSink<WebSocket, Message>>>>>> = 
-        Arc::new(Mutex::new(HashMap::new()));
+    static ref REGISTRY: Arc<Mutex<Registry>> = Arc::new(Mutex::new(Registry::new()));
 }
 
-pub async fn handle_websocket(websocket: WebSocket) {
-    let (ws_sender, mut ws_receiver) = websocket.split();
+/// Serves one socket for `user_id`, the identity authenticated at upgrade time
+pub async fn handle_websocket(websocket: WebSocket, user_id: String) {
+    let (mut ws_sender, mut ws_receiver) = websocket.split();
     let connection_id = Uuid::now_v7().to_string();
-    
-    // Store the sender half for this connection
-    let sender_arc = Arc::new(Mutex::new(ws_sender));
-    {
-        let mut connections = CONNECTIONS.lock().await;
-        connections.insert(connection_id.clone(), sender_arc.clone());
-    }
-    
+
+    // Forward this connection's outbox to the socket; when the socket fails the outbox
+    // closes and the registry drops the connection on its next send
+    let (outbox, mut inbox) = mpsc::unbounded_channel::<Message>();
+    tokio::spawn(async move {
+        while let Some(message) = inbox.recv().await {
+            if let Err(e) = ws_sender.send(message).await {
+                log::error!("Error sending message: {}", e);
+                break;
+            }
+        }
+    });
+    REGISTRY.lock().await.connect(&connection_id, &user_id, outbox);
+
     // Handle incoming messages
     while let Some(result) = ws_receiver.next().await {
         match result {
             Ok(msg) => {
                 if let Ok(text) = msg.to_str() {
-                    if let Err(e) = handle_message(&connection_id, text).await {
-                        eprintln!("Error handling message: {}", e);
+                    if let Err(e) = handle_message(&connection_id, &user_id, text).await {
+                        send_error(&connection_id, &e).await;
                     }
                 }
             }
             Err(e) => {
-                eprintln!("WebSocket error: {}", e);
+                log::error!("WebSocket error: {}", e);
                 break;
             }
         }
     }
-    
-    // Clean up connection
-    {
-        let mut connections = CONNECTIONS.lock().await;
-        connections.remove(&connection_id);
+
+    // Clean up connection; a user with no sockets left can't be told about a match
+    let still_connected = {
+        let mut registry = REGISTRY.lock().await;
+        registry.disconnect(&connection_id);
+        registry.connected(&user_id)
+    };
+    if !still_connected {
+        matching::remove_waiting(&user_id).await;
+
+        // Sessions stay open while one side may come back, not once both are gone
+        let sessions = matching::sessions_of(&user_id).await;
+        let abandoned: Vec<String> = {
+            let registry = REGISTRY.lock().await;
+            sessions
+                .into_iter()
+                .filter(|(_, partner)| !registry.connected(partner))
+                .map(|(session_id, _)| session_id)
+                .collect()
+        };
+        matching::abandon(&abandoned).await;
+    }
+}
+
+/// Pushes an event to every socket of the user, returning whether any was reached
+pub async fn notify(user_id: &str, event: &ServerEvent) -> bool {
+    match serde_json::to_string(event) {
+        Ok(json) => REGISTRY.lock().await.send_to_user(user_id, &Message::text(json)) > 0,
+        Err(e) => {
+            log::error!("Error serializing chat event: {}", e);
+            false
+        }
+    }
+}
+
+/// Tells this connection its frame was rejected. Server errors are logged and hidden.
+async fn send_error(connection_id: &str, error: &AppError) {
+    let message = if error.status().is_server_error() {
+        log::error!("Error handling chat frame: {:#?}", error);
+        "An error occured on our side".to_string()
+    } else {
+        error.to_string()
+    };
+    let event = ServerEvent::Error { status: error.status().as_u16(), message };
+    if let Ok(json) = serde_json::to_string(&event) {
+        REGISTRY.lock().await.send_to(connection_id, &Message::text(json));
+    }
+}
+
+/// Tells this connection when the safety filter flagged or masked its text
+async fn warn(connection_id: &str, verdict: &Verdict) {
+    let Some(message) = verdict.notice() else {
+        return;
+    };
+    if let Ok(json) = serde_json::to_string(&ServerEvent::Warning { message }) {
+        REGISTRY.lock().await.send_to(connection_id, &Message::text(json));
     }
 }
 
-async fn handle_message(connection_id: &str, message_text: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+/// rejects a frame naming a different user than the socket authenticated as
+fn check_sender(claimed: Option<&str>, user_id: &str) -> Result<(), AppError> {
+    match claimed {
+        Some(claimed) if claimed != user_id => Err(AppError::new_status(
+            "user_id doesn't match the authenticated user",
+            StatusCode::FORBIDDEN,
+        )),
+        _ => Ok(()),
+    }
+}
+
+async fn handle_message(connection_id: &str, user_id: &str, message_text: &str) -> Result<(), AppError> {
     // Parse the incoming message
-    let message: serde_json::Value = serde_json::from_str(message_text)?;
-    
-    match message["type"].as_str() {
-        Some("chat") => {
-            let session_id = message["session_id"].as_str()
-                .ok_or("Missing session_id")?;
-            let user_id = message["user_id"].as_str()
-                .ok_or("Missing user_id")?;
-            let text = message["message"].as_str()
-                .ok_or("Missing message")?;
-            
+    let event: ClientEvent = serde_json::from_str(message_text).map_err(|e| {
+        AppError::new_status(&format!("Invalid frame: {}", e), StatusCode::BAD_REQUEST)
+    })?;
+
+    match event {
+        ClientEvent::Chat { session_id, message, user_id: claimed } => {
+            check_sender(claimed.as_deref(), user_id)?;
+
             // Verify the session exists and the user is part of it
-            let sessions = ACTIVE_SESSIONS.lock().await;
-            if let Some(session) = sessions.get(session_id) {
-                if session.user1_id == user_id || session.user2_id == user_id {
-                    let partner_id = if session.user1_id == user_id {
-                        &session.user2_id
-                    } else {
-                        &session.user1_id
-                    };
-                    
-                    // Create chat message
-                    let chat_message = ChatMessage::new(
-                        session_id.to_string(),
-                        user_id.to_string(),
-                        text.to_string(),
-                        MessageType::Text,
-                    );
-                    
-                    // Save message with embedding in the background
-                    let chat_message_clone = chat_message.clone();
-                    tokio::spawn(async move {
-                        if let Err(e) = storage::save_message(chat_message_clone).await {
-                            eprintln!("Error saving message: {:?}", e);
-                        }
-                    });
-                    
-                    // Send to both users in the session
-                    broadcast_to_session(&session.id, &chat_message).await?;
-                }
+            if !is_participant(&session_id, user_id).await {
+                return Err(not_participant());
             }
+            let verdict = safety::screen(ContentKind::ChatMessage, user_id, &message).await?;
+
+            // Make sure the sender gets the session's messages on this connection too
+            REGISTRY.lock().await.join(&session_id, user_id, connection_id);
+
+            // Create chat message
+            let chat_message = ChatMessage::new(
+                session_id.clone(),
+                user_id.to_string(),
+                verdict.text.clone(),
+                MessageType::Text,
+            );
+
+            // Save message with embedding in the background
+            let chat_message_clone = chat_message.clone();
+            tokio::spawn(async move {
+                if let Err(e) = storage::save_message(chat_message_clone).await {
+                    log::error!("Error saving message: {:?}", e);
+                }
+            });
+
+            // Send to both users in the session
+            broadcast_to_session(&session_id, chat_message).await?;
+            warn(connection_id, &verdict).await;
         }
-        Some("join_session") => {
-            let session_id = message["session_id"].as_str()
-                .ok_or("Missing session_id")?;
-            let user_id = message["user_id"].as_str()
-                .ok_or("Missing user_id")?;
-            
+        ClientEvent::JoinSession { session_id, user_id: claimed } => {
+            check_sender(claimed.as_deref(), user_id)?;
+
+            if !is_participant(&session_id, user_id).await {
+                return Err(not_participant());
+            }
+            REGISTRY.lock().await.join(&session_id, user_id, connection_id);
+
             // Notify the session that a user has joined
             let join_message = ChatMessage::new(
-                session_id.to_string(),
+                session_id.clone(),
                 "system".to_string(),
                 format!("User {} has joined the chat", user_id),
                 MessageType::UserConnected,
             );
-            
+
             // Save system message
             let join_message_clone = join_message.clone();
             tokio::spawn(async move {
                 if let Err(e) = storage::save_message(join_message_clone).await {
-                    eprintln!("Error saving join message: {:?}", e);
+                    log::error!("Error saving join message: {:?}", e);
                 }
             });
-            
-            broadcast_to_session(session_id, &join_message).await?;
+
+            broadcast_to_session(&session_id, join_message).await?;
         }
-        Some("leave_session") => {
-            let session_id = message["session_id"].as_str()
-                .ok_or("Missing session_id")?;
-            let user_id = message["user_id"].as_str()
-                .ok_or("Missing user_id")?;
-            
+        ClientEvent::LeaveSession { session_id, user_id: claimed } => {
+            check_sender(claimed.as_deref(), user_id)?;
+
+            if !REGISTRY.lock().await.joined(&session_id, user_id, connection_id) {
+                return Err(AppError::new_status("Not in this session", StatusCode::BAD_REQUEST));
+            }
+
             // Notify the session that a user has left
             let leave_message = ChatMessage::new(
-                session_id.to_string(),
+                session_id.clone(),
                 "system".to_string(),
                 format!("User {} has left the chat", user_id),
                 MessageType::UserDisconnected,
             );
-            
+
             // Save system message
             let leave_message_clone = leave_message.clone();
             tokio::spawn(async move {
                 if let Err(e) = storage::save_message(leave_message_clone).await {
-                    eprintln!("Error saving leave message: {:?}", e);
+                    log::error!("Error saving leave message: {:?}", e);
                 }
             });
-            
-            broadcast_to_session(session_id, &leave_message).await?;
+
+            broadcast_to_session(&session_id, leave_message).await?;
+            REGISTRY.lock().await.leave(&session_id, user_id);
+
+            // Don't pair the two of them again straight away
+            let pair = ACTIVE_SESSIONS
+                .lock()
+                .await
+                .get(&session_id)
+                .map(|session| (session.user1_id.clone(), session.user2_id.clone()));
+            if let Some((a, b)) = pair {
+                matchmaker::record_ended(&a, &b).await;
+            }
         }
-        _ => {
-            eprintln!("Unknown message type: {}", message_text);
+        ClientEvent::EndSession { session_id, requeue } => {
+            let session = matching::end_session(&session_id, user_id, SessionOutcome::Ended).await?;
+            session_ended(&session).await;
+            if requeue {
+                requeue_user(user_id).await?;
+            }
+        }
+        ClientEvent::Next { session_id } => {
+            let session = matching::end_session(&session_id, user_id, SessionOutcome::Skipped).await?;
+            session_ended(&session).await;
+            requeue_user(user_id).await?;
+        }
+        ClientEvent::Requeue => {
+            requeue_user(user_id).await?;
+        }
+        ClientEvent::EditMessage { message_id, message } => {
+            let verdict = safety::screen(ContentKind::ChatMessage, user_id, &message).await?;
+            let edited = storage::edit_message(user_id, &message_id, verdict.text.clone()).await?;
+            warn(connection_id, &verdict).await;
+            broadcast_event(&edited.session_id, &ServerEvent::MessageEdited {
+                session_id: edited.session_id.clone(),
+                message_id: edited.id,
+                message: edited.message,
+                edited_at: edited.edited_at.unwrap_or_default(),
+            }).await?;
+        }
+        ClientEvent::DeleteMessage { message_id } => {
+            let deleted = storage::delete_message(user_id, &message_id).await?;
+            broadcast_event(&deleted.session_id, &ServerEvent::MessageDeleted {
+                session_id: deleted.session_id.clone(),
+                message_id: deleted.id,
+                deleted_at: deleted.deleted_at.unwrap_or_default(),
+            }).await?;
+        }
+        ClientEvent::React { message_id, emoji } => {
+            react(user_id, &message_id, &emoji, true).await?;
+        }
+        ClientEvent::Unreact { message_id, emoji } => {
+            react(user_id, &message_id, &emoji, false).await?;
         }
     }
-    
+
     Ok(())
 }
 
-async fn broadcast_to_session(session_id: &str, message: &ChatMessage) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let message_json = serde_json::to_string(message)?;
-    let ws_message = Message::text(message_json);
-    
-    let connections = CONNECTIONS.lock().await;
-    
-    // In a real implementation, you'd maintain a mapping of user_id to connection_id
-    // For now, we'll broadcast to all connections (simplified)
-    for (_, sender) in connections.iter() {
-        let mut sender_guard = sender.lock().await;
-        if let Err(e) = sender_guard.send(ws_message.clone()).await {
-            eprintln!("Error sending message: {}", e);
+/// Tells both users their session ended and stops routing its messages
+async fn session_ended(session: &ChatSession) {
+    let ended = ServerEvent::SessionEnded {
+        session_id: session.id.clone(),
+        ended_by: session.ended_by.clone(),
+        outcome: session.outcome.unwrap_or(SessionOutcome::Ended),
+    };
+    for user_id in [&session.user1_id, &session.user2_id] {
+        notify(user_id, &ended).await;
+    }
+    let mut registry = REGISTRY.lock().await;
+    registry.leave(&session.id, &session.user1_id);
+    registry.leave(&session.id, &session.user2_id);
+}
+
+/// Ends the chats `user_id` is in with `other`, e.g. after one blocked the other
+pub async fn end_sessions_between(user_id: &str, other: &str) {
+    for (session_id, partner) in matching::sessions_of(user_id).await {
+        if partner != other {
+            continue;
+        }
+        match matching::end_session(&session_id, user_id, SessionOutcome::Blocked).await {
+            Ok(session) => session_ended(&session).await,
+            Err(e) => log::error!("Error ending chat session {}: {:?}", session_id, e),
         }
     }
-    
+}
+
+async fn requeue_user(user_id: &str) -> Result<(), AppError> {
+    matching::requeue(user_id).await?;
+    notify(user_id, &ServerEvent::Requeued).await;
     Ok(())
-} 
\ No newline at end of file
+}
+
+async fn react(user_id: &str, message_id: &str, emoji: &str, add: bool) -> Result<(), AppError> {
+    let changed = storage::react(user_id, message_id, emoji, add).await?;
+    broadcast_event(&changed.session_id, &ServerEvent::ReactionsChanged {
+        session_id: changed.session_id.clone(),
+        message_id: changed.id,
+        reactions: changed.reactions,
+    }).await
+}
+
+/// whether `user_id` is one of the two users matched into the session
+async fn is_participant(session_id: &str, user_id: &str) -> bool {
+    ACTIVE_SESSIONS
+        .lock()
+        .await
+        .get(session_id)
+        .is_some_and(|session| session.has_participant(user_id))
+}
+
+/// Sends the message to the connections that joined the session, which are only ever
+/// its two participants
+pub(super) async fn broadcast_to_session(session_id: &str, message: ChatMessage) -> Result<(), AppError> {
+    broadcast_event(session_id, &ServerEvent::Message(message)).await
+}
+
+/// Sends the event to the connections that joined the session
+async fn broadcast_event(session_id: &str, event: &ServerEvent) -> Result<(), AppError> {
+    let event_json = serde_json::to_string(event)
+        .map_err(|e| AppError::new("serializing chat event", e))?;
+    let ws_message = Message::text(event_json);
+
+    REGISTRY.lock().await.send(session_id, &ws_message);
+
+    Ok(())
+}
@@ -1,8 +1,9 @@
 use warp::{Filter, Reply};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 use crate::{
-    util::{AppResult, AppError, embedding, id, qdrant::{qdrant_path, qdrant_post}},
+    repo::{self, models::ChatGroup},
+    routes::user::find_user,
+    util::{AppResult, embed, id, safety::{self, ContentKind}},
 };
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,75 +34,34 @@ pub async fn handler(request: ChatGroupAddRequest) -> impl Reply {
             log::error!("Chat group add error: {:#?}", e);
             warp::reply::with_status(
                 format!("Error: {}", e),
-                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                e.status(),
             )
         }
     }
 }
 
-async fn add_chatgroup(request: ChatGroupAddRequest) -> AppResult<String> {
-    // Get user details to inherit zone and position if not provided
-    let user_data = get_user_data(&request.u).await?;
-    
+async fn add_chatgroup(mut request: ChatGroupAddRequest) -> AppResult<String> {
+    // Get user details to inherit zone if not provided
+    let user = find_user(&request.u).await?;
+
+    request.n = safety::screen(ContentKind::ChatGroup, &request.u, &request.n).await?.text;
+    request.t = safety::screen(ContentKind::ChatGroup, &request.u, &request.t).await?.text;
+
     // Create embedding from chat group name and text
-    let embedding_text = format!("{} {}", request.n, request.t);
-    let embedding_vec = embedding(embedding_text).await?;
-    
-    let embedding_floats: Vec<f32> = embedding_vec
-        .as_array()
-        .ok_or(AppError::new_plain("Embedding is not an array"))?
-        .iter()
-        .map(|v| v.as_f64().unwrap_or(0.0) as f32)
-        .collect();
+    let embedding = embed(format!("{} {}", request.n, request.t)).await?;
 
-    // Create chat group object
-    let chatgroup_id = id();
-    let point = json!({
-        "id": chatgroup_id,
-        "vector": embedding_floats,
-        "payload": {
-            "n": request.n,               // name
-            "t": request.t,               // text
-            "l": request.l,               // link
-            "i": request.i,               // image
-            "u": request.u,               // user
-            "z": request.z.or(user_data.zone_id), // zone (inherited if not provided)
-            "s": "cg",                    // tenant id for chat groups
-            "a": request.a,               // additional field
-        }
-    });
+    let chatgroup = ChatGroup {
+        name: request.n,
+        text: request.t,
+        link: request.l,
+        image: request.i,
+        user: request.u,
+        zone: request.z.or(user.zone), // inherited if not provided
+        extra: request.a,
+    };
 
-    qdrant_post(
-        &qdrant_path("collections/i/points?wait=true").await?,
-        json!({
-            "points": [point]
-        })
-    ).await?;
+    let chatgroup_id = id();
+    repo::upsert(&chatgroup_id, embedding, &chatgroup).await?;
 
     Ok(chatgroup_id)
 }
-
-#[derive(Debug)]
-struct UserData {
-    zone_id: Option<String>,
-}
-
-async fn get_user_data(user_id: &str) -> AppResult<UserData> {
-    let user_check = qdrant_post(
-        &qdrant_path("collections/i/points").await?,
-        json!({
-            "ids": [user_id],
-            "with_payload": ["z"]
-        })
-    ).await?;
-
-    let user_data = user_check["result"].as_array()
-        .and_then(|arr| arr.first())
-        .ok_or(AppError::new_plain("User not found"))?;
-
-    let payload = &user_data["payload"];
-    
-    Ok(UserData {
-        zone_id: payload["z"].as_str().map(|s| s.to_string()),
-    })
-} 
\ No newline at end of file
@@ -1,7 +1,9 @@
 use warp::{Filter, Reply};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use crate::util::{AppResult, AppError, qdrant::{qdrant_path, qdrant_post}};
+use crate::{
+    repo::{self, models::ChatGroup},
+    util::{AppResult, AppError},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatGroupDeleteRequest {
@@ -29,46 +31,21 @@ pub async fn handler(chatgroup_id: String, request: ChatGroupDeleteRequest) -> i
             let error_msg = format!("Error: {}", e);
             warp::reply::with_status(
                 error_msg,
-                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                e.status(),
             )
         }
     }
 }
 
 async fn delete_chatgroup(chatgroup_id: String, request: ChatGroupDeleteRequest) -> AppResult<()> {
+    let chatgroup = repo::find::<ChatGroup>(&chatgroup_id).await?;
+
     // Check if the user is the owner of the chat group
-    let is_owner = check_ownership(&chatgroup_id, &request.user_id).await?;
-    
-    if !is_owner {
-        return Err(AppError::new_plain("Not authorized to delete this chat group"));
+    if chatgroup.user != request.user_id {
+        return Err(AppError::new_status("Not authorized to delete this chat group", warp::http::StatusCode::FORBIDDEN));
     }
-    
-    // Delete the chat group
-    qdrant_post(
-        &qdrant_path("collections/i/points/delete").await?,
-        json!({
-            "points": [chatgroup_id]
-        })
-    ).await?;
-    
-    Ok(())
-}
 
-async fn check_ownership(chatgroup_id: &str, user_id: &str) -> AppResult<bool> {
-    let response = qdrant_post(
-        &qdrant_path("collections/i/points").await?,
-        json!({
-            "ids": [chatgroup_id],
-            "with_payload": ["u"]
-        })
-    ).await?;
+    repo::delete::<ChatGroup>(&[chatgroup_id.as_str()]).await?;
 
-    let result = response["result"].as_array()
-        .and_then(|arr| arr.first())
-        .ok_or(AppError::new_plain("Chat group not found"))?;
-
-    let payload = &result["payload"];
-    let owner_id = payload["u"].as_str().unwrap_or("");
-    
-    Ok(owner_id == user_id)
-} 
\ No newline at end of file
+    Ok(())
+}
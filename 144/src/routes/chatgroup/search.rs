@@ -1,7 +1,10 @@
 use warp::{Filter, Reply};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use crate::util::{AppResult, AppError, embedding, qdrant::{qdrant_path, qdrant_post}};
+use crate::{
+    repo::{self, keys, models::ChatGroup, Condition},
+    util::{AppResult, embed},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChatGroupSearchRequest {
@@ -32,71 +35,32 @@ pub async fn handler(request: ChatGroupSearchRequest) -> impl Reply {
 }
 
 async fn search_chatgroups(request: ChatGroupSearchRequest) -> AppResult<serde_json::Value> {
-    let limit = request.limit.unwrap_or(20).min(100);
-    let offset = request.offset.unwrap_or(0);
-    
-    // Build filter for tenant_id = "cg" (chat groups)
-    let mut filter = json!({
-        "must": [
-            {
-                "key": "s",
-                "match": {
-                    "value": "cg"
-                }
-            }
-        ]
-    });
-    
+    let limit = request.limit.unwrap_or(20).min(100) as usize;
+    let offset = request.offset.unwrap_or(0) as usize;
+
+    let mut filter = repo::Filter::new();
+
     // Add zone filter if provided
     if let Some(zone_id) = &request.zone_id {
-        filter["must"].as_array_mut().unwrap().push(json!({
-            "key": "z",
-            "match": {
-                "value": zone_id
-            }
-        }));
+        filter = filter.must(Condition::matches(keys::ZONE, zone_id.as_str()));
     }
-    
+
     // Add user filter if provided
     if let Some(user_id) = &request.user_id {
-        filter["must"].as_array_mut().unwrap().push(json!({
-            "key": "u",
-            "match": {
-                "value": user_id
-            }
-        }));
+        filter = filter.must(Condition::matches(keys::USER, user_id.as_str()));
     }
-    
-    // Build search request
-    let mut search_request = json!({
-        "filter": filter,
-        "limit": limit,
-        "offset": offset,
-        "with_payload": true
-    });
-    
-    // Add vector search if query is provided
-    if let Some(query) = &request.query {
-        let embedding_vec = embedding(query.clone()).await?;
-        
-        let embedding_floats: Vec<f32> = embedding_vec
-            .as_array()
-            .ok_or(AppError::new_plain("Embedding is not an array"))?
-            .iter()
-            .map(|v| v.as_f64().unwrap_or(0.0) as f32)
-            .collect();
-            
-        search_request["vector"] = json!(embedding_floats);
-    } else {
-        // If no query provided, sort by creation time (if available) or use simple discovery
-        search_request["with_vectors"] = json!(false);
-    }
-    
-    // Execute search
-    let response = qdrant_post(
-        &qdrant_path("collections/i/points/search").await?,
-        search_request
-    ).await?;
-    
-    Ok(response)
-} 
\ No newline at end of file
+
+    let groups = match &request.query {
+        Some(query) => {
+            let embedding = embed(query.clone()).await?;
+            repo::search_from::<ChatGroup>(&embedding, filter, limit, offset).await?
+        }
+        // Without a query there's nothing to rank by, so list matching groups in storage order
+        None => {
+            let (groups, _) = repo::scroll::<ChatGroup>(filter, offset + limit, None).await?;
+            groups.into_iter().skip(offset).collect()
+        }
+    };
+
+    Ok(json!({ "result": groups }))
+}
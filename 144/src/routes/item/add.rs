@@ -1,28 +1,35 @@
 use warp::{Filter, Reply};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 use crate::{
-    util::{AppResult, AppError, embedding, id, qdrant::{qdrant_path, qdrant_post}},
+    repo,
+    routes::user::find_user,
+    util::{AppResult, AppError, embed, id, with_auth, safety::{self, ContentKind}},
 };
+use super::types::{Listing, ListingKind};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ItemAddRequest {
     pub description: String,
     pub price: f64,
     pub images: Option<Vec<String>>,
-    pub user_id: String,
-    pub item_type: String, // "product" or "service"
+    pub item_type: Option<ListingKind>, // "product" or "service", implied on /product and /service
 }
 
-pub fn route() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
-    warp::path!("item" / "add")
+pub fn route(
+    prefix: &'static str,
+    kind: Option<ListingKind>,
+) -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path(prefix)
+        .and(warp::path("add"))
+        .and(warp::path::end())
         .and(warp::post())
+        .and(with_auth())
         .and(warp::body::json())
-        .then(handler)
+        .then(move |user_id, request| handler(kind, user_id, request))
 }
 
-pub async fn handler(request: ItemAddRequest) -> impl Reply {
-    match add_item(request).await {
+pub async fn handler(kind: Option<ListingKind>, user_id: String, request: ItemAddRequest) -> impl Reply {
+    match add_item(kind, user_id, request).await {
         Ok(item_id) => warp::reply::with_status(
             item_id,
             warp::http::StatusCode::OK,
@@ -31,85 +38,39 @@ pub async fn handler(request: ItemAddRequest) -> impl Reply {
             log::error!("Item add error: {:#?}", e);
             warp::reply::with_status(
                 format!("Error: {}", e),
-                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                e.status(),
             )
         }
     }
 }
 
-async fn add_item(request: ItemAddRequest) -> AppResult<String> {
-    // Validate item type
-    let item_type_code = match request.item_type.as_str() {
-        "product" => "p",
-        "service" => "s",
-        _ => return Err(AppError::new_plain("Invalid item_type. Must be 'product' or 'service'"))
-    };
-    
+async fn add_item(kind: Option<ListingKind>, user_id: String, request: ItemAddRequest) -> AppResult<String> {
+    let kind = kind.or(request.item_type).ok_or_else(|| AppError::new_status(
+        "Missing item_type. Must be 'product' or 'service'",
+        warp::http::StatusCode::BAD_REQUEST,
+    ))?;
+
     // Get user details to inherit zone, location, and position
-    let user_data = get_user_data(&request.user_id).await?;
-    
+    let user = find_user(&user_id).await?;
+
+    let description = safety::screen(ContentKind::Listing, &user_id, &request.description).await?.text;
+
     // Create embedding from item description
-    let embedding_vec = embedding(request.description.clone()).await?;
-    
-    let embedding_floats: Vec<f32> = embedding_vec
-        .as_array()
-        .ok_or(AppError::new_plain("Embedding is not an array"))?
-        .iter()
-        .map(|v| v.as_f64().unwrap_or(0.0) as f32)
-        .collect();
+    let embedding = embed(description.clone()).await?;
 
-    // Create item object
-    let item_id = id();
-    let point = json!({
-        "id": item_id,
-        "vector": embedding_floats,
-        "payload": {
-            "t": request.description,  // description
-            "c": request.price,        // price
-            "u": request.user_id,      // user
-            "z": user_data.zone_id,    // zone (inherited)
-            "images": request.images.unwrap_or_default(),
-            "l": user_data.location,   // location (inherited)
-            "p": user_data.position,   // position (inherited)
-            "s": item_type_code        // tenant id: "p" for products, "s" for services
-        }
-    });
+    let listing = Listing {
+        description,
+        price: request.price,
+        user: user_id,
+        zone: user.zone,
+        images: request.images.unwrap_or_default(),
+        location_url: user.location_url,
+        position: user.position,
+        kind,
+    };
 
-    qdrant_post(
-        &qdrant_path("collections/i/points?wait=true").await?,
-        json!({
-            "points": [point]
-        })
-    ).await?;
+    let item_id = id();
+    repo::upsert(&item_id, embedding, &listing).await?;
 
     Ok(item_id)
 }
-
-#[derive(Debug)]
-struct UserData {
-    zone_id: Option<String>,
-    location: String,
-    position: serde_json::Value,
-}
-
-async fn get_user_data(user_id: &str) -> AppResult<UserData> {
-    let user_check = qdrant_post(
-        &qdrant_path("collections/i/points").await?,
-        json!({
-            "ids": [user_id],
-            "with_payload": ["z", "l", "p"]
-        })
-    ).await?;
-
-    let user_data = user_check["result"].as_array()
-        .and_then(|arr| arr.first())
-        .ok_or(AppError::new_plain("User not found"))?;
-
-    let payload = &user_data["payload"];
-    
-    Ok(UserData {
-        zone_id: payload["z"].as_str().map(|s| s.to_string()),
-        location: payload["l"].as_str().unwrap_or("").to_string(),
-        position: payload["p"].clone(),
-    })
-} 
\ No newline at end of file
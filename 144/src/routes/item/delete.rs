@@ -1,45 +1,43 @@
 use warp::{Filter, Reply};
-use serde_json::json;
-use crate::util::{AppResult, AppError, qdrant::{qdrant_path, qdrant_delete}};
+use crate::{repo, util::{AppResult, AppError, with_auth}};
+use super::{get::find_item, types::{Listing, ListingKind}};
 
-pub fn route() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
-    warp::path!("item" / "delete" / String)
+pub fn route(
+    prefix: &'static str,
+    kind: Option<ListingKind>,
+) -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path(prefix)
+        .and(warp::path("delete"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
         .and(warp::delete())
-        .then(handler)
+        .and(with_auth())
+        .then(move |item_id, user_id| handler(kind, item_id, user_id))
 }
 
-pub async fn handler(item_id: String) -> impl Reply {
-    match delete_item(item_id).await {
+pub async fn handler(kind: Option<ListingKind>, item_id: String, user_id: String) -> impl Reply {
+    match delete_item(kind, &item_id, &user_id).await {
         Ok(_) => warp::reply::with_status(
-            "Item deleted successfully",
+            "Item deleted successfully".to_string(),
             warp::http::StatusCode::OK,
         ),
         Err(e) => {
             log::error!("Item delete error: {:#?}", e);
             warp::reply::with_status(
                 format!("Error: {}", e),
-                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                e.status(),
             )
         }
     }
 }
 
-async fn delete_item(item_id: String) -> AppResult<()> {
-    // Delete the item from the Qdrant collection
-    let delete_result = qdrant_delete(
-        &qdrant_path("collections/i/points").await?,
-        json!({
-            "points": [item_id],
-            "wait": true
-        })
-    ).await?;
-
-    // Check if deletion was successful
-    if let Some(status) = delete_result.get("status") {
-        if status.as_str() == Some("ok") {
-            return Ok(());
-        }
+async fn delete_item(kind: Option<ListingKind>, item_id: &str, user_id: &str) -> AppResult<()> {
+    let listing = find_item(kind, item_id).await?;
+    if listing.user != user_id {
+        return Err(AppError::new_status("Not authorized to delete this item", warp::http::StatusCode::FORBIDDEN));
     }
 
-    Err(AppError::new_plain("Failed to delete item"))
-} 
\ No newline at end of file
+    repo::delete::<Listing>(&[item_id]).await?;
+
+    Ok(())
+}
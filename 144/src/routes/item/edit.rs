@@ -1,9 +1,10 @@
 use warp::{Filter, Reply};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 use crate::{
-    util::{AppResult, AppError, embedding, qdrant::{qdrant_path, qdrant_post, qdrant_put}},
+    repo,
+    util::{AppResult, AppError, embed, with_auth, safety::{self, ContentKind}},
 };
+use super::{get::find_item, types::{Listing, ListingKind, ListingPatch}};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ItemEditRequest {
@@ -12,93 +13,60 @@ pub struct ItemEditRequest {
     pub images: Option<Vec<String>>,
 }
 
-pub fn route() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
-    warp::path!("item" / "edit" / String)
+pub fn route(
+    prefix: &'static str,
+    kind: Option<ListingKind>,
+) -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
+    warp::path(prefix)
+        .and(warp::path("edit"))
+        .and(warp::path::param::<String>())
+        .and(warp::path::end())
         .and(warp::put())
+        .and(with_auth())
         .and(warp::body::json())
-        .then(handler)
+        .then(move |item_id, user_id, request| handler(kind, item_id, user_id, request))
 }
 
-pub async fn handler(item_id: String, request: ItemEditRequest) -> impl Reply {
-    match edit_item(item_id, request).await {
+pub async fn handler(kind: Option<ListingKind>, item_id: String, user_id: String, request: ItemEditRequest) -> impl Reply {
+    match edit_item(kind, &item_id, &user_id, request).await {
         Ok(_) => warp::reply::with_status(
-            "Item updated successfully",
+            "Item updated successfully".to_string(),
             warp::http::StatusCode::OK,
         ),
         Err(e) => {
             log::error!("Item edit error: {:#?}", e);
             warp::reply::with_status(
                 format!("Error: {}", e),
-                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                e.status(),
             )
         }
     }
 }
 
-async fn edit_item(item_id: String, request: ItemEditRequest) -> AppResult<()> {
-    // First, get the current item to check if it exists and to get current values
-    let current_item = qdrant_post(
-        &qdrant_path("collections/i/points").await?,
-        json!({
-            "ids": [item_id],
-            "with_payload": true,
-            "with_vector": true
-        })
-    ).await?;
-
-    let item = current_item["result"].as_array()
-        .and_then(|arr| arr.first())
-        .ok_or(AppError::new_plain("Item not found"))?;
-
-    let mut payload = item["payload"].clone();
-    let mut needs_vector_update = false;
-
-    // Update description if provided
-    if let Some(description) = request.description {
-        payload["t"] = json!(description);
-        needs_vector_update = true;
-    }
-
-    // Update price if provided
-    if let Some(price) = request.price {
-        payload["c"] = json!(price);
+/// partially updates a listing owned by `user_id`, re-embedding it when the description changes
+async fn edit_item(kind: Option<ListingKind>, item_id: &str, user_id: &str, mut request: ItemEditRequest) -> AppResult<()> {
+    let listing = find_item(kind, item_id).await?;
+    if listing.user != user_id {
+        return Err(AppError::new_status("Not authorized to edit this item", warp::http::StatusCode::FORBIDDEN));
     }
 
-    // Update images if provided
-    if let Some(images) = request.images {
-        payload["images"] = json!(images);
+    if let Some(description) = &request.description {
+        request.description = Some(safety::screen(ContentKind::Listing, user_id, description).await?.text);
     }
 
-    // Create updated point
-    let mut update_data = json!({
-        "points": [
-            {
-                "id": item_id,
-                "payload": payload
-            }
-        ]
-    });
-
     // If description changed, update the vector embedding
-    if needs_vector_update {
-        let description = payload["t"].as_str().unwrap_or("");
-        let embedding_vec = embedding(description.to_string()).await?;
-        
-        let embedding_floats: Vec<f32> = embedding_vec
-            .as_array()
-            .ok_or(AppError::new_plain("Embedding is not an array"))?
-            .iter()
-            .map(|v| v.as_f64().unwrap_or(0.0) as f32)
-            .collect();
-
-        update_data["points"][0]["vector"] = json!(embedding_floats);
+    if let Some(description) = request.description.as_ref().filter(|d| **d != listing.description) {
+        let embedding = embed(description.clone()).await?;
+        repo::update_vector::<Listing>(item_id, embedding).await?;
     }
 
-    // Send the update request
-    qdrant_put(
-        &qdrant_path("collections/i/points?wait=true").await?,
-        update_data
-    ).await?;
+    let patch = ListingPatch {
+        description: request.description,
+        price: request.price,
+        images: request.images,
+        ..Default::default()
+    };
+    repo::set_payload::<Listing>(&[item_id], &patch).await?;
 
     Ok(())
-} 
\ No newline at end of file
+}
@@ -3,13 +3,24 @@ pub mod search;
 pub mod edit;
 pub mod delete;
 pub mod get;
+pub mod types;
 
 use warp::Filter;
+use types::ListingKind;
 
 pub fn routes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    add::route()
-        .or(search::route())
-        .or(edit::route())
-        .or(delete::route())
-        .or(get::route())
-} 
\ No newline at end of file
+    listing_routes("item", None)
+}
+
+/// Marketplace listing routes under `/{prefix}`. With a `kind` they only see listings of
+/// that kind, which is how `/product/*` and `/service/*` are served.
+pub fn listing_routes(
+    prefix: &'static str,
+    kind: Option<ListingKind>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    add::route(prefix, kind)
+        .or(search::route(prefix, kind))
+        .or(edit::route(prefix, kind))
+        .or(delete::route(prefix, kind))
+        .or(get::route(prefix, kind))
+}
@@ -0,0 +1,36 @@
+use serde::Serialize;
+
+pub use crate::repo::models::{Listing, ListingKind, ListingPatch, Position};
+
+/// A listing as returned by get and search
+#[derive(Debug, Serialize)]
+pub struct ListingResponse {
+    pub id: String,
+    pub description: String,
+    pub price: f64,
+    pub user_id: String,
+    pub zone_id: Option<String>,
+    pub images: Vec<String>,
+    pub location: String,
+    pub position: Option<Position>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<f32>,
+    pub item_type: ListingKind,
+}
+
+impl ListingResponse {
+    pub fn new(id: String, listing: Listing, score: Option<f32>) -> Self {
+        ListingResponse {
+            id,
+            description: listing.description,
+            price: listing.price,
+            user_id: listing.user,
+            zone_id: listing.zone,
+            images: listing.images,
+            location: listing.location_url,
+            position: listing.position,
+            score,
+            item_type: listing.kind,
+        }
+    }
+}
@@ -14,3 +14,4 @@ pub mod product;
 pub mod service;
 pub mod chatgroup;
 pub mod item;
+pub mod moderation;
@@ -0,0 +1,87 @@
+//! Per-user block lists.
+//!
+//! A block works both ways: once either user blocked the other, chat matching, voice chat
+//! matching and voice chat signaling keep them apart. Each block is one point in
+//! [`BLOCKS_COLLECTION`], keyed by the pair, so blocking twice changes nothing.
+
+use serde_json::json;
+use std::collections::HashSet;
+use warp::http::StatusCode;
+
+use crate::repo::{store, Condition, Filter, Point, Selector};
+use crate::routes::chat::{matching, storage::scroll_all, websocket};
+use crate::util::{stable_id, AppError, AppResult};
+
+pub const BLOCKS_COLLECTION: &str = "blocks";
+
+fn block_id(blocker_id: &str, blocked_id: &str) -> String {
+    stable_id(&[blocker_id, blocked_id])
+}
+
+/// Blocks `blocked_id` for `blocker_id`, ending any chat the two are in
+pub async fn block(blocker_id: &str, blocked_id: &str) -> AppResult<()> {
+    if blocker_id == blocked_id {
+        return Err(AppError::new_status("You can't block yourself", StatusCode::BAD_REQUEST));
+    }
+    let payload = json!({
+        "blocker_id": blocker_id,
+        "blocked_id": blocked_id,
+        "created_at": chrono::Utc::now().timestamp(),
+    });
+    store()
+        .upsert(BLOCKS_COLLECTION, vec![Point {
+            id: block_id(blocker_id, blocked_id),
+            vector: Some(vec![0.0]),
+            payload: payload.as_object().cloned().unwrap_or_default(),
+            score: None,
+        }])
+        .await?;
+
+    matching::apply_block(blocker_id, blocked_id).await;
+    websocket::end_sessions_between(blocker_id, blocked_id).await;
+    Ok(())
+}
+
+pub async fn unblock(blocker_id: &str, blocked_id: &str) -> AppResult<()> {
+    store()
+        .delete(BLOCKS_COLLECTION, Selector::ids([block_id(blocker_id, blocked_id)]))
+        .await
+}
+
+/// The users `user_id` blocked
+pub async fn blocked_by(user_id: &str) -> AppResult<Vec<String>> {
+    let filter = Filter::new().must(Condition::matches("blocker_id", user_id));
+    Ok(scroll_all(BLOCKS_COLLECTION, &filter)
+        .await?
+        .into_iter()
+        .filter_map(|point| Some(point.payload.get("blocked_id")?.as_str()?.to_string()))
+        .collect())
+}
+
+/// Everyone `user_id` must be kept apart from: the users they blocked and the users who
+/// blocked them
+pub async fn block_set(user_id: &str) -> AppResult<HashSet<String>> {
+    let filter = Filter::new()
+        .should(Condition::matches("blocker_id", user_id))
+        .should(Condition::matches("blocked_id", user_id));
+    Ok(scroll_all(BLOCKS_COLLECTION, &filter)
+        .await?
+        .into_iter()
+        .filter_map(|point| {
+            let other = |key: &str| point.payload.get(key)?.as_str().map(str::to_string);
+            match other("blocker_id")? {
+                blocker if blocker == user_id => other("blocked_id"),
+                blocker => Some(blocker),
+            }
+        })
+        .collect())
+}
+
+/// Whether either user blocked the other
+pub async fn is_blocked(a: &str, b: &str) -> AppResult<bool> {
+    let ids = [block_id(a, b), block_id(b, a)];
+    let points = store()
+        .retrieve(BLOCKS_COLLECTION, &[ids[0].as_str(), ids[1].as_str()], false)
+        .await?;
+    Ok(!points.is_empty())
+}
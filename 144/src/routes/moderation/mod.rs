@@ -0,0 +1,110 @@
+pub mod blocks;
+pub mod reports;
+pub mod types;
+
+use warp::Filter;
+use crate::util::{safety, with_admin, with_auth};
+use types::{BlockRequest, FlaggedQuery, ReportRequest, ReportStatus, ReportsQuery, ResolveRequest};
+
+pub fn routes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    block_route()
+        .or(unblock_route())
+        .or(blocks_route())
+        .or(report_route())
+        .or(queue_route())
+        .or(resolve_route())
+        .or(flagged_route())
+        .or(review_route())
+}
+
+fn block_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("moderation" / "block")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_auth())
+        .and_then(|request: BlockRequest, user_id: String| async move {
+            blocks::block(&user_id, &request.user_id).await.map_err(warp::reject::custom)?;
+            Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({ "success": true })))
+        })
+}
+
+fn unblock_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("moderation" / "block" / String)
+        .and(warp::delete())
+        .and(with_auth())
+        .and_then(|blocked_id: String, user_id: String| async move {
+            blocks::unblock(&user_id, &blocked_id).await.map_err(warp::reject::custom)?;
+            Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({ "success": true })))
+        })
+}
+
+fn blocks_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("moderation" / "blocks")
+        .and(warp::get())
+        .and(with_auth())
+        .and_then(|user_id: String| async move {
+            let blocked = blocks::blocked_by(&user_id).await.map_err(warp::reject::custom)?;
+            Ok::<_, warp::Rejection>(warp::reply::json(&blocked))
+        })
+}
+
+fn report_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("moderation" / "report")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_auth())
+        .and_then(|request: ReportRequest, user_id: String| async move {
+            let report = reports::report(&user_id, request).await.map_err(warp::reject::custom)?;
+            Ok::<_, warp::Rejection>(warp::reply::json(&serde_json::json!({ "id": report.id })))
+        })
+}
+
+/// the moderation queue, admins only
+fn queue_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("moderation" / "reports")
+        .and(warp::get())
+        .and(warp::query::<ReportsQuery>())
+        .and(with_admin())
+        .and_then(|query: ReportsQuery, _admin_id: String| async move {
+            let status = query.status.unwrap_or(ReportStatus::Open);
+            let limit = query.limit.unwrap_or(50).clamp(1, 200);
+            let reports = reports::queue(status, limit).await.map_err(warp::reject::custom)?;
+            Ok::<_, warp::Rejection>(warp::reply::json(&reports))
+        })
+}
+
+fn resolve_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("moderation" / "reports" / String)
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(with_admin())
+        .and_then(|report_id: String, request: ResolveRequest, admin_id: String| async move {
+            let report = reports::resolve(&admin_id, &report_id, request.status, request.note).await
+                .map_err(warp::reject::custom)?;
+            Ok::<_, warp::Rejection>(warp::reply::json(&report))
+        })
+}
+
+/// text the safety filter flagged, admins only
+fn flagged_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("moderation" / "flagged")
+        .and(warp::get())
+        .and(warp::query::<FlaggedQuery>())
+        .and(with_admin())
+        .and_then(|query: FlaggedQuery, _admin_id: String| async move {
+            let limit = query.limit.unwrap_or(50).clamp(1, 200);
+            let flagged = safety::flagged(query.reviewed.unwrap_or(false), limit).await
+                .map_err(warp::reject::custom)?;
+            Ok::<_, warp::Rejection>(warp::reply::json(&flagged))
+        })
+}
+
+fn review_route() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("moderation" / "flagged" / String)
+        .and(warp::post())
+        .and(with_admin())
+        .and_then(|flagged_id: String, admin_id: String| async move {
+            let flagged = safety::review(&admin_id, &flagged_id).await.map_err(warp::reject::custom)?;
+            Ok::<_, warp::Rejection>(warp::reply::json(&flagged))
+        })
+}
@@ -0,0 +1,122 @@
+//! User reports and the admin moderation queue.
+//!
+//! A chat report keeps a snapshot of the session's latest [`SNAPSHOT_MESSAGES`] messages,
+//! so moderators see what was said even after messages are edited or deleted. Reports wait
+//! in [`REPORTS_COLLECTION`] as [`ReportStatus::Open`] until an admin resolves them.
+
+use serde_json::Value;
+use warp::http::StatusCode;
+
+use super::blocks;
+use super::types::{Report, ReportContext, ReportRequest, ReportStatus};
+use crate::repo::{store, Condition, Direction, Filter, OrderBy, Point};
+use crate::routes::chat::storage;
+use crate::util::{self, AppError, AppResult};
+
+pub const REPORTS_COLLECTION: &str = "reports";
+
+pub const SNAPSHOT_MESSAGES: usize = 50;
+
+const MAX_REASON_CHARS: usize = 2000;
+
+/// Files a report from `reporter_id`, blocking the reported user too if asked
+pub async fn report(reporter_id: &str, request: ReportRequest) -> AppResult<Report> {
+    if request.reported_id == reporter_id {
+        return Err(AppError::new_status("You can't report yourself", StatusCode::BAD_REQUEST));
+    }
+    let reason = request.reason.trim();
+    if reason.is_empty() || reason.chars().count() > MAX_REASON_CHARS {
+        return Err(AppError::new_status(
+            &format!("Give a reason of up to {} characters", MAX_REASON_CHARS),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+
+    let messages = match (request.context, &request.session_id) {
+        (ReportContext::Chat, None) => {
+            return Err(AppError::new_status("Chat reports need a session_id", StatusCode::BAD_REQUEST));
+        }
+        (ReportContext::Chat, Some(session_id)) => {
+            // both users have to have been in the session
+            let met = storage::is_participant(session_id, reporter_id).await?
+                && storage::is_participant(session_id, &request.reported_id).await?;
+            if !met {
+                return Err(storage::not_participant());
+            }
+            storage::get_session_messages(session_id, None, SNAPSHOT_MESSAGES).await?.messages
+        }
+        (ReportContext::Voice, _) => Vec::new(),
+    };
+
+    let report = Report {
+        id: util::id(),
+        reporter_id: reporter_id.to_string(),
+        reported_id: request.reported_id,
+        context: request.context,
+        session_id: request.session_id,
+        reason: reason.to_string(),
+        messages,
+        status: ReportStatus::Open,
+        created_at: chrono::Utc::now().timestamp(),
+        resolved_at: None,
+        resolved_by: None,
+        note: None,
+    };
+    save(&report).await?;
+
+    if request.block {
+        blocks::block(reporter_id, &report.reported_id).await?;
+    }
+    Ok(report)
+}
+
+async fn save(report: &Report) -> AppResult<()> {
+    let mut payload = match serde_json::to_value(report) {
+        Ok(Value::Object(payload)) => payload,
+        Ok(_) => Default::default(),
+        Err(e) => return Err(AppError::new("Failed to serialize report", e)),
+    };
+    payload.remove("id");
+    store()
+        .upsert(REPORTS_COLLECTION, vec![Point {
+            id: report.id.clone(),
+            vector: Some(vec![0.0]),
+            payload,
+            score: None,
+        }])
+        .await
+}
+
+fn parse_reports(points: Vec<Point>) -> Vec<Report> {
+    points
+        .into_iter()
+        .filter_map(|point| {
+            let mut payload = point.payload;
+            payload.insert("id".to_string(), point.id.into());
+            serde_json::from_value(payload.into()).ok()
+        })
+        .collect()
+}
+
+/// Reports with `status`, oldest first
+pub async fn queue(status: ReportStatus, limit: usize) -> AppResult<Vec<Report>> {
+    let filter = Filter::new().must(Condition::matches("status", status.as_str()));
+    let order = OrderBy::new("created_at", Direction::Asc);
+    Ok(parse_reports(store().scroll_ordered(REPORTS_COLLECTION, &filter, &order, limit).await?))
+}
+
+/// Closes a report as dismissed or actioned
+pub async fn resolve(admin_id: &str, report_id: &str, status: ReportStatus, note: Option<String>) -> AppResult<Report> {
+    if status == ReportStatus::Open {
+        return Err(AppError::new_status("Resolve a report as dismissed or actioned", StatusCode::BAD_REQUEST));
+    }
+    let mut report = parse_reports(store().retrieve(REPORTS_COLLECTION, &[report_id], false).await?)
+        .pop()
+        .ok_or_else(|| AppError::new_status("Report not found", StatusCode::NOT_FOUND))?;
+    report.status = status;
+    report.resolved_at = Some(chrono::Utc::now().timestamp());
+    report.resolved_by = Some(admin_id.to_string());
+    report.note = note;
+    save(&report).await?;
+    Ok(report)
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+use crate::routes::chat::types::ChatMessage;
+
+/// Where the reported user was met
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportContext {
+    Chat,
+    Voice,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportStatus {
+    /// waiting in the moderation queue
+    Open,
+    /// looked at, nothing to do
+    Dismissed,
+    /// looked at and acted on
+    Actioned,
+}
+
+impl ReportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportStatus::Open => "open",
+            ReportStatus::Dismissed => "dismissed",
+            ReportStatus::Actioned => "actioned",
+        }
+    }
+}
+
+/// A user's report about someone they met. Stored in the `reports` collection, which
+/// admins work through oldest first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub id: String,
+    pub reporter_id: String,
+    pub reported_id: String,
+    pub context: ReportContext,
+    pub session_id: Option<String>,
+    pub reason: String,
+    /// the latest messages of the chat session when it was reported
+    #[serde(default)]
+    pub messages: Vec<ChatMessage>,
+    pub status: ReportStatus,
+    pub created_at: i64,
+    #[serde(default)]
+    pub resolved_at: Option<i64>,
+    #[serde(default)]
+    pub resolved_by: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportRequest {
+    pub reported_id: String,
+    pub context: ReportContext,
+    /// the chat session the report is about, required for chat reports
+    pub session_id: Option<String>,
+    pub reason: String,
+    /// block the reported user as well, which is what reporters almost always want
+    #[serde(default = "default_true")]
+    pub block: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BlockRequest {
+    pub user_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReportsQuery {
+    pub status: Option<ReportStatus>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ResolveRequest {
+    pub status: ReportStatus,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FlaggedQuery {
+    pub reviewed: Option<bool>,
+    pub limit: Option<usize>,
+}
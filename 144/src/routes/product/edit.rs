@@ -1,16 +0,0 @@
-use warp::{Filter, Reply};
-
-pub fn route() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
-    warp::path!("product" / "edit" / String)
-        .and(warp::put())
-        .and(warp::body::json())
-        .then(handler)
-}
-
-pub async fn handler(product_id: String, _body: serde_json::Value) -> impl Reply {
-    // TODO: Implement product editing
-    warp::reply::with_status(
-        format!("Product edit not yet implemented for product: {}", product_id),
-        warp::http::StatusCode::NOT_IMPLEMENTED,
-    )
-} 
\ No newline at end of file
@@ -1,16 +0,0 @@
-use warp::{Filter, Reply};
-
-pub fn route() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
-    warp::path!("service" / "edit" / String)
-        .and(warp::put())
-        .and(warp::body::json())
-        .then(handler)
-}
-
-pub async fn handler(service_id: String, _body: serde_json::Value) -> impl Reply {
-    // TODO: Implement service editing
-    warp::reply::with_status(
-        format!("Service edit not yet implemented for service: {}", service_id),
-        warp::http::StatusCode::NOT_IMPLEMENTED,
-    )
-} 
\ No newline at end of file
@@ -1,13 +1,10 @@
-pub mod add;
-pub mod search;
-pub mod edit;
-pub mod delete;
+//! `/service/*` routes, kept for existing clients. They are the `item` handlers limited to
+//! services, so search results come back under `services` instead of `items`.
 
 use warp::Filter;
 
+use super::item::{listing_routes, types::ListingKind};
+
 pub fn routes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    add::route()
-        .or(search::route())
-        .or(edit::route())
-        .or(delete::route())
-} 
\ No newline at end of file
+    listing_routes("service", Some(ListingKind::Service))
+}
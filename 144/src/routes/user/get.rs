@@ -1,9 +1,9 @@
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
 use warp::{Filter, Reply, Rejection};
 use serde_json::json;
 
-use crate::util::{AppError, AppResult};
-use crate::util::qdrant::{qdrant_path, qdrant_post};
+use crate::util::AppResult;
+use super::find_user;
 
 #[derive(Debug, Serialize)]
 pub struct UserResponse {
@@ -39,40 +39,21 @@ pub async fn r(user_id: String) -> Result<impl Reply, Rejection> {
 
 async fn f(user_id: String) -> AppResult<UserResponse> {
     log::info!("Fetching user with ID: {}", user_id);
-    
-    // Retrieve user from Qdrant
-    let user_result = qdrant_post(
-        &qdrant_path("collections/i/points").await?,
-        json!({
-            "ids": [user_id],
-            "with_payload": true
-        })
-    ).await?;
-    
-    let user_points = user_result["result"].as_array()
-        .ok_or_else(|| AppError::new_plain("Invalid response format"))?;
-        
-    if user_points.is_empty() {
-        return Err(AppError::new_plain("User not found"));
-    }
-    
-    let user_data = &user_points[0];
-    let payload = &user_data["payload"];
-    
-    // Extract user properties from payload
-    // The payload keys are shortened to save space (n = name, t = description, etc.)
+
+    let user = find_user(&user_id).await?;
+
     let user = UserResponse {
         id: user_id,
-        name: payload["n"].as_str().map(|s| s.to_string()),
-        email: payload["email"].as_str().map(|s| s.to_string()),
-        description: payload["t"].as_str().map(|s| s.to_string()),
-        username: payload["u"].as_str().map(|s| s.to_string()),
-        picture: payload["p"].as_str().map(|s| s.to_string()),
-        age: payload["age"].as_i64().map(|i| i as i32),
-        gender: payload["g"].as_str().map(|s| s.to_string()),
-        zone_id: payload["z"].as_str().map(|s| s.to_string()),
+        name: Some(user.name),
+        email: user.email,
+        description: Some(user.about),
+        username: Some(user.username),
+        picture: user.images.into_iter().next(),
+        age: user.age,
+        gender: user.gender,
+        zone_id: user.zone,
     };
-    
+
     log::debug!("Successfully retrieved user data for ID: {}", user.id);
     Ok(user)
-} 
\ No newline at end of file
+}
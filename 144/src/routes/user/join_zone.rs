@@ -1,19 +1,21 @@
 use warp::{Filter, Reply};
-use serde_json::json;
 use crate::{
-    util::{AppResult, AppError, qdrant::{qdrant_path, qdrant_post, qdrant_get}},
+    repo::{self, models::{User, UserPatch}},
+    util::{AppResult, with_auth},
 };
-use super::super::zone::types::UserJoinZoneRequest;
+use super::find_user;
+use super::super::zone::{members::{find_zone, update_member_count}, types::UserJoinZoneRequest};
 
 pub fn route() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
     warp::path!("user" / "join_zone")
         .and(warp::post())
+        .and(with_auth())
         .and(warp::body::json())
         .then(handler)
 }
 
-pub async fn handler(request: UserJoinZoneRequest) -> impl Reply {
-    match join_zone(request).await {
+pub async fn handler(user_id: String, request: UserJoinZoneRequest) -> impl Reply {
+    match join_zone(&user_id, request).await {
         Ok(message) => warp::reply::with_status(
             message,
             warp::http::StatusCode::OK,
@@ -22,38 +24,34 @@ pub async fn handler(request: UserJoinZoneRequest) -> impl Reply {
             log::error!("Join zone error: {:#?}", e);
             warp::reply::with_status(
                 format!("Error: {}", e),
-                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                e.status(),
             )
         }
     }
 }
 
-async fn join_zone(request: UserJoinZoneRequest) -> AppResult<String> {
-    // TODO: Get user ID from authentication context
-    // For now, using a placeholder user ID
-    let user_id = "placeholder_user_id";
+async fn join_zone(user_id: &str, request: UserJoinZoneRequest) -> AppResult<String> {
+    find_zone(&request.zone_id).await?;
+    let user = find_user(user_id).await?;
 
-    // Validate zone exists
-    let zone_check = qdrant_post(
-        &qdrant_path("collections/i/points").await?,
-        json!({
-            "ids": [request.zone_id],
-            "with_payload": true
-        })
-    ).await?;
-
-    if zone_check["result"].as_array().unwrap_or(&vec![]).is_empty() {
-        return Err(AppError::new_plain("Zone not found"));
+    let previous_zone = user.zone;
+    if previous_zone.as_deref() == Some(&request.zone_id) {
+        return Ok(format!("Already in zone: {}", request.zone_id));
     }
 
     // Update user's zone field
-    qdrant_post(
-        &qdrant_path("collections/i/points/payload?wait=true").await?,
-        json!({
-            "payload": {"z": request.zone_id},
-            "points": [user_id]
-        })
+    repo::set_payload::<User>(
+        &[user_id],
+        &UserPatch { zone: Some(Some(request.zone_id.clone())), ..Default::default() },
     ).await?;
 
+    update_member_count(&request.zone_id).await?;
+    if let Some(previous_zone) = previous_zone {
+        // the old zone may have been deleted since the user joined it
+        if let Err(e) = update_member_count(&previous_zone).await {
+            log::warn!("Failed to update member count of zone {}: {}", previous_zone, e);
+        }
+    }
+
     Ok(format!("Successfully joined zone: {}", request.zone_id))
-} 
\ No newline at end of file
+}
@@ -1,19 +1,21 @@
 use warp::{Filter, Reply};
-use serde_json::json;
 use crate::{
-    util::{AppResult, AppError, qdrant::{qdrant_path, qdrant_post}},
+    repo::{self, models::{User, UserPatch}},
+    util::{AppResult, AppError, with_auth},
 };
-use super::super::zone::types::UserLeaveZoneRequest;
+use super::find_user;
+use super::super::zone::{members::{find_zone, update_member_count}, types::UserLeaveZoneRequest};
 
 pub fn route() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
     warp::path!("user" / "leave_zone")
         .and(warp::post())
+        .and(with_auth())
         .and(warp::body::json())
         .then(handler)
 }
 
-pub async fn handler(request: UserLeaveZoneRequest) -> impl Reply {
-    match leave_zone(request).await {
+pub async fn handler(user_id: String, request: UserLeaveZoneRequest) -> impl Reply {
+    match leave_zone(&user_id, request).await {
         Ok(message) => warp::reply::with_status(
             message,
             warp::http::StatusCode::OK,
@@ -22,60 +24,28 @@ pub async fn handler(request: UserLeaveZoneRequest) -> impl Reply {
             log::error!("Leave zone error: {:#?}", e);
             warp::reply::with_status(
                 format!("Error: {}", e),
-                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                e.status(),
             )
         }
     }
 }
 
-async fn leave_zone(request: UserLeaveZoneRequest) -> AppResult<String> {
-    // TODO: Get user ID from authentication context
-    // For now, using a placeholder user ID
-    let user_id = "placeholder_user_id";
+async fn leave_zone(user_id: &str, request: UserLeaveZoneRequest) -> AppResult<String> {
+    // 404 for a zone that doesn't exist
+    find_zone(&request.zone_id).await?;
 
-    // Get zone details to check if user created it
-    let zone_check = qdrant_post(
-        &qdrant_path("collections/i/points").await?,
-        json!({
-            "ids": [request.zone_id],
-            "with_payload": true
-        })
-    ).await?;
-
-    let zone_data = zone_check["result"].as_array()
-        .and_then(|arr| arr.first())
-        .ok_or(AppError::new_plain("Zone not found"))?;
-
-    // TODO: Check if user created this zone
-    // This would require storing creator information in the zone payload
-    // For now, we'll allow leaving any zone
-    
-    // Validate zone exists and user is in it
-    let user_check = qdrant_post(
-        &qdrant_path("collections/i/points").await?,
-        json!({
-            "ids": [user_id],
-            "with_payload": ["z"]
-        })
-    ).await?;
-
-    let user_data = user_check["result"].as_array()
-        .and_then(|arr| arr.first())
-        .ok_or(AppError::new_plain("User not found"))?;
-
-    let current_zone = user_data["payload"]["z"].as_str();
-    if current_zone != Some(&request.zone_id) {
-        return Err(AppError::new_plain("User is not in this zone"));
+    let user = find_user(user_id).await?;
+    if user.zone.as_deref() != Some(&request.zone_id) {
+        return Err(AppError::new_status("User is not in this zone", warp::http::StatusCode::BAD_REQUEST));
     }
 
     // Remove user from zone by clearing the zone field
-    qdrant_post(
-        &qdrant_path("collections/i/points/payload?wait=true").await?,
-        json!({
-            "payload": {"z": null},
-            "points": [user_id]
-        })
+    repo::set_payload::<User>(
+        &[user_id],
+        &UserPatch { zone: Some(None), ..Default::default() },
     ).await?;
 
+    update_member_count(&request.zone_id).await?;
+
     Ok(format!("Successfully left zone: {}", request.zone_id))
-} 
\ No newline at end of file
+}
@@ -6,10 +6,18 @@ pub mod get;
 
 use warp::Filter;
 
+use crate::repo::{self, models::User};
+use crate::util::AppResult;
+
 pub fn routes() -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     search::route()
         .or(similarity::route())
         .or(join_zone::route())
         .or(leave_zone::route())
         .or(get::route())
-} 
\ No newline at end of file
+}
+
+/// fetches a user, 404 if the point is missing or isn't a user
+pub async fn find_user(user_id: &str) -> AppResult<User> {
+    repo::find::<User>(user_id).await
+}
@@ -6,14 +6,10 @@ use reqwest::Client;
 
 use crate::util::{AppError, AppResult, embed};
 use crate::util::qdrant::{qdrant_path, qdrant_get, qdrant_post};
-use crate::constants;
+use crate::repo::schema::VOICE_CHAT_USERS_COLLECTION;
+use crate::routes::moderation::blocks;
 use super::user_tags::UserTagsMetadata;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct MatchRequest {
-    pub user_id: String,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MatchResponse {
     pub matched_user_id: Option<String>,
@@ -21,9 +17,9 @@ pub struct MatchResponse {
     pub score: Option<f32>,
 }
 
-/// Find a matching user based on semantic similarity
-pub async fn find_match(request: MatchRequest) -> Result<impl Reply, Infallible> {
-    match process_match_request(request).await {
+/// Find a matching user for the authenticated user based on semantic similarity
+pub async fn find_match(user_id: String) -> Result<impl Reply, Infallible> {
+    match process_match_request(&user_id).await {
         Ok(response) => Ok(warp::reply::json(&response)),
         Err(e) => {
             eprintln!("Error finding match: {}", e);
@@ -32,12 +28,12 @@ pub async fn find_match(request: MatchRequest) -> Result<impl Reply, Infallible>
     }
 }
 
-async fn process_match_request(request: MatchRequest) -> AppResult<MatchResponse> {
-    let collection_name = "voice_chat_users";
+async fn process_match_request(user_id: &str) -> AppResult<MatchResponse> {
+    let collection_name = VOICE_CHAT_USERS_COLLECTION;
     
     // First, get the user's tags from Qdrant
     let user_point = qdrant_get(
-        &qdrant_path(&format!("collections/{}/points/{}", collection_name, request.user_id)).await?
+        &qdrant_path(&format!("collections/{}/points/{}", collection_name, user_id)).await?
     ).await?;
     
     let user_data = user_point.get("result")
@@ -48,20 +44,8 @@ async fn process_match_request(request: MatchRequest) -> AppResult<MatchResponse
         .and_then(|t| t.as_str())
         .ok_or_else(|| AppError::new_plain("User tags not found"))?;
     
-    // Access the API key
-    let api_key = match std::env::var("GEMINI_API_KEY") {
-        Ok(key) => key,
-        Err(_) => {
-            let secrets = constants::get_secrets().await
-                .map_err(|e| AppError::new("accessing secrets", e))?;
-            secrets.get("GEMINI_API_KEY")
-                .ok_or_else(|| AppError::new_plain("GEMINI_API_KEY not found in secrets"))?
-                .to_string()
-        }
-    };
-    
     // Now search for similar users
-    let search_result = search_similar_users(user_tags, &api_key, request.user_id.clone()).await?;
+    let search_result = search_similar_users(user_tags, user_id).await?;
     
     // If no matches found
     if search_result.is_empty() {
@@ -89,25 +73,25 @@ struct MatchResult {
     score: f32,
 }
 
-async fn search_similar_users(user_tags: &str, api_key: &str, current_user_id: String) -> AppResult<Vec<MatchResult>> {
+async fn search_similar_users(user_tags: &str, current_user_id: &str) -> AppResult<Vec<MatchResult>> {
     // Generate embedding for user's tags
     let embedding = embed(user_tags.to_string()).await?;
     
+    // Leave out the user and anyone they blocked or were blocked by
+    let blocked: Vec<String> = blocks::block_set(current_user_id).await?.into_iter().collect();
+    let mut must_not = vec![json!({"key": "user_id", "match": {"value": current_user_id}})];
+    if !blocked.is_empty() {
+        must_not.push(json!({"key": "user_id", "match": {"any": blocked}}));
+    }
+    
     // Search in Qdrant
-    let collection_name = "voice_chat_users";
+    let collection_name = VOICE_CHAT_USERS_COLLECTION;
     let search_body = json!({
         "vector": embedding,
         "limit": 5,
         "with_payload": true,
         "filter": {
-            "must_not": [
-                {
-                    "key": "user_id",
-                    "match": {
-                        "value": current_user_id
-                    }
-                }
-            ]
+            "must_not": must_not
         }
     });
     
@@ -1,6 +1,6 @@
 use warp::Filter;
 use serde::{Deserialize, Serialize};
-use crate::util::AppResult;
+use crate::util::{with_auth, AppResult};
 
 pub mod user_tags;
 pub mod matching;
@@ -20,18 +20,20 @@ pub fn routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejecti
         .and(warp::path("register"))
         .and(warp::post())
         .and(warp::body::json())
+        .and(with_auth())
         .and_then(user_tags::register_user);
     
     let find_match_route = voice_chat_routes
         .and(warp::path("match"))
         .and(warp::post())
-        .and(warp::body::json())
+        .and(with_auth())
         .and_then(matching::find_match);
 
     let signaling_route = voice_chat_routes
         .and(warp::path("signal"))
         .and(warp::post())
         .and(warp::body::json())
+        .and(with_auth())
         .and_then(signaling::relay_signal);
     
     register_route.or(find_match_route).or(signaling_route)
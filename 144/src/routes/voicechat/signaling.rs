@@ -6,15 +6,17 @@ use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 use once_cell::sync::Lazy;
 
+use crate::routes::moderation::blocks;
 use crate::util::{AppError, AppResult};
+use warp::http::StatusCode;
 
 // In-memory storage for pending signals
 static SIGNAL_STORE: Lazy<Arc<Mutex<HashMap<String, Vec<SignalData>>>>> = 
     Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
 
+/// The sender is the authenticated user, never a field of the body
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignalRequest {
-    pub from_user_id: String,
     pub to_user_id: String,
     pub signal_type: String,  // "offer", "answer", "ice-candidate"
     pub signal_data: String,  // JSON stringified WebRTC data
@@ -35,20 +37,25 @@ pub struct SignalResponse {
 }
 
 /// Relay WebRTC signaling data between users
-pub async fn relay_signal(request: SignalRequest) -> Result<impl Reply, Infallible> {
-    match process_signal(request).await {
+pub async fn relay_signal(request: SignalRequest, from_user_id: String) -> Result<impl Reply, Infallible> {
+    match process_signal(&from_user_id, request).await {
         Ok(response) => Ok(warp::reply::json(&response)),
         Err(e) => {
             eprintln!("Error relaying signal: {}", e);
-            Ok(warp::reply::json(&json!({"error": e.to_string(), "status": "error", "code": 500})))
+            Ok(warp::reply::json(&json!({"error": e.to_string(), "status": "error", "code": e.status().as_u16()})))
         }
     }
 }
 
-async fn process_signal(request: SignalRequest) -> AppResult<SignalResponse> {
+async fn process_signal(from_user_id: &str, request: SignalRequest) -> AppResult<SignalResponse> {
+    // A blocked user can't reach the user who blocked them, nor the other way round
+    if blocks::is_blocked(from_user_id, &request.to_user_id).await? {
+        return Err(AppError::new_status("You can't signal this user", StatusCode::FORBIDDEN));
+    }
+    
     // Create signal data with timestamp
     let signal_data = SignalData {
-        from_user_id: request.from_user_id.clone(),
+        from_user_id: from_user_id.to_string(),
         signal_type: request.signal_type.clone(),
         signal_data: request.signal_data.clone(),
         timestamp: chrono::Utc::now(),
@@ -71,7 +78,7 @@ async fn process_signal(request: SignalRequest) -> AppResult<SignalResponse> {
     // Retrieve pending signals for the sender
     let pending_signals = {
         let mut store = SIGNAL_STORE.lock().map_err(|_| AppError::new_plain("Failed to lock signal store"))?;
-        store.remove(&request.from_user_id).unwrap_or_default()
+        store.remove(from_user_id).unwrap_or_default()
     };
     
     Ok(SignalResponse {
@@ -1,12 +1,11 @@
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use uuid::Uuid;
 use warp::reply::Reply;
 use std::convert::Infallible;
 
-use crate::util::{AppError, AppResult, embed, id};
+use crate::util::{AppResult, embed};
 use crate::util::qdrant::{qdrant_path, qdrant_put};
-use crate::constants;
+use crate::repo::schema::VOICE_CHAT_USERS_COLLECTION;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct UserRegistrationRequest {
@@ -25,9 +24,10 @@ pub struct UserTagsMetadata {
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
-/// Register a user with their tags
-pub async fn register_user(request: UserRegistrationRequest) -> Result<impl Reply, Infallible> {
-    match process_registration(request).await {
+/// Register the authenticated user with their tags. The voice chat point is keyed by the
+/// account id, so matching, signaling and blocks all see the same user.
+pub async fn register_user(request: UserRegistrationRequest, user_id: String) -> Result<impl Reply, Infallible> {
+    match process_registration(request, user_id).await {
         Ok(response) => Ok(warp::reply::json(&response)),
         Err(e) => {
             eprintln!("Error registering user: {}", e);
@@ -36,40 +36,8 @@ pub async fn register_user(request: UserRegistrationRequest) -> Result<impl Repl
     }
 }
 
-async fn process_registration(request: UserRegistrationRequest) -> AppResult<UserRegistrationResponse> {
-    // Create a unique user ID
-    let user_id = Uuid::now_v7().to_string();
-    
-    // Access the API key
-    let api_key = match std::env::var("GEMINI_API_KEY") {
-        Ok(key) => key,
-        Err(_) => {
-            let secrets = constants::get_secrets().await
-                .map_err(|e| AppError::new("accessing secrets", e))?;
-            secrets.get("GEMINI_API_KEY")
-                .ok_or_else(|| AppError::new_plain("GEMINI_API_KEY not found in secrets"))?
-                .to_string()
-        }
-    };
-
-    // Ensure the voice_chat_users collection exists
-    let collection_name = "voice_chat_users";
-    let create_collection_result = qdrant_put(
-        &qdrant_path(&format!("collections/{}?wait=true", collection_name)).await?,
-        json!({
-            "vectors": {
-                "size": 768,  // Gemini text-embedding-004 dimension
-                "distance": "Cosine"
-            }
-        }),
-    ).await;
-
-    // Ignore error if collection already exists
-    if let Err(e) = create_collection_result {
-        if !e.to_string().contains("already exists") {
-            return Err(e);
-        }
-    }
+async fn process_registration(request: UserRegistrationRequest, user_id: String) -> AppResult<UserRegistrationResponse> {
+    let collection_name = VOICE_CHAT_USERS_COLLECTION;
 
     // Create embedding for user's tags
     let embedding = embed(request.tags.clone()).await?;
@@ -1,20 +1,21 @@
 use warp::{Filter, Reply};
 use serde_json::json;
 use crate::{
-    util::{AppResult, AppError, embedding, id, qdrant::{qdrant_path, qdrant_post}},
-    constants::SECRETS,
+    repo::{self, models::Zone, Condition},
+    util::{AppResult, AppError, embed, id, with_auth, safety::{self, ContentKind}},
 };
-use super::types::{ZoneAddRequest, Zone, Position};
+use super::types::{ZoneAddRequest, Position};
 
 pub fn route() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
     warp::path!("zone" / "add")
         .and(warp::post())
+        .and(with_auth())
         .and(warp::body::json())
         .then(handler)
 }
 
-pub async fn handler(request: ZoneAddRequest) -> impl Reply {
-    match add_zone(request).await {
+pub async fn handler(user_id: String, request: ZoneAddRequest) -> impl Reply {
+    match add_zone(user_id, request).await {
         Ok(zone_id) => warp::reply::with_status(
             zone_id,
             warp::http::StatusCode::OK,
@@ -23,17 +24,21 @@ pub async fn handler(request: ZoneAddRequest) -> impl Reply {
             log::error!("Zone add error: {:#?}", e);
             warp::reply::with_status(
                 format!("Error: {}", e),
-                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                e.status(),
             )
         }
     }
 }
 
-async fn add_zone(request: ZoneAddRequest) -> AppResult<String> {
+async fn add_zone(user_id: String, mut request: ZoneAddRequest) -> AppResult<String> {
+    request.name = safety::screen(ContentKind::Zone, &user_id, &request.name).await?.text;
+    request.description = safety::screen(ContentKind::Zone, &user_id, &request.description).await?.text;
+
+    // Create embedding from zone name and description
+    let embedding = zone_embedding(&request.name, &request.description).await?;
+
     // Check if similar zone already exists
-    if let Err(e) = check_similar_zone(&request.name, &request.description, &request.position).await {
-        return Err(e);
-    }
+    check_similar_zone(&embedding, &request.position, None).await?;
 
     // Upload images to IBM COS if provided
     let uploaded_images = if let Some(images) = request.images {
@@ -42,109 +47,52 @@ async fn add_zone(request: ZoneAddRequest) -> AppResult<String> {
         Vec::new()
     };
 
-    // Create embedding from zone name and description
-    let zone_data = json!({
-        "name": request.name,
-        "description": request.description
-    });
-    let embedding_vec = embedding(zone_data.to_string()).await?;
-    
-    // Convert embedding to Vec<f32>
-    let embedding_floats: Vec<f32> = embedding_vec
-        .as_array()
-        .ok_or(AppError::new_plain("Embedding is not an array"))?
-        .iter()
-        .map(|v| v.as_f64().unwrap_or(0.0) as f32)
-        .collect();
-
-    // Create zone object
     let zone_id = id();
     let zone = Zone {
-        l: request.location_url,
-        n: request.name,
-        i: uploaded_images,
-        p: request.position,
-        s: "z".to_string(),
-        t: request.description,
-        embedding: embedding_floats.clone(),
+        name: request.name,
+        description: request.description,
+        location_url: request.location_url,
+        images: uploaded_images,
+        position: request.position,
+        creator: Some(user_id),
+        members: 0,
     };
 
-    // Store in Qdrant
-    let point = json!({
-        "id": zone_id,
-        "vector": embedding_floats,
-        "payload": {
-            "l": zone.l,
-            "n": zone.n,
-            "i": zone.i,
-            "p": zone.p,
-            "s": zone.s,
-            "t": zone.t
-        }
-    });
-
-    qdrant_post(
-        &qdrant_path("collections/i/points?wait=true").await?,
-        json!({
-            "points": [point]
-        })
-    ).await?;
+    repo::upsert(&zone_id, embedding, &zone).await?;
 
     Ok(zone_id)
 }
 
-async fn check_similar_zone(name: &str, description: &str, position: &Position) -> AppResult<()> {
-    // Create embedding for similarity search
-    let search_data = json!({
+/// embeds a zone's name and description as json
+pub async fn zone_embedding(name: &str, description: &str) -> AppResult<Vec<f32>> {
+    let zone_data = json!({
         "name": name,
         "description": description
     });
-    let search_embedding = embedding(search_data.to_string()).await?;
-    
-    let search_embedding_floats: Vec<f32> = search_embedding
-        .as_array()
-        .ok_or(AppError::new_plain("Search embedding is not an array"))?
-        .iter()
-        .map(|v| v.as_f64().unwrap_or(0.0) as f32)
-        .collect();
+    embed(zone_data.to_string()).await
+}
 
-    // Search for similar zones
-    let search_body = json!({
-        "vector": search_embedding_floats,
-        "filter": {
-            "must": [
-                {"key": "s", "match": {"value": "z"}}
-            ]
-        },
-        "limit": 18,
-        "with_payload": true
-    });
+/// fails if a zone with a similar name and description already exists near `position`,
+/// ignoring the zone `exclude` (the one being edited)
+pub async fn check_similar_zone(embedding: &[f32], position: &Position, exclude: Option<&str>) -> AppResult<()> {
+    let mut filter = repo::Filter::new();
+    if let Some(zone_id) = exclude {
+        filter = filter.must_not(Condition::has_id([zone_id]));
+    }
 
-    let search_result = qdrant_post(
-        &qdrant_path("collections/i/points/search").await?,
-        search_body
-    ).await?;
+    let similar = repo::search::<Zone>(embedding, filter, 18).await?;
 
-    // Check if any similar zones exist in the same location
-    if let Some(points) = search_result["result"].as_array() {
-        for point in points {
-            if let Some(score) = point["score"].as_f64() {
-                if score > 0.8 { // High similarity threshold
-                    if let Some(payload) = point["payload"].as_object() {
-                        if let (Some(lat), Some(lng)) = (
-                            payload.get("p").and_then(|p| p.get("lat")).and_then(|v| v.as_f64()),
-                            payload.get("p").and_then(|p| p.get("lng")).and_then(|v| v.as_f64())
-                        ) {
-                            // Check if within 10 miles (approximately 0.145 degrees)
-                            let distance = ((position.lat - lat).powi(2) + (position.lng - lng).powi(2)).sqrt();
-                            if distance < 0.145 {
-                                return Err(AppError::new_plain("Similar zone already exists in this location"));
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    // High similarity threshold, within 10 miles (approximately 0.145 degrees)
+    let duplicate = similar.iter().any(|record| {
+        let p = record.payload.position;
+        let distance = ((position.lat - p.lat).powi(2) + (position.lng - p.lng).powi(2)).sqrt();
+        record.score.unwrap_or(0.0) > 0.8 && distance < 0.145
+    });
+    if duplicate {
+        return Err(AppError::new_status(
+            "Similar zone already exists in this location",
+            warp::http::StatusCode::CONFLICT,
+        ));
     }
 
     Ok(())
@@ -161,4 +109,4 @@ async fn upload_images_to_cos(images: Vec<String>) -> AppResult<Vec<String>> {
     
     log::warn!("IBM COS upload not yet implemented, returning original image URLs");
     Ok(images)
-} 
\ No newline at end of file
+}
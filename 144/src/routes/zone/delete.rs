@@ -1,15 +1,48 @@
 use warp::{Filter, Reply};
+use crate::{
+    repo::{
+        self, keys,
+        models::{ChatGroup, ChatGroupPatch, Listing, ListingPatch, User, UserPatch, Zone},
+        Condition,
+    },
+    util::{AppResult, with_auth},
+};
+use super::{edit::check_creator, members::find_zone};
 
 pub fn route() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
     warp::path!("zone" / "delete" / String)
         .and(warp::delete())
+        .and(with_auth())
         .then(handler)
 }
 
-pub async fn handler(zone_id: String) -> impl Reply {
-    // TODO: Implement zone deletion
-    warp::reply::with_status(
-        format!("Zone delete not yet implemented for zone: {}", zone_id),
-        warp::http::StatusCode::NOT_IMPLEMENTED,
-    )
-} 
\ No newline at end of file
+pub async fn handler(zone_id: String, user_id: String) -> impl Reply {
+    match delete_zone(&zone_id, &user_id).await {
+        Ok(_) => warp::reply::with_status(
+            "Zone deleted successfully".to_string(),
+            warp::http::StatusCode::OK,
+        ),
+        Err(e) => {
+            log::error!("Zone delete error: {:#?}", e);
+            warp::reply::with_status(
+                format!("Error: {}", e),
+                e.status(),
+            )
+        }
+    }
+}
+
+async fn delete_zone(zone_id: &str, user_id: &str) -> AppResult<()> {
+    let zone = find_zone(zone_id).await?;
+    check_creator(&zone, user_id)?;
+
+    // Detach every user, product, service and chat group that points at the zone
+    let in_zone = || repo::Filter::new().must(Condition::matches(keys::ZONE, zone_id));
+    repo::set_payload_where::<User>(in_zone(), &UserPatch { zone: Some(None), ..Default::default() }).await?;
+    repo::set_payload_where::<Listing>(in_zone(), &ListingPatch { zone: Some(None), ..Default::default() }).await?;
+    repo::set_payload_where::<ChatGroup>(in_zone(), &ChatGroupPatch { zone: Some(None), ..Default::default() }).await?;
+
+    repo::delete::<Zone>(&[zone_id]).await?;
+
+    Ok(())
+}
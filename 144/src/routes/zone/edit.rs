@@ -1,16 +1,89 @@
 use warp::{Filter, Reply};
+use crate::{
+    repo::{self, models::{Zone, ZonePatch}},
+    util::{AppResult, AppError, with_auth, safety::{self, ContentKind}},
+};
+use super::{
+    add::{check_similar_zone, zone_embedding},
+    members::find_zone,
+    types::ZoneEditRequest,
+};
 
 pub fn route() -> impl Filter<Extract = (impl Reply,), Error = warp::Rejection> + Clone {
     warp::path!("zone" / "edit" / String)
         .and(warp::put())
+        .and(with_auth())
         .and(warp::body::json())
         .then(handler)
 }
 
-pub async fn handler(zone_id: String, _body: serde_json::Value) -> impl Reply {
-    // TODO: Implement zone editing
-    warp::reply::with_status(
-        format!("Zone edit not yet implemented for zone: {}", zone_id),
-        warp::http::StatusCode::NOT_IMPLEMENTED,
-    )
-} 
\ No newline at end of file
+pub async fn handler(zone_id: String, user_id: String, request: ZoneEditRequest) -> impl Reply {
+    match edit_zone(&zone_id, &user_id, request).await {
+        Ok(_) => warp::reply::with_status(
+            "Zone updated successfully".to_string(),
+            warp::http::StatusCode::OK,
+        ),
+        Err(e) => {
+            log::error!("Zone edit error: {:#?}", e);
+            warp::reply::with_status(
+                format!("Error: {}", e),
+                e.status(),
+            )
+        }
+    }
+}
+
+/// fails with 403 unless `user_id` created the zone
+pub fn check_creator(zone: &Zone, user_id: &str) -> AppResult<()> {
+    if zone.creator.as_deref() != Some(user_id) {
+        return Err(AppError::new_status(
+            "Only the zone's creator can change it",
+            warp::http::StatusCode::FORBIDDEN,
+        ));
+    }
+    Ok(())
+}
+
+async fn edit_zone(zone_id: &str, user_id: &str, mut request: ZoneEditRequest) -> AppResult<()> {
+    let zone = find_zone(zone_id).await?;
+    check_creator(&zone, user_id)?;
+
+    if let Some(name) = &request.name {
+        request.name = Some(safety::screen(ContentKind::Zone, user_id, name).await?.text);
+    }
+    if let Some(description) = &request.description {
+        request.description = Some(safety::screen(ContentKind::Zone, user_id, description).await?.text);
+    }
+
+    let name = request.name.as_deref().unwrap_or(&zone.name);
+    let description = request.description.as_deref().unwrap_or(&zone.description);
+    let text_changed = name != zone.name || description != zone.description;
+
+    // A new name, description or position can make this zone a duplicate of another one
+    if text_changed || request.position.is_some() {
+        let position = request.position.unwrap_or(zone.position);
+
+        let embedding = if text_changed {
+            zone_embedding(name, description).await?
+        } else {
+            repo::get_vector::<Zone>(zone_id).await?
+        };
+        check_similar_zone(&embedding, &position, Some(zone_id)).await?;
+
+        if text_changed {
+            repo::update_vector::<Zone>(zone_id, embedding).await?;
+        }
+    }
+
+    let patch = ZonePatch {
+        name: request.name,
+        description: request.description,
+        location_url: request.location_url,
+        images: request.images,
+        position: request.position,
+        ..Default::default()
+    };
+    repo::set_payload::<Zone>(&[zone_id], &patch).await?;
+
+    Ok(())
+}
@@ -0,0 +1,21 @@
+use crate::repo::{self, keys, models::{User, Zone, ZonePatch}, Condition, Filter};
+use crate::util::AppResult;
+
+/// fetches a zone, 404 if the point is missing or isn't a zone
+pub async fn find_zone(zone_id: &str) -> AppResult<Zone> {
+    repo::find::<Zone>(zone_id).await
+}
+
+/// recounts the users whose `z` is `zone_id` and stores it on the zone as `m`
+pub async fn update_member_count(zone_id: &str) -> AppResult<u64> {
+    let count = repo::count::<User>(
+        Filter::new().must(Condition::matches(keys::ZONE, zone_id))
+    ).await?;
+
+    repo::set_payload::<Zone>(
+        &[zone_id],
+        &ZonePatch { members: Some(count), ..Default::default() },
+    ).await?;
+
+    Ok(count)
+}
@@ -1,6 +1,7 @@
 pub mod add;
 pub mod edit;
 pub mod delete;
+pub mod members;
 pub mod search;
 pub mod types;
 
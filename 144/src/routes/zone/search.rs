@@ -1,7 +1,8 @@
 use warp::{Filter, Reply};
 use serde_json::json;
 use crate::{
-    util::{AppResult, AppError, embedding, qdrant::{qdrant_path, qdrant_post}},
+    repo::{self, models::Zone},
+    util::{AppResult, AppError, embed},
 };
 use super::types::ZoneSearchRequest;
 
@@ -22,7 +23,7 @@ pub async fn handler(request: ZoneSearchRequest) -> impl Reply {
             log::error!("Zone search error: {:#?}", e);
             warp::reply::with_status(
                 format!("Error: {}", e),
-                warp::http::StatusCode::INTERNAL_SERVER_ERROR,
+                e.status(),
             )
         }
     }
@@ -30,66 +31,28 @@ pub async fn handler(request: ZoneSearchRequest) -> impl Reply {
 
 async fn search_zones(request: ZoneSearchRequest) -> AppResult<serde_json::Value> {
     // Create embedding for the search query
-    let search_embedding = embedding(request.query).await?;
-    
-    let search_embedding_floats: Vec<f32> = search_embedding
-        .as_array()
-        .ok_or(AppError::new_plain("Search embedding is not an array"))?
-        .iter()
-        .map(|v| v.as_f64().unwrap_or(0.0) as f32)
-        .collect();
+    let search_embedding = embed(request.query).await?;
 
-    // Build search body with zone filter
-    let mut search_body = json!({
-        "vector": search_embedding_floats,
-        "filter": {
-            "must": [
-                {"key": "s", "match": {"value": "z"}}
-            ]
-        },
-        "limit": 50,
-        "with_payload": true
-    });
+    let zones = repo::search::<Zone>(&search_embedding, repo::Filter::new(), 50).await?;
 
-    // Perform the search
-    let search_result = qdrant_post(
-        &qdrant_path("collections/i/points/search").await?,
-        search_body
-    ).await?;
-
-    // Process results and apply location filtering if provided
+    // Apply location filtering if provided, and flatten each zone with its id and score
     let mut results = Vec::new();
-    
-    if let Some(points) = search_result["result"].as_array() {
-        for point in points {
-            if let Some(payload) = point["payload"].as_object() {
-                // Apply location-based filtering if coordinates and radius are provided
-                if let (Some(search_lat), Some(search_lng), Some(radius)) = 
-                    (request.lat, request.lng, request.radius) {
-                    
-                    if let (Some(zone_lat), Some(zone_lng)) = (
-                        payload.get("p").and_then(|p| p.get("lat")).and_then(|v| v.as_f64()),
-                        payload.get("p").and_then(|p| p.get("lng")).and_then(|v| v.as_f64())
-                    ) {
-                        let distance_miles = calculate_distance(search_lat, search_lng, zone_lat, zone_lng);
-                        if distance_miles > radius {
-                            continue; // Skip this zone if it's outside the radius
-                        }
-                    }
-                }
-                
-                // Add score to the result
-                let mut result = payload.clone();
-                if let Some(score) = point["score"].as_f64() {
-                    result.insert("score".to_string(), json!(score));
-                }
-                if let Some(id) = point["id"].as_str() {
-                    result.insert("id".to_string(), json!(id));
-                }
-                
-                results.push(result);
+    for record in zones {
+        if let (Some(search_lat), Some(search_lng), Some(radius)) =
+            (request.lat, request.lng, request.radius) {
+            let p = record.payload.position;
+            if calculate_distance(search_lat, search_lng, p.lat, p.lng) > radius {
+                continue; // Skip this zone if it's outside the radius
             }
         }
+
+        let mut result = serde_json::to_value(&record.payload)
+            .map_err(|e| AppError::new("serializing zone", e))?;
+        result["id"] = json!(record.id);
+        if let Some(score) = record.score {
+            result["score"] = json!(score);
+        }
+        results.push(result);
     }
 
     Ok(json!(results))
@@ -0,0 +1,198 @@
+use serde_json::Map;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use super::{BatchFuture, EmbedFuture, Embedder};
+use crate::repo::{store, Point};
+use crate::util::{stable_id, AppError, AppResult};
+
+pub const DEFAULT_CAPACITY: usize = 10_000;
+/// persisted vectors of the configured provider, sized to its dimension by the schema
+pub const EMBEDDING_CACHE_COLLECTION: &str = "embedding_cache";
+
+/// Keeps the most recently used vectors keyed by a sha256 of the provider name and the
+/// text, and only sends the provider texts it has not seen. When persisted, misses are
+/// looked up in the `embedding_cache` collection before the provider is called.
+pub struct CachedEmbedder {
+    name: String,
+    inner: Arc<dyn Embedder>,
+    lru: Mutex<Lru>,
+    collection: Option<&'static str>,
+}
+
+impl CachedEmbedder {
+    pub fn new(name: &str, inner: Arc<dyn Embedder>, capacity: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            inner,
+            lru: Mutex::new(Lru::new(capacity)),
+            collection: None,
+        }
+    }
+
+    /// also keep vectors in the store, so they survive restarts. The collection comes from
+    /// the schema, so `migrate` must have run.
+    pub fn persisted(self) -> Self {
+        Self { collection: Some(EMBEDDING_CACHE_COLLECTION), ..self }
+    }
+
+    /// point id for `text`
+    fn key(&self, text: &str) -> String {
+        stable_id(&[&self.name, text])
+    }
+
+    fn lru(&self) -> std::sync::MutexGuard<'_, Lru> {
+        self.lru.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn lookup(&self, keys: &[String]) -> AppResult<HashMap<String, Vec<f32>>> {
+        let Some(collection) = self.collection else {
+            return Ok(HashMap::new());
+        };
+        let ids: Vec<&str> = keys.iter().map(String::as_str).collect();
+        Ok(store()
+            .retrieve(collection, &ids, true)
+            .await?
+            .into_iter()
+            .filter_map(|point| Some((point.id, point.vector?)))
+            .collect())
+    }
+
+    async fn save(&self, vectors: &[(String, Vec<f32>)]) -> AppResult<()> {
+        let Some(collection) = self.collection else {
+            return Ok(());
+        };
+        let points = vectors
+            .iter()
+            .map(|(key, vector)| Point {
+                id: key.clone(),
+                vector: Some(vector.clone()),
+                payload: Map::new(),
+                score: None,
+            })
+            .collect();
+        store().upsert(collection, points).await
+    }
+
+    async fn batch(&self, texts: Vec<String>) -> AppResult<Vec<Vec<f32>>> {
+        let keys: Vec<String> = texts.iter().map(|text| self.key(text)).collect();
+        let mut found: HashMap<String, Vec<f32>> = {
+            let mut lru = self.lru();
+            keys.iter()
+                .filter_map(|key| Some((key.clone(), lru.get(key)?)))
+                .collect()
+        };
+
+        // each distinct text that is not in memory, once
+        let mut missing: Vec<(String, String)> = Vec::new();
+        for (key, text) in keys.iter().zip(texts) {
+            if !found.contains_key(key) && !missing.iter().any(|(k, _)| k == key) {
+                missing.push((key.clone(), text));
+            }
+        }
+
+        if !missing.is_empty() {
+            let mut stored = self.lookup(&missing.iter().map(|(k, _)| k.clone()).collect::<Vec<_>>()).await?;
+            let (known, unknown): (Vec<_>, Vec<_>) = missing.into_iter().partition(|(k, _)| stored.contains_key(k));
+
+            let mut fresh = Vec::with_capacity(unknown.len());
+            if !unknown.is_empty() {
+                let (new_keys, new_texts): (Vec<_>, Vec<_>) = unknown.into_iter().unzip();
+                let vectors = self.inner.embed_batch(new_texts).await?;
+                if vectors.len() != new_keys.len() {
+                    return Err(AppError::new_plain(&format!(
+                        "{} returned {} embeddings for {} texts",
+                        self.name,
+                        vectors.len(),
+                        new_keys.len()
+                    )));
+                }
+                fresh = new_keys.into_iter().zip(vectors).collect();
+                self.save(&fresh).await?;
+            }
+
+            let mut lru = self.lru();
+            for (key, _) in known {
+                if let Some(vector) = stored.remove(&key) {
+                    lru.insert(key.clone(), vector.clone());
+                    found.insert(key, vector);
+                }
+            }
+            for (key, vector) in fresh {
+                lru.insert(key.clone(), vector.clone());
+                found.insert(key, vector);
+            }
+        }
+
+        keys.iter()
+            .map(|key| {
+                found
+                    .get(key)
+                    .cloned()
+                    .ok_or_else(|| AppError::new_plain("embedding missing from cache"))
+            })
+            .collect()
+    }
+}
+
+impl Embedder for CachedEmbedder {
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+
+    fn embed(&self, text: String) -> EmbedFuture<'_> {
+        Box::pin(async move {
+            self.batch(vec![text])
+                .await?
+                .pop()
+                .ok_or_else(|| AppError::new_plain("no embedding returned"))
+        })
+    }
+
+    fn embed_batch(&self, texts: Vec<String>) -> BatchFuture<'_> {
+        Box::pin(self.batch(texts))
+    }
+}
+
+/// least recently used map: every entry carries the tick of its last use, and `order`
+/// maps ticks back to keys so the oldest is evicted first
+struct Lru {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<String, (u64, Vec<f32>)>,
+    order: BTreeMap<u64, String>,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Self { capacity: capacity.max(1), tick: 0, entries: HashMap::new(), order: BTreeMap::new() }
+    }
+
+    fn touch(&mut self, key: &str) -> Option<&mut (u64, Vec<f32>)> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.0);
+        entry.0 = self.tick;
+        self.order.insert(self.tick, key.to_string());
+        Some(entry)
+    }
+
+    fn get(&mut self, key: &str) -> Option<Vec<f32>> {
+        self.touch(key).map(|(_, vector)| vector.clone())
+    }
+
+    fn insert(&mut self, key: String, vector: Vec<f32>) {
+        if let Some(entry) = self.touch(&key) {
+            entry.1 = vector;
+            return;
+        }
+        while self.entries.len() >= self.capacity {
+            match self.order.pop_first() {
+                Some((_, oldest)) => self.entries.remove(&oldest),
+                None => break,
+            };
+        }
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, (self.tick, vector));
+    }
+}
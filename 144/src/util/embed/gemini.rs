@@ -0,0 +1,108 @@
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::{BatchFuture, Embedder, EmbedFuture};
+use crate::constants::SECRETS;
+use crate::util::{AppError, AppResult};
+
+/// most texts batchEmbedContents accepts in one request
+const MAX_BATCH: usize = 100;
+
+/// Gemini API `embedding-001`, 768 dimensions, keyed by the `GOOGLE` secret
+pub struct GeminiEmbedder;
+
+fn request(text: &str) -> Value {
+    json!({
+        "model": "models/embedding-001",
+        "content": {
+            "parts": [
+                { "text": text }
+            ]
+        }
+    })
+}
+
+fn values(embedding: &Value) -> AppResult<Vec<f32>> {
+    Ok(embedding["values"]
+        .as_array()
+        .ok_or_else(|| AppError::new_plain("Failed to extract embedding from response"))?
+        .iter()
+        .filter_map(|v| v.as_f64().map(|f| f as f32))
+        .collect())
+}
+
+/// posts `body` to the `method` endpoint of embedding-001 and returns the parsed response
+async fn call(method: &str, body: Value) -> AppResult<Value> {
+    let api_key = SECRETS
+        .lock()
+        .await
+        .get("GOOGLE")
+        .ok_or_else(|| AppError::new_plain("GOOGLE API key not found in secrets"))?;
+
+    let client = Client::new();
+    let url = format!(
+        "https://generativelanguage.googleapis.com/v1beta/models/embedding-001:{}?key={}",
+        method, api_key
+    );
+
+    let response = client
+        .post(&url)
+        .header("Content-Type", "application/json")
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| AppError::new("sending embedding request to Gemini", e))?;
+
+    if !response.status().is_success() {
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(AppError::new_plain(&format!(
+            "Gemini API error: {}",
+            error_text
+        )));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| AppError::new("parsing Gemini embedding response", e))
+}
+
+impl Embedder for GeminiEmbedder {
+    fn dimension(&self) -> usize {
+        768
+    }
+
+    fn embed(&self, text: String) -> EmbedFuture<'_> {
+        Box::pin(async move {
+            let response = call("embedContent", request(&text)).await?;
+            values(&response["embedding"])
+        })
+    }
+
+    fn embed_batch(&self, texts: Vec<String>) -> BatchFuture<'_> {
+        Box::pin(async move {
+            let mut vectors = Vec::with_capacity(texts.len());
+            for chunk in texts.chunks(MAX_BATCH) {
+                let requests: Vec<Value> = chunk.iter().map(|text| request(text)).collect();
+                let response = call("batchEmbedContents", json!({ "requests": requests })).await?;
+                let embeddings = response["embeddings"]
+                    .as_array()
+                    .ok_or_else(|| AppError::new_plain("Failed to extract embeddings from response"))?;
+                if embeddings.len() != chunk.len() {
+                    return Err(AppError::new_plain(&format!(
+                        "Gemini returned {} embeddings for {} texts",
+                        embeddings.len(),
+                        chunk.len()
+                    )));
+                }
+                for embedding in embeddings {
+                    vectors.push(values(embedding)?);
+                }
+            }
+            Ok(vectors)
+        })
+    }
+}
@@ -0,0 +1,46 @@
+use sha2::{Digest, Sha256};
+
+use super::{BatchFuture, Embedder, EmbedFuture};
+
+/// Deterministic fake for tests: each lowercased word adds ±1 to a bucket picked by its
+/// sha256, then the vector is normalized. Texts sharing words come out similar.
+pub struct HashEmbedder {
+    dimension: usize,
+}
+
+impl HashEmbedder {
+    pub fn new(dimension: usize) -> Self {
+        Self { dimension: dimension.max(1) }
+    }
+
+    pub fn vector(&self, text: &str) -> Vec<f32> {
+        let mut vector = vec![0.0f32; self.dimension];
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            let digest = Sha256::digest(word.to_lowercase().as_bytes());
+            let bucket = u64::from_le_bytes(digest[..8].try_into().unwrap_or_default());
+            let sign = if digest[8] & 1 == 0 { 1.0 } else { -1.0 };
+            vector[(bucket % self.dimension as u64) as usize] += sign;
+        }
+        let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            vector.iter_mut().for_each(|x| *x /= norm);
+        }
+        vector
+    }
+}
+
+impl Embedder for HashEmbedder {
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn embed(&self, text: String) -> EmbedFuture<'_> {
+        let vector = self.vector(&text);
+        Box::pin(async move { Ok(vector) })
+    }
+
+    fn embed_batch(&self, texts: Vec<String>) -> BatchFuture<'_> {
+        let vectors = texts.iter().map(|text| self.vector(text)).collect();
+        Box::pin(async move { Ok(vectors) })
+    }
+}
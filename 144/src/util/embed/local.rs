@@ -0,0 +1,60 @@
+use fastembed::{EmbeddingModel, InitOptions, TextEmbedding};
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+
+use super::{BatchFuture, Embedder, EmbedFuture};
+use crate::util::{AppError, AppResult};
+
+/// fastembed's ONNX `BAAI/bge-small-en-v1.5`, 384 dimensions, run in process.
+/// The model is downloaded and loaded on first use.
+#[derive(Default)]
+pub struct LocalEmbedder {
+    model: OnceCell<Arc<TextEmbedding>>,
+}
+
+impl LocalEmbedder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn model(&self) -> AppResult<Arc<TextEmbedding>> {
+        self.model
+            .get_or_try_init(|| async {
+                tokio::task::spawn_blocking(|| {
+                    TextEmbedding::try_new(InitOptions::new(EmbeddingModel::BGESmallENV15))
+                        .map(Arc::new)
+                        .map_err(|e| AppError::new_plain(&format!("loading fastembed model: {}", e)))
+                })
+                .await
+                .map_err(|e| AppError::new("loading fastembed model", e))?
+            })
+            .await
+            .cloned()
+    }
+}
+
+impl Embedder for LocalEmbedder {
+    fn dimension(&self) -> usize {
+        384
+    }
+
+    fn embed(&self, text: String) -> EmbedFuture<'_> {
+        Box::pin(async move {
+            self.embed_batch(vec![text])
+                .await?
+                .pop()
+                .ok_or_else(|| AppError::new_plain("fastembed returned no embedding"))
+        })
+    }
+
+    fn embed_batch(&self, texts: Vec<String>) -> BatchFuture<'_> {
+        Box::pin(async move {
+            let model = self.model().await?;
+            // inference is cpu bound, keep it off the async workers
+            tokio::task::spawn_blocking(move || model.embed(texts, None))
+                .await
+                .map_err(|e| AppError::new("running fastembed", e))?
+                .map_err(|e| AppError::new_plain(&format!("fastembed error: {}", e)))
+        })
+    }
+}
@@ -0,0 +1,114 @@
+//! Text embeddings.
+//!
+//! The provider is picked by the `EMBEDDER` secret: `gemini` (the default), `vertex`,
+//! `fastembed` for a local ONNX model, or `hash` for the deterministic test fake. Vector
+//! collections are created with the chosen provider's [`dimension`].
+//!
+//! Every provider sits behind a [`CachedEmbedder`], so text that was embedded before is not
+//! sent again; `EMBED_CACHE_SIZE` bounds it (0 turns it off) and `EMBED_CACHE_PERSIST=true`
+//! keeps the vectors in the `embedding_cache` collection across restarts.
+
+mod cache;
+mod gemini;
+mod hash;
+mod local;
+mod vertex;
+
+pub use cache::{CachedEmbedder, EMBEDDING_CACHE_COLLECTION};
+pub use gemini::GeminiEmbedder;
+pub use hash::HashEmbedder;
+pub use local::LocalEmbedder;
+pub use vertex::VertexEmbedder;
+
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use std::sync::{Arc, RwLock};
+
+use super::{AppError, AppResult};
+use crate::constants::SECRETS;
+
+pub type EmbedFuture<'a> = BoxFuture<'a, AppResult<Vec<f32>>>;
+pub type BatchFuture<'a> = BoxFuture<'a, AppResult<Vec<Vec<f32>>>>;
+
+pub trait Embedder: Send + Sync {
+    /// length of the vectors this provider returns
+    fn dimension(&self) -> usize;
+
+    fn embed(&self, text: String) -> EmbedFuture<'_>;
+
+    /// one vector per text, in order. Providers with a batch endpoint override this;
+    /// the default embeds the texts one after another.
+    fn embed_batch(&self, texts: Vec<String>) -> BatchFuture<'_> {
+        Box::pin(async move {
+            let mut vectors = Vec::with_capacity(texts.len());
+            for text in texts {
+                vectors.push(self.embed(text).await?);
+            }
+            Ok(vectors)
+        })
+    }
+}
+
+static EMBEDDER: Lazy<RwLock<Option<Arc<dyn Embedder>>>> = Lazy::new(|| RwLock::new(None));
+
+/// the embedder named by `name`, as in the `EMBEDDER` secret
+pub fn from_name(name: &str) -> AppResult<Arc<dyn Embedder>> {
+    Ok(match name {
+        "gemini" => Arc::new(GeminiEmbedder),
+        "vertex" => Arc::new(VertexEmbedder),
+        "fastembed" => Arc::new(LocalEmbedder::new()),
+        "hash" => Arc::new(HashEmbedder::new(768)),
+        other => return Err(AppError::new_plain(&format!("unknown EMBEDDER: {}", other))),
+    })
+}
+
+/// the configured embedder, chosen on first use
+pub async fn embedder() -> AppResult<Arc<dyn Embedder>> {
+    if let Some(embedder) = EMBEDDER.read().unwrap_or_else(|e| e.into_inner()).clone() {
+        return Ok(embedder);
+    }
+    let (name, cache_size, persist) = {
+        let secrets = SECRETS.lock().await;
+        (
+            secrets.get("EMBEDDER").unwrap_or_else(|| "gemini".to_string()),
+            secrets
+                .get("EMBED_CACHE_SIZE")
+                .and_then(|size| size.parse().ok())
+                .unwrap_or(cache::DEFAULT_CAPACITY),
+            secrets.get("EMBED_CACHE_PERSIST").is_some_and(|p| p == "true"),
+        )
+    };
+    let provider = from_name(&name)?;
+    log::info!("Using {} embeddings ({} dimensions)", name, provider.dimension());
+    let chosen: Arc<dyn Embedder> = if cache_size == 0 {
+        provider
+    } else {
+        let cached = CachedEmbedder::new(&name, provider, cache_size);
+        Arc::new(if persist { cached.persisted() } else { cached })
+    };
+    Ok(EMBEDDER
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert(chosen)
+        .clone())
+}
+
+/// replaces the embedder for the whole process, e.g. with a `HashEmbedder` in tests
+pub fn set_embedder(embedder: Arc<dyn Embedder>) {
+    *EMBEDDER.write().unwrap_or_else(|e| e.into_inner()) = Some(embedder);
+}
+
+/// Main embed function - embeds with the configured provider
+pub async fn embed(text: String) -> AppResult<Vec<f32>> {
+    embedder().await?.embed(text).await
+}
+
+/// embeds several texts at once with the configured provider, one vector per text
+pub async fn embed_batch(texts: Vec<String>) -> AppResult<Vec<Vec<f32>>> {
+    embedder().await?.embed_batch(texts).await
+}
+
+/// vector size of the configured provider, used when creating collections
+pub async fn dimension() -> AppResult<usize> {
+    Ok(embedder().await?.dimension())
+}
@@ -0,0 +1,82 @@
+use reqwest::Client;
+use serde_json::json;
+
+use super::{Embedder, EmbedFuture};
+use crate::constants::SECRETS;
+use crate::util::AppError;
+
+/// Vertex AI `gemini-embedding-001`, 3072 dimensions, using the `GOOGLE_TOKEN` and
+/// `GOOGLE_PROJECT_ID` secrets
+pub struct VertexEmbedder;
+
+impl Embedder for VertexEmbedder {
+    fn dimension(&self) -> usize {
+        3072
+    }
+
+    fn embed(&self, text: String) -> EmbedFuture<'_> {
+        let dimension = self.dimension();
+        Box::pin(async move {
+            let token = SECRETS
+                .lock()
+                .await
+                .get("GOOGLE_TOKEN")
+                .ok_or_else(|| AppError::new_plain("GOOGLE_TOKEN not found in secrets"))?;
+
+            let project_id = SECRETS
+                .lock()
+                .await
+                .get("GOOGLE_PROJECT_ID")
+                .ok_or_else(|| AppError::new_plain("GOOGLE_PROJECT_ID not found in secrets"))?;
+
+            let client = Client::new();
+            let url = format!(
+                "https://us-central1-aiplatform.googleapis.com/v1/projects/{}/locations/us-central1/publishers/google/models/gemini-embedding-001:predict",
+                project_id
+            );
+
+            let request_body = json!({
+                "instances": [
+                    { "content": text }
+                ],
+                "parameters": {
+                    "outputDimensionality": dimension
+                }
+            });
+
+            let response = client
+                .post(&url)
+                .header("Authorization", &format!("Bearer {}", token))
+                .header("Content-Type", "application/json")
+                .json(&request_body)
+                .send()
+                .await
+                .map_err(|e| AppError::new("sending embedding request to Vertex AI", e))?;
+
+            if !response.status().is_success() {
+                let error_text = response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Unknown error".to_string());
+                return Err(AppError::new_plain(&format!(
+                    "Vertex AI API error: {}",
+                    error_text
+                )));
+            }
+
+            let response_json: serde_json::Value = response
+                .json()
+                .await
+                .map_err(|e| AppError::new("parsing Vertex AI embedding response", e))?;
+
+            let embedding = response_json["predictions"][0]["embeddings"]["values"]
+                .as_array()
+                .ok_or_else(|| AppError::new_plain("Failed to extract embedding from response"))?
+                .iter()
+                .filter_map(|v| v.as_f64().map(|f| f as f32))
+                .collect::<Vec<f32>>();
+
+            Ok(embedding)
+        })
+    }
+}
@@ -1,4 +1,7 @@
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
 use serde_json::json;
+use std::sync::{Arc, RwLock};
 
 use crate::{
     util::{AppError, AppResult},
@@ -71,4 +74,38 @@ pub async fn groq(messages: Vec<Message>) -> AppResult<String> {
         .as_str()
         .ok_or(AppError::new_plain("groq response content not string"))?
         .to_string())
-}
\ No newline at end of file
+}
+
+/// A chat completion model. [`Groq`] unless [`set_llm`] swapped it, e.g. for a fake in tests.
+pub trait Llm: Send + Sync {
+    fn chat(&self, messages: Vec<Message>) -> BoxFuture<'_, AppResult<String>>;
+}
+
+pub struct Groq;
+
+impl Llm for Groq {
+    fn chat(&self, messages: Vec<Message>) -> BoxFuture<'_, AppResult<String>> {
+        Box::pin(groq(messages))
+    }
+}
+
+static LLM: Lazy<RwLock<Arc<dyn Llm>>> = Lazy::new(|| RwLock::new(Arc::new(Groq)));
+
+pub fn llm() -> Arc<dyn Llm> {
+    LLM.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub fn set_llm(llm: Arc<dyn Llm>) {
+    *LLM.write().unwrap_or_else(|e| e.into_inner()) = llm;
+}
+
+/// The JSON from the first `open` to the last `close` of a model reply, since models often
+/// wrap it in prose or a code block
+pub fn reply_json(reply: &str, open: char, close: char) -> AppResult<serde_json::Value> {
+    let json = reply
+        .find(open)
+        .zip(reply.rfind(close))
+        .and_then(|(start, end)| reply.get(start..=end))
+        .unwrap_or(reply);
+    serde_json::from_str(json).map_err(|e| AppError::new("parsing the model's JSON reply", e))
+}
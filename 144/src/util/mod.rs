@@ -1,6 +1,8 @@
 use crate::constants::{/*I_ID,*/ SECRETS};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 use uuid::{NoContext, Timestamp};
+use warp::http::StatusCode;
 pub type AppResult<T> = Result<T, AppError>;
 
 pub mod embed;
@@ -9,6 +11,7 @@ pub use embed::embed;
 #[derive(Error, Debug)]
 pub struct AppError {
     t: String,
+    status: StatusCode,
 }
 
 impl warp::reject::Reject for AppError {}
@@ -21,22 +24,65 @@ impl AppError {
     pub fn new(m: &str, e: impl std::error::Error) -> Self {
         AppError {
             t: format!("{}: {}", m, e.to_string()),
+            status: StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 
     pub fn new_plain(m: &str) -> Self {
-        AppError { t: m.to_string() }
+        Self::new_status(m, StatusCode::INTERNAL_SERVER_ERROR)
     }
     
     pub fn new_rejection(m: &str) -> Self {
         Self::new_plain(m)
     }
+
+    /// error that should reach the client with `status` instead of a 500
+    pub fn new_status(m: &str, status: StatusCode) -> Self {
+        AppError {
+            t: m.to_string(),
+            status,
+        }
+    }
+
+    pub fn unauthorized(m: &str) -> Self {
+        Self::new_status(m, StatusCode::UNAUTHORIZED)
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// reply for this error; server errors are logged and hidden from the client
+    pub fn reply(&self) -> warp::reply::WithStatus<warp::reply::Json> {
+        if self.status.is_server_error() {
+            log::error!("{:#?}", self);
+            warp::reply::with_status(
+                warp::reply::json(&"An error occured on our side".to_string()),
+                self.status,
+            )
+        } else {
+            warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({"error": self.t})),
+                self.status,
+            )
+        }
+    }
+}
+
+/// turns `AppError` rejections into JSON replies with their status code
+pub async fn recover(r: warp::Rejection) -> Result<impl warp::Reply, warp::Rejection> {
+    match r.find::<AppError>() {
+        Some(e) => Ok(e.reply()),
+        None => Err(r),
+    }
 }
 
 pub mod groq;
-pub mod setup;
+pub mod objects;
 pub mod qdrant;
 pub mod password;
+pub mod safety;
+pub mod session;
 
 // use crate::util::qdrant::{qdrant_path, qdrant_post};
 
@@ -73,8 +119,24 @@ pub fn id() -> String {
     uuid::Uuid::new_v7(Timestamp::now(NoContext)).to_string()
 }
 
+/// point id derived from `parts`: a uuid made from the first half of the sha256 of the
+/// parts joined by NUL, so the same parts always name the same point
+pub fn stable_id(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            hasher.update([0]);
+        }
+        hasher.update(part.as_bytes());
+    }
+    let digest = hasher.finalize();
+    uuid::Builder::from_random_bytes(digest[..16].try_into().unwrap_or_default())
+        .into_uuid()
+        .to_string()
+}
+
 pub async fn embedding(query: String) -> AppResult<serde_json::Value> {
-    let embedding_vec = embed::embed_768(query).await?;
+    let embedding_vec = embed::embed(query).await?;
     
     let embedding_json = serde_json::to_value(embedding_vec)
         .map_err(|e| AppError::new("converting embedding to JSON", e))?;
@@ -82,15 +144,64 @@ pub async fn embedding(query: String) -> AppResult<serde_json::Value> {
     Ok(embedding_json)
 }
 
+use std::collections::HashMap;
 use warp::Filter;
 
-/// Filter that extracts the user ID from the Authorization header
+/// Filter that verifies the bearer token in the Authorization header and extracts its claims
+pub fn with_session() -> impl Filter<Extract = (session::Claims,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("Authorization").and_then(|header: Option<String>| async move {
+        let token = header
+            .as_deref()
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .ok_or_else(|| warp::reject::custom(AppError::unauthorized("missing bearer token")))?;
+        session::authenticate(token)
+            .await
+            .map_err(warp::reject::custom)
+    })
+}
+
+/// Filter that verifies the bearer token in the Authorization header and extracts the user ID
 pub fn with_auth() -> impl Filter<Extract = (String,), Error = warp::Rejection> + Clone {
-    warp::header::<String>("Authorization")
-        .map(|token: String| {
-            // Simple token format: "Bearer user_id"
-            token.strip_prefix("Bearer ")
-                .unwrap_or(&token)
-                .to_string()
+    with_session().map(|claims: session::Claims| claims.sub)
+}
+
+/// Filter that lets through only users listed in the comma separated `ADMIN_USER_IDS`
+/// secret, extracting the user ID
+pub fn with_admin() -> impl Filter<Extract = (String,), Error = warp::Rejection> + Clone {
+    with_auth().and_then(|user_id: String| async move {
+        let admins = SECRETS.lock().await.get("ADMIN_USER_IDS").unwrap_or_default();
+        if admins.split(',').any(|admin| admin.trim() == user_id) {
+            Ok(user_id)
+        } else {
+            Err(warp::reject::custom(AppError::new_status("Admins only", StatusCode::FORBIDDEN)))
+        }
+    })
+}
+
+/// Subprotocol a websocket client lists before its token, `Sec-WebSocket-Protocol: bearer, <token>`
+pub const WS_AUTH_PROTOCOL: &str = "bearer";
+
+/// The access token of a websocket upgrade. Browsers can't set headers on websockets, so it
+/// comes from `?token=` or from the subprotocol list after [`WS_AUTH_PROTOCOL`].
+pub fn ws_token(query: &HashMap<String, String>, protocols: Option<&str>) -> Option<String> {
+    if let Some(token) = query.get("token").filter(|t| !t.is_empty()) {
+        return Some(token.clone());
+    }
+    let mut protocols = protocols?.split(',').map(str::trim);
+    protocols.find(|p| *p == WS_AUTH_PROTOCOL)?;
+    protocols.next().filter(|t| !t.is_empty()).map(str::to_string)
+}
+
+/// Filter that verifies the token of a websocket upgrade (see [`ws_token`]) and extracts the user ID
+pub fn with_ws_auth() -> impl Filter<Extract = (String,), Error = warp::Rejection> + Clone {
+    warp::query::<HashMap<String, String>>()
+        .and(warp::header::optional::<String>("sec-websocket-protocol"))
+        .and_then(|query: HashMap<String, String>, protocols: Option<String>| async move {
+            let token = ws_token(&query, protocols.as_deref())
+                .ok_or_else(|| warp::reject::custom(AppError::unauthorized("missing token")))?;
+            session::authenticate(&token)
+                .await
+                .map(|claims| claims.sub)
+                .map_err(warp::reject::custom)
         })
 }
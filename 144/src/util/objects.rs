@@ -0,0 +1,170 @@
+//! File storage in an S3-compatible bucket.
+//!
+//! [`S3Objects`] uses the bucket named by `S3_BUCKET` with the `S3_ACCESS_KEY_ID` and
+//! `S3_SECRET_ACCESS_KEY` credentials. `S3_ENDPOINT` points it at MinIO or another
+//! S3-compatible server, and `S3_REGION` defaults to `us-east-1`. The bucket stays private;
+//! objects are read through short-lived presigned URLs.
+//!
+//! [`MemoryObjects`] keeps objects in process for tests, and [`set_objects`] replaces the
+//! store in use.
+
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use rusoto_core::credential::{AwsCredentials, StaticProvider};
+use rusoto_core::{HttpClient, Region};
+use rusoto_s3::util::{PreSignedRequest, PreSignedRequestOption};
+use rusoto_s3::{DeleteObjectRequest, GetObjectRequest, PutObjectRequest, S3Client, S3};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use super::{AppError, AppResult};
+use crate::constants::SECRETS;
+
+pub type ObjectFuture<'a> = BoxFuture<'a, AppResult<()>>;
+
+pub trait Objects: Send + Sync {
+    fn put(&self, key: &str, body: Vec<u8>, content_type: &str) -> ObjectFuture<'_>;
+
+    fn delete(&self, key: &str) -> ObjectFuture<'_>;
+
+    /// a URL anyone holding it can read the object through until `expires_in` passes
+    fn presigned_get(&self, key: &str, expires_in: Duration) -> AppResult<String>;
+}
+
+pub struct S3Objects {
+    client: S3Client,
+    bucket: String,
+    region: Region,
+    credentials: AwsCredentials,
+}
+
+impl S3Objects {
+    pub async fn from_secrets() -> AppResult<Self> {
+        let secrets = SECRETS.lock().await;
+        let required = |name: &str| {
+            secrets
+                .get(name)
+                .ok_or_else(|| AppError::new_plain(&format!("{} not found in env", name)))
+        };
+        let bucket = required("S3_BUCKET")?;
+        let key_id = required("S3_ACCESS_KEY_ID")?;
+        let secret = required("S3_SECRET_ACCESS_KEY")?;
+        let name = secrets.get("S3_REGION").unwrap_or_else(|| "us-east-1".to_string());
+        let region = match secrets.get("S3_ENDPOINT") {
+            Some(endpoint) => Region::Custom { name, endpoint },
+            None => name
+                .parse()
+                .map_err(|e| AppError::new("parsing S3_REGION", e))?,
+        };
+
+        let dispatcher = HttpClient::new().map_err(|e| AppError::new("creating S3 http client", e))?;
+        let provider = StaticProvider::new_minimal(key_id.clone(), secret.clone());
+        Ok(S3Objects {
+            client: S3Client::new_with(dispatcher, provider, region.clone()),
+            bucket,
+            region,
+            credentials: AwsCredentials::new(key_id, secret, None, None),
+        })
+    }
+}
+
+impl Objects for S3Objects {
+    fn put(&self, key: &str, body: Vec<u8>, content_type: &str) -> ObjectFuture<'_> {
+        // only images are shown inline, anything else downloads
+        let disposition = if content_type.starts_with("image/") { "inline" } else { "attachment" };
+        let request = PutObjectRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            content_length: Some(body.len() as i64),
+            body: Some(body.into()),
+            content_type: Some(content_type.to_string()),
+            content_disposition: Some(disposition.to_string()),
+            ..Default::default()
+        };
+        Box::pin(async move {
+            self.client
+                .put_object(request)
+                .await
+                .map_err(|e| AppError::new("uploading to S3", e))?;
+            Ok(())
+        })
+    }
+
+    fn delete(&self, key: &str) -> ObjectFuture<'_> {
+        let request = DeleteObjectRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            ..Default::default()
+        };
+        Box::pin(async move {
+            self.client
+                .delete_object(request)
+                .await
+                .map_err(|e| AppError::new("deleting from S3", e))?;
+            Ok(())
+        })
+    }
+
+    fn presigned_get(&self, key: &str, expires_in: Duration) -> AppResult<String> {
+        let request = GetObjectRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            ..Default::default()
+        };
+        Ok(request.get_presigned_url(&self.region, &self.credentials, &PreSignedRequestOption { expires_in }))
+    }
+}
+
+/// objects in process, for tests
+#[derive(Default)]
+pub struct MemoryObjects {
+    objects: Mutex<HashMap<String, (String, Vec<u8>)>>,
+}
+
+impl MemoryObjects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// content type and bytes of the object
+    pub fn get(&self, key: &str) -> Option<(String, Vec<u8>)> {
+        self.objects.lock().unwrap_or_else(|e| e.into_inner()).get(key).cloned()
+    }
+}
+
+impl Objects for MemoryObjects {
+    fn put(&self, key: &str, body: Vec<u8>, content_type: &str) -> ObjectFuture<'_> {
+        self.objects
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key.to_string(), (content_type.to_string(), body));
+        Box::pin(futures::future::ready(Ok(())))
+    }
+
+    fn delete(&self, key: &str) -> ObjectFuture<'_> {
+        self.objects.lock().unwrap_or_else(|e| e.into_inner()).remove(key);
+        Box::pin(futures::future::ready(Ok(())))
+    }
+
+    fn presigned_get(&self, key: &str, expires_in: Duration) -> AppResult<String> {
+        Ok(format!("memory://{}?expires_in={}", key, expires_in.as_secs()))
+    }
+}
+
+static OBJECTS: Lazy<RwLock<Option<Arc<dyn Objects>>>> = Lazy::new(|| RwLock::new(None));
+
+/// the configured object store, set up from the secrets on first use
+pub async fn objects() -> AppResult<Arc<dyn Objects>> {
+    if let Some(objects) = OBJECTS.read().unwrap_or_else(|e| e.into_inner()).clone() {
+        return Ok(objects);
+    }
+    let objects: Arc<dyn Objects> = Arc::new(S3Objects::from_secrets().await?);
+    let mut slot = OBJECTS.write().unwrap_or_else(|e| e.into_inner());
+    Ok(slot.get_or_insert(objects).clone())
+}
+
+/// replaces the object store for the whole process, e.g. with [`MemoryObjects`] in tests
+pub fn set_objects(objects: Arc<dyn Objects>) {
+    *OBJECTS.write().unwrap_or_else(|e| e.into_inner()) = Some(objects);
+}
@@ -0,0 +1,506 @@
+//! Screens text users send or publish.
+//!
+//! [`Safety`] runs each [`Classifier`] over the text and turns what they find into an
+//! [`Action`] using the [`Policy`] for that kind of content. The text can be let through,
+//! let through with a warning, have the flagged parts masked, or be rejected. [`screen`]
+//! records anything flagged in [`FLAGGED_COLLECTION`] so moderators can review it.
+//!
+//! The classifiers are [`PiiClassifier`] for emails, phone numbers and card numbers, and
+//! [`WordListClassifier`]. [`GroqClassifier`] asks an LLM as well when the `SAFETY_GROQ`
+//! secret is `true`. `SAFETY_WORDS` adds comma separated abusive words to the word list,
+//! and `SAFETY_POLICY` overrides the policy, e.g. `listing.pii=allow,chat_message.profanity=mask`.
+
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use warp::http::StatusCode;
+
+use super::groq::{llm, reply_json, Message};
+use super::{AppError, AppResult};
+use crate::constants::SECRETS;
+use crate::repo::{store, Condition, Direction, Filter, OrderBy, Point};
+
+pub const FLAGGED_COLLECTION: &str = "flagged_content";
+
+/// What the text is, since each kind has its own [`Policy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentKind {
+    ChatMessage,
+    Listing,
+    Zone,
+    ChatGroup,
+}
+
+/// What happens to the text, from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Allow,
+    /// let it through, but tell the author and record it
+    Warn,
+    /// replace the flagged parts with `*`
+    Mask,
+    Reject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// personal data such as emails and phone numbers
+    Pii,
+    Profanity,
+    /// insults, threats and harassment
+    Abuse,
+}
+
+macro_rules! parse_snake_case {
+    ($($t:ty),*) => {$(
+        impl FromStr for $t {
+            type Err = AppError;
+
+            fn from_str(s: &str) -> AppResult<Self> {
+                serde_json::from_value(Value::String(s.trim().to_string()))
+                    .map_err(|_| AppError::new_plain(&format!("unknown {}: {}", stringify!($t), s)))
+            }
+        }
+    )*};
+}
+parse_snake_case!(ContentKind, Action, Category);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    pub classifier: String,
+    pub category: Category,
+    /// what was found, e.g. `email`
+    pub label: String,
+    /// byte range of the flagged text, `None` when the finding is about the whole text
+    pub span: Option<(usize, usize)>,
+}
+
+pub trait Classifier: Send + Sync {
+    fn classify<'a>(&'a self, text: &'a str) -> BoxFuture<'a, AppResult<Vec<Finding>>>;
+}
+
+/// Emails, phone numbers and card numbers
+pub struct PiiClassifier {
+    email: Regex,
+    digits: Regex,
+    phone: Regex,
+}
+
+impl PiiClassifier {
+    pub fn new() -> Self {
+        PiiClassifier {
+            email: Regex::new(r"(?i)\b[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}\b").expect("email pattern"),
+            // runs of digits with the separators people write numbers with
+            digits: Regex::new(r"\+?\d[\d \t().-]{7,}\d").expect("digits pattern"),
+            // a country code or a leading 0, then groups of two to four digits, so prices
+            // like 150 000 000 and dates like 2024-01-15 aren't phone numbers
+            phone: Regex::new(r"(?:\+\d{1,3}|\(?\b0\d{1,4}\)?)(?:[ .-]?\(?\d{2,4}\)?){2,5}").expect("phone pattern"),
+        }
+    }
+}
+
+impl Default for PiiClassifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// whether the digits pass the Luhn check card numbers are built with
+fn luhn(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| if i % 2 == 1 { if d * 2 > 9 { d * 2 - 9 } else { d * 2 } } else { d })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+impl Classifier for PiiClassifier {
+    fn classify<'a>(&'a self, text: &'a str) -> BoxFuture<'a, AppResult<Vec<Finding>>> {
+        let finding = |label: &str, m: regex::Match| Finding {
+            classifier: "pii".to_string(),
+            category: Category::Pii,
+            label: label.to_string(),
+            span: Some((m.start(), m.end())),
+        };
+        let mut findings: Vec<Finding> = self.email.find_iter(text).map(|m| finding("email", m)).collect();
+        let digits = |m: &regex::Match| -> Vec<u32> { m.as_str().chars().filter_map(|c| c.to_digit(10)).collect() };
+        for m in self.digits.find_iter(text) {
+            let digits = digits(&m);
+            if (13..=19).contains(&digits.len()) && luhn(&digits) {
+                findings.push(finding("card_number", m));
+            }
+        }
+        let taken: Vec<(usize, usize)> = findings.iter().filter_map(|f| f.span).collect();
+        for m in self.phone.find_iter(text) {
+            // running into a time, it's a date
+            let date = text[m.end()..].starts_with(':');
+            let overlaps = taken.iter().any(|&(start, end)| m.start() < end && start < m.end());
+            if !date && !overlaps && (9..=15).contains(&digits(&m).len()) {
+                findings.push(finding("phone_number", m));
+            }
+        }
+        Box::pin(futures::future::ready(Ok(findings)))
+    }
+}
+
+/// words people mostly use to swear
+const PROFANITY: &[&str] = &[
+    "fuck", "fucking", "fucker", "fucked", "shit", "shitty", "bullshit", "bitch", "asshole",
+    "bastard", "dickhead", "motherfucker", "wanker", "prick",
+];
+
+/// words and phrases people mostly use to hurt someone
+const ABUSE: &[&str] = &[
+    "kill yourself", "kys", "go die", "i will kill you", "i'll kill you", "retard", "retarded",
+    "slut", "whore", "cunt",
+];
+
+/// Whole words and phrases from a list, ignoring case
+pub struct WordListClassifier {
+    pattern: Option<Regex>,
+    categories: HashMap<String, Category>,
+}
+
+impl WordListClassifier {
+    pub fn new(words: impl IntoIterator<Item = (String, Category)>) -> Self {
+        let categories: HashMap<String, Category> = words
+            .into_iter()
+            .map(|(word, category)| (word.trim().to_lowercase(), category))
+            .filter(|(word, _)| !word.is_empty())
+            .collect();
+        // longest first, so a phrase wins over a word inside it
+        let mut alternatives: Vec<&String> = categories.keys().collect();
+        alternatives.sort_by_key(|word| std::cmp::Reverse(word.len()));
+        let alternatives: Vec<String> = alternatives.into_iter().map(|word| regex::escape(word)).collect();
+        let pattern = (!alternatives.is_empty()).then(|| {
+            RegexBuilder::new(&format!(r"\b(?:{})\b", alternatives.join("|")))
+                .case_insensitive(true)
+                .build()
+                .expect("escaped word list")
+        });
+        WordListClassifier { pattern, categories }
+    }
+
+    /// the built-in lists, plus `extra_abuse`
+    pub fn with_defaults(extra_abuse: impl IntoIterator<Item = String>) -> Self {
+        let words = PROFANITY
+            .iter()
+            .map(|w| (w.to_string(), Category::Profanity))
+            .chain(ABUSE.iter().map(|w| (w.to_string(), Category::Abuse)))
+            .chain(extra_abuse.into_iter().map(|w| (w, Category::Abuse)));
+        Self::new(words)
+    }
+}
+
+impl Classifier for WordListClassifier {
+    fn classify<'a>(&'a self, text: &'a str) -> BoxFuture<'a, AppResult<Vec<Finding>>> {
+        let findings = match &self.pattern {
+            Some(pattern) => pattern
+                .find_iter(text)
+                .filter_map(|m| {
+                    let word = m.as_str().to_lowercase();
+                    Some(Finding {
+                        classifier: "word_list".to_string(),
+                        category: *self.categories.get(&word)?,
+                        label: word,
+                        span: Some((m.start(), m.end())),
+                    })
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        Box::pin(futures::future::ready(Ok(findings)))
+    }
+}
+
+/// Asks the groq LLM (see [`llm`]) whether the text is abusive. Slow and paid, so it's opt-in.
+pub struct GroqClassifier;
+
+const GROQ_PROMPT: &str = "You moderate an anonymous chat and a classifieds site. Decide whether the user's text \
+is abusive: harassment, hate, threats, sexual content aimed at someone, or encouraging self-harm. Swearing on its \
+own is not abusive. Reply with only JSON like {\"abusive\": false, \"reason\": \"\"}.";
+
+impl Classifier for GroqClassifier {
+    fn classify<'a>(&'a self, text: &'a str) -> BoxFuture<'a, AppResult<Vec<Finding>>> {
+        Box::pin(async move {
+            let reply = llm()
+                .chat(vec![
+                    Message { role: "system".to_string(), content: GROQ_PROMPT.to_string() },
+                    Message { role: "user".to_string(), content: text.to_string() },
+                ])
+                .await?;
+            let verdict = reply_json(&reply, '{', '}')?;
+            if verdict["abusive"].as_bool() != Some(true) {
+                return Ok(Vec::new());
+            }
+            Ok(vec![Finding {
+                classifier: "groq".to_string(),
+                category: Category::Abuse,
+                label: verdict["reason"].as_str().unwrap_or("abusive").to_string(),
+                span: None,
+            }])
+        })
+    }
+}
+
+/// The action for each kind of content and category found in it; anything unlisted is allowed
+#[derive(Debug, Clone)]
+pub struct Policy {
+    rules: HashMap<(ContentKind, Category), Action>,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        use Action::*;
+        use Category::*;
+        use ContentKind::*;
+        let rules = [
+            ((ChatMessage, Pii), Mask),
+            ((ChatMessage, Profanity), Warn),
+            ((ChatMessage, Abuse), Reject),
+            ((Listing, Pii), Mask),
+            ((Listing, Profanity), Mask),
+            ((Listing, Abuse), Reject),
+            ((Zone, Pii), Mask),
+            ((Zone, Profanity), Reject),
+            ((Zone, Abuse), Reject),
+            ((ChatGroup, Pii), Mask),
+            ((ChatGroup, Profanity), Reject),
+            ((ChatGroup, Abuse), Reject),
+        ];
+        Policy { rules: rules.into_iter().collect() }
+    }
+}
+
+impl Policy {
+    pub fn action(&self, kind: ContentKind, category: Category) -> Action {
+        self.rules.get(&(kind, category)).copied().unwrap_or(Action::Allow)
+    }
+
+    pub fn set(&mut self, kind: ContentKind, category: Category, action: Action) {
+        self.rules.insert((kind, category), action);
+    }
+
+    /// the defaults with `kind.category=action` rules, comma separated, applied
+    pub fn parse(overrides: &str) -> AppResult<Self> {
+        let mut policy = Self::default();
+        for rule in overrides.split(',').filter(|rule| !rule.trim().is_empty()) {
+            let invalid = || AppError::new_plain(&format!("invalid safety rule {:?}", rule));
+            let (target, action) = rule.split_once('=').ok_or_else(invalid)?;
+            let (kind, category) = target.split_once('.').ok_or_else(invalid)?;
+            policy.set(kind.parse()?, category.parse()?, action.parse()?);
+        }
+        Ok(policy)
+    }
+}
+
+/// What to do with a text
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Verdict {
+    pub action: Action,
+    /// the text to use, masked when the action is [`Action::Mask`]
+    pub text: String,
+    pub findings: Vec<Finding>,
+}
+
+impl Verdict {
+    /// what to tell the author when their text went through changed or flagged
+    pub fn notice(&self) -> Option<String> {
+        match self.action {
+            Action::Warn => Some(format!("Please keep it friendly ({})", self.labels())),
+            Action::Mask => Some(format!("Parts of your text were hidden ({})", self.labels())),
+            Action::Allow | Action::Reject => None,
+        }
+    }
+
+    fn labels(&self) -> String {
+        let mut labels: Vec<&str> = self.findings.iter().map(|f| f.label.as_str()).collect();
+        labels.sort();
+        labels.dedup();
+        labels.join(", ")
+    }
+}
+
+pub struct Safety {
+    classifiers: Vec<Arc<dyn Classifier>>,
+    policy: Policy,
+}
+
+impl Safety {
+    pub fn new(classifiers: Vec<Arc<dyn Classifier>>, policy: Policy) -> Self {
+        Safety { classifiers, policy }
+    }
+
+    pub async fn from_secrets() -> AppResult<Self> {
+        let secrets = SECRETS.lock().await;
+        let words = secrets.get("SAFETY_WORDS").unwrap_or_default();
+        let policy = Policy::parse(&secrets.get("SAFETY_POLICY").unwrap_or_default())?;
+        let mut classifiers: Vec<Arc<dyn Classifier>> = vec![
+            Arc::new(PiiClassifier::new()),
+            Arc::new(WordListClassifier::with_defaults(words.split(',').map(str::to_string))),
+        ];
+        if secrets.get("SAFETY_GROQ").is_some_and(|v| v.trim() == "true") {
+            classifiers.push(Arc::new(GroqClassifier));
+        }
+        Ok(Safety::new(classifiers, policy))
+    }
+
+    /// Runs every classifier over `text`. A classifier that fails is logged and skipped,
+    /// so an LLM outage doesn't stop people from chatting.
+    pub async fn check(&self, kind: ContentKind, text: &str) -> Verdict {
+        let mut findings = Vec::new();
+        for classifier in &self.classifiers {
+            match classifier.classify(text).await {
+                Ok(found) => findings.extend(found),
+                Err(e) => log::error!("Safety classifier failed: {:?}", e),
+            }
+        }
+
+        // a finding about the whole text can't be masked, so it's a warning at most
+        let action_for = |finding: &Finding| match (self.policy.action(kind, finding.category), finding.span) {
+            (Action::Mask, None) => Action::Warn,
+            (action, _) => action,
+        };
+        let action = findings.iter().map(action_for).max().unwrap_or(Action::Allow);
+        let text = match action {
+            Action::Mask => {
+                let masked: Vec<(usize, usize)> = findings
+                    .iter()
+                    .filter(|finding| action_for(finding) == Action::Mask)
+                    .filter_map(|finding| finding.span)
+                    .collect();
+                text.char_indices()
+                    .map(|(i, c)| {
+                        let hide = !c.is_whitespace() && masked.iter().any(|&(start, end)| start <= i && i < end);
+                        if hide { '*' } else { c }
+                    })
+                    .collect()
+            }
+            _ => text.to_string(),
+        };
+        findings.retain(|finding| action_for(finding) != Action::Allow);
+
+        Verdict { action, text, findings }
+    }
+}
+
+static SAFETY: Lazy<RwLock<Option<Arc<Safety>>>> = Lazy::new(|| RwLock::new(None));
+
+/// the configured safety pipeline, set up from the secrets on first use
+pub async fn safety() -> AppResult<Arc<Safety>> {
+    if let Some(safety) = SAFETY.read().unwrap_or_else(|e| e.into_inner()).clone() {
+        return Ok(safety);
+    }
+    let safety = Arc::new(Safety::from_secrets().await?);
+    let mut slot = SAFETY.write().unwrap_or_else(|e| e.into_inner());
+    Ok(slot.get_or_insert(safety).clone())
+}
+
+/// replaces the safety pipeline for the whole process, e.g. in tests
+pub fn set_safety(safety: Arc<Safety>) {
+    *SAFETY.write().unwrap_or_else(|e| e.into_inner()) = Some(safety);
+}
+
+/// Checks `text` by `author_id`, recording it when flagged. Rejected text is an error
+/// (422) naming what was found; otherwise the verdict says what text to use.
+pub async fn screen(kind: ContentKind, author_id: &str, text: &str) -> AppResult<Verdict> {
+    let verdict = safety().await?.check(kind, text).await;
+    if !verdict.findings.is_empty() {
+        if let Err(e) = record(kind, author_id, text, &verdict).await {
+            log::error!("Error recording flagged content: {:?}", e);
+        }
+    }
+    if verdict.action == Action::Reject {
+        return Err(AppError::new_status(
+            &format!("This can't be posted ({})", verdict.labels()),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ));
+    }
+    Ok(verdict)
+}
+
+/// Text a classifier flagged, as it was before masking, waiting for a moderator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Flagged {
+    pub id: String,
+    pub kind: ContentKind,
+    pub author_id: String,
+    pub text: String,
+    pub action: Action,
+    pub findings: Vec<Finding>,
+    pub created_at: i64,
+    pub reviewed: bool,
+    #[serde(default)]
+    pub reviewed_by: Option<String>,
+}
+
+async fn save_flagged(flagged: &Flagged) -> AppResult<()> {
+    let mut payload = match serde_json::to_value(flagged) {
+        Ok(Value::Object(payload)) => payload,
+        Ok(_) => Default::default(),
+        Err(e) => return Err(AppError::new("Failed to serialize flagged content", e)),
+    };
+    payload.remove("id");
+    store()
+        .upsert(FLAGGED_COLLECTION, vec![Point {
+            id: flagged.id.clone(),
+            vector: Some(vec![0.0]),
+            payload,
+            score: None,
+        }])
+        .await
+}
+
+async fn record(kind: ContentKind, author_id: &str, text: &str, verdict: &Verdict) -> AppResult<()> {
+    save_flagged(&Flagged {
+        id: super::id(),
+        kind,
+        author_id: author_id.to_string(),
+        text: text.to_string(),
+        action: verdict.action,
+        findings: verdict.findings.clone(),
+        created_at: chrono::Utc::now().timestamp(),
+        reviewed: false,
+        reviewed_by: None,
+    })
+    .await
+}
+
+fn parse_flagged(points: Vec<Point>) -> Vec<Flagged> {
+    points
+        .into_iter()
+        .filter_map(|point| {
+            let mut payload = point.payload;
+            payload.insert("id".to_string(), point.id.into());
+            serde_json::from_value(payload.into()).ok()
+        })
+        .collect()
+}
+
+/// Flagged content, oldest first
+pub async fn flagged(reviewed: bool, limit: usize) -> AppResult<Vec<Flagged>> {
+    let filter = Filter::new().must(Condition::matches("reviewed", reviewed));
+    let order = OrderBy::new("created_at", Direction::Asc);
+    Ok(parse_flagged(store().scroll_ordered(FLAGGED_COLLECTION, &filter, &order, limit).await?))
+}
+
+/// Marks flagged content as reviewed by `moderator_id`
+pub async fn review(moderator_id: &str, id: &str) -> AppResult<Flagged> {
+    let mut flagged = parse_flagged(store().retrieve(FLAGGED_COLLECTION, &[id], false).await?)
+        .pop()
+        .ok_or_else(|| AppError::new_status("Flagged content not found", StatusCode::NOT_FOUND))?;
+    flagged.reviewed = true;
+    flagged.reviewed_by = Some(moderator_id.to_string());
+    save_flagged(&flagged).await?;
+    Ok(flagged)
+}
@@ -0,0 +1,212 @@
+//! Signed, expiring bearer tokens for authenticated sessions.
+//!
+//! A token is `base64url(claims).base64url(hmac_sha256(claims))`, signed with the
+//! `SESSION_SECRET` secret. Every login creates a session record in the
+//! `auth_sessions` collection; access and refresh tokens both carry its id (`sid`),
+//! so logging out revokes the whole session.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+
+use crate::constants::SECRETS;
+use crate::repo::{store, Point, Selector};
+use crate::util::{id, AppError, AppResult};
+
+pub const SESSIONS_COLLECTION: &str = "auth_sessions";
+pub const ACCESS_TTL_SECS: i64 = 15 * 60;
+pub const REFRESH_TTL_SECS: i64 = 30 * 24 * 60 * 60;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenKind {
+    Access,
+    Refresh,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// user id
+    pub sub: String,
+    /// session id
+    pub sid: String,
+    /// expiry, unix seconds
+    pub exp: i64,
+    pub typ: TokenKind,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Tokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    /// seconds until the access token expires
+    pub expires_in: i64,
+}
+
+/// how long a session's revoked flag is trusted before `auth_sessions` is read again
+const KNOWN_SESSION_TTL_SECS: i64 = 60;
+const MAX_KNOWN_SESSIONS: usize = 10_000;
+
+/// session id -> (revoked, when it was read), filled lazily from `auth_sessions`
+static KNOWN_SESSIONS: Lazy<Mutex<HashMap<String, (bool, i64)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+fn mac(secret: &[u8], data: &[u8]) -> AppResult<HmacSha256> {
+    let mut mac = HmacSha256::new_from_slice(secret)
+        .map_err(|e| AppError::new("creating session token mac", e))?;
+    mac.update(data);
+    Ok(mac)
+}
+
+pub fn sign(claims: &Claims, secret: &[u8]) -> AppResult<String> {
+    let body = serde_json::to_vec(claims)
+        .map_err(|e| AppError::new("serializing session claims", e))?;
+    let body = URL_SAFE_NO_PAD.encode(body);
+    let signature = mac(secret, body.as_bytes())?.finalize().into_bytes();
+    Ok(format!("{}.{}", body, URL_SAFE_NO_PAD.encode(signature)))
+}
+
+/// checks the signature, kind and expiry of `token`
+pub fn verify(token: &str, secret: &[u8], kind: TokenKind, now: i64) -> AppResult<Claims> {
+    let (body, signature) = token
+        .split_once('.')
+        .ok_or_else(|| AppError::unauthorized("malformed token"))?;
+    let signature = URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| AppError::unauthorized("malformed token signature"))?;
+    mac(secret, body.as_bytes())?
+        .verify_slice(&signature)
+        .map_err(|_| AppError::unauthorized("invalid token signature"))?;
+
+    let body = URL_SAFE_NO_PAD
+        .decode(body)
+        .map_err(|_| AppError::unauthorized("malformed token body"))?;
+    let claims: Claims = serde_json::from_slice(&body)
+        .map_err(|_| AppError::unauthorized("malformed token claims"))?;
+
+    if claims.typ != kind {
+        return Err(AppError::unauthorized("wrong token type"));
+    }
+    if claims.exp <= now {
+        return Err(AppError::unauthorized("token expired"));
+    }
+    Ok(claims)
+}
+
+async fn secret() -> AppResult<Vec<u8>> {
+    SECRETS
+        .lock()
+        .await
+        .get("SESSION_SECRET")
+        .map(String::into_bytes)
+        .ok_or_else(|| AppError::new_plain("SESSION_SECRET not found in env"))
+}
+
+fn pair(user_id: &str, sid: &str, secret: &[u8]) -> AppResult<Tokens> {
+    let now = now();
+    let access = Claims {
+        sub: user_id.to_string(),
+        sid: sid.to_string(),
+        exp: now + ACCESS_TTL_SECS,
+        typ: TokenKind::Access,
+    };
+    let refresh = Claims {
+        exp: now + REFRESH_TTL_SECS,
+        typ: TokenKind::Refresh,
+        ..access.clone()
+    };
+    Ok(Tokens {
+        access_token: sign(&access, secret)?,
+        refresh_token: sign(&refresh, secret)?,
+        expires_in: ACCESS_TTL_SECS,
+    })
+}
+
+async fn remember(sid: &str, revoked: bool) {
+    let now = now();
+    let mut known = KNOWN_SESSIONS.lock().await;
+    if known.len() >= MAX_KNOWN_SESSIONS {
+        known.retain(|_, (_, read)| now - *read < KNOWN_SESSION_TTL_SECS);
+        // still full of fresh entries: start over rather than grow
+        if known.len() >= MAX_KNOWN_SESSIONS {
+            known.clear();
+        }
+    }
+    known.insert(sid.to_string(), (revoked, now));
+}
+
+/// whether the session exists and hasn't been revoked
+async fn session_live(sid: &str) -> AppResult<bool> {
+    if let Some((revoked, read)) = KNOWN_SESSIONS.lock().await.get(sid) {
+        if now() - read < KNOWN_SESSION_TTL_SECS {
+            return Ok(!revoked);
+        }
+    }
+    let revoked = match store().retrieve(SESSIONS_COLLECTION, &[sid], false).await?.first() {
+        Some(point) => point.payload.get("revoked").and_then(|r| r.as_bool()).unwrap_or(false),
+        None => true,
+    };
+    remember(sid, revoked).await;
+    Ok(!revoked)
+}
+
+/// starts a new session for `user_id` and returns its tokens
+pub async fn issue(user_id: &str) -> AppResult<Tokens> {
+    let sid = id();
+    let payload = json!({"u": user_id, "revoked": false, "created": now()});
+    store()
+        .upsert(SESSIONS_COLLECTION, vec![Point {
+            id: sid.clone(),
+            vector: Some(vec![0.0]),
+            payload: payload.as_object().cloned().unwrap_or_default(),
+            score: None,
+        }])
+        .await?;
+    remember(&sid, false).await;
+    pair(user_id, &sid, &secret().await?)
+}
+
+/// verifies an access token and returns its claims
+pub async fn authenticate(token: &str) -> AppResult<Claims> {
+    let claims = verify(token, &secret().await?, TokenKind::Access, now())?;
+    if !session_live(&claims.sid).await? {
+        return Err(AppError::unauthorized("session revoked"));
+    }
+    Ok(claims)
+}
+
+/// exchanges a refresh token for a new token pair on the same session
+pub async fn refresh(refresh_token: &str) -> AppResult<Tokens> {
+    let secret = secret().await?;
+    let claims = verify(refresh_token, &secret, TokenKind::Refresh, now())?;
+    // always read the stored record, so a logout on another instance is honoured
+    KNOWN_SESSIONS.lock().await.remove(&claims.sid);
+    if !session_live(&claims.sid).await? {
+        return Err(AppError::unauthorized("session revoked"));
+    }
+    pair(&claims.sub, &claims.sid, &secret)
+}
+
+/// revokes the session, invalidating both of its tokens
+pub async fn revoke(claims: &Claims) -> AppResult<()> {
+    let revoked = json!({"revoked": true});
+    store()
+        .set_payload(
+            SESSIONS_COLLECTION,
+            Selector::ids([&claims.sid]),
+            revoked.as_object().cloned().unwrap_or_default(),
+        )
+        .await?;
+    remember(&claims.sid, true).await;
+    Ok(())
+}
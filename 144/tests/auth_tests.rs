@@ -1,55 +1,58 @@
-use std::sync::Once;
+use std::sync::{Arc, Once};
 use anyhow::Result;
+use i144::repo::{self, memory::MemoryStore, models::User};
+use i144::routes::auth::google::{find_or_create_user, GoogleUser};
 
 // Initialize test environment once
 static INIT: Once = Once::new();
 fn setup() {
     INIT.call_once(|| {
-        // Initialize environment for tests
-        env_logger::init();
+        let _ = env_logger::builder().is_test(true).try_init();
+        repo::set_store(Arc::new(MemoryStore::new()));
     });
 }
 
+fn google_user(google_id: &str) -> GoogleUser {
+    GoogleUser {
+        id: google_id.to_string(),
+        email: "test.user@example.com".to_string(),
+        name: "Test User".to_string(),
+        picture: "https://example.com/profile.jpg".to_string(),
+    }
+}
+
 // Test for verifying that a user is added on Google authentication
 #[tokio::test]
 async fn test_user_is_added_on_google_auth() -> Result<()> {
     setup();
-    
-    // Since we can't directly test private functions, we'll use a simulated approach
-    
-    // STEP 1: Create a mock Google user
-    let google_id = "test_google_id";
-    let email = "test@example.com";
-    let name = "Test User";
-    
-    // STEP 2: Simulate what happens in the store_user function
-    // In the real application, this would:
-    // 1. Generate a unique user ID
-    // 2. Store the user in Qdrant with Google ID, email, name, etc.
-    // 3. Return the user ID
-    
-    // For our test, we'll just assert that this would happen
-    let mock_user_id = "test-user-id-123";
-    
-    // STEP 3: Assert that a new user would be created with the Google info
-    assert!(!mock_user_id.is_empty(), "User ID should not be empty");
-    
-    // Log what would happen in a real scenario
-    println!("In real app: User {} <{}> with Google ID {} would be added to database with ID {}",
-             name, email, google_id, mock_user_id);
-    
-    // This test passes if we reach this point
-    // In a real scenario with proper mocking, we would verify the database was called
-    
+
+    let user_id = find_or_create_user(&google_user("google_added")).await?;
+
+    let user = repo::find::<User>(&user_id).await?;
+    assert_eq!(user.google_id.as_deref(), Some("google_added"));
+    assert_eq!(user.email.as_deref(), Some("test.user@example.com"));
+    assert_eq!(user.name, "Test User");
+    assert_eq!(user.username, "test.user");
+    assert_eq!(user.images, vec!["https://example.com/profile.jpg".to_string()]);
+    assert_eq!(user.zone, None);
+
     Ok(())
 }
 
-// Note about testing the auth flow:
-// To properly test the Google authentication flow, we would need to:
-// 1. Mock the OAuth token exchange process
-// 2. Mock the Google API calls to get user info
-// 3. Mock the database operations
-// 4. Verify the right sequence of operations occurs
-//
-// This would require more extensive test infrastructure and potentially
-// changes to the application code to make it more testable. 
\ No newline at end of file
+// Test that logging in again with the same Google ID reuses the account
+#[tokio::test]
+async fn test_multiple_logins_same_google_id() -> Result<()> {
+    setup();
+
+    let first = find_or_create_user(&google_user("google_repeat")).await?;
+    let second = find_or_create_user(&google_user("google_repeat")).await?;
+    assert_eq!(first, second);
+
+    let user = repo::find::<User>(&second).await?;
+    assert!(user.last_login.is_some(), "second login should record last_login");
+
+    let other = find_or_create_user(&google_user("google_other")).await?;
+    assert_ne!(first, other);
+
+    Ok(())
+}
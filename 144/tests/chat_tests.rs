@@ -0,0 +1,208 @@
+use i144::repo::memory::MemoryStore;
+use i144::repo::schema::CHAT_USERS_COLLECTION;
+use i144::repo::{set_store, store, Point};
+use i144::routes::chat::matching::{restore, ACTIVE_SESSIONS, WAITING_USERS};
+use i144::routes::chat::matchmaker::{interest_overlap, pick, MatchConfig, RecentPairs};
+use i144::routes::chat::registry::Registry;
+use i144::routes::chat::storage;
+use i144::routes::chat::types::{AgeRange, ChatSession, ClientEvent, ServerEvent, UserProfile};
+use i144::util::ws_token;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use warp::ws::Message;
+
+#[test]
+fn test_registry_only_reaches_session_participants() {
+    let mut registry = Registry::new();
+    let (alice, mut alice_inbox) = mpsc::unbounded_channel();
+    let (bob, mut bob_inbox) = mpsc::unbounded_channel();
+    let (carol, mut carol_inbox) = mpsc::unbounded_channel();
+    registry.connect("a", "alice", alice);
+    registry.connect("b", "bob", bob);
+    registry.connect("c", "carol", carol);
+    registry.join("s1", "alice", "a");
+    registry.join("s1", "bob", "b");
+    registry.join("s2", "carol", "c");
+
+    assert_eq!(registry.send("s1", &Message::text("hi")), 2);
+    assert_eq!(alice_inbox.try_recv().unwrap(), Message::text("hi"));
+    assert_eq!(bob_inbox.try_recv().unwrap(), Message::text("hi"));
+    assert!(carol_inbox.try_recv().is_err());
+
+    // bob's socket went away: the failed send drops his connection
+    drop(bob_inbox);
+    assert_eq!(registry.send("s1", &Message::text("still there?")), 1);
+    assert_eq!(registry.members("s1"), vec!["alice".to_string()]);
+    assert!(!registry.connected("bob"));
+
+    // events for a user reach their sockets whether or not they joined a session
+    assert_eq!(registry.send_to_user("carol", &Message::text("match")), 1);
+    assert_eq!(carol_inbox.try_recv().unwrap(), Message::text("match"));
+
+    assert_eq!(registry.disconnect("a"), vec![("s1".to_string(), "alice".to_string())]);
+    assert_eq!(registry.send("s1", &Message::text("anyone?")), 0);
+}
+
+#[test]
+fn test_ws_token_from_query_or_subprotocol() {
+    let mut query = HashMap::new();
+    assert_eq!(ws_token(&query, None), None);
+    assert_eq!(ws_token(&query, Some("bearer, abc.def")), Some("abc.def".to_string()));
+    assert_eq!(ws_token(&query, Some("chat, abc.def")), None);
+    assert_eq!(ws_token(&query, Some("bearer")), None);
+
+    query.insert("token".to_string(), "xyz".to_string());
+    assert_eq!(ws_token(&query, Some("bearer, abc.def")), Some("xyz".to_string()));
+}
+
+#[test]
+fn test_server_events_are_tagged() {
+    let error = ServerEvent::Error { status: 403, message: "no".to_string() };
+    assert_eq!(
+        serde_json::to_value(&error).unwrap(),
+        serde_json::json!({"type": "error", "status": 403, "message": "no"})
+    );
+    let frame: ClientEvent = serde_json::from_str(r#"{"type": "join_session", "session_id": "s1"}"#).unwrap();
+    assert!(matches!(frame, ClientEvent::JoinSession { session_id, user_id: None } if session_id == "s1"));
+}
+
+fn queued(id: &str, embedding: Vec<f32>, queued_at: i64) -> UserProfile {
+    let mut user = UserProfile::new(id.to_string(), String::new(), vec![], None);
+    user.embedding = Some(embedding);
+    user.created_at = queued_at;
+    user
+}
+
+#[test]
+fn test_matchmaker_relaxes_expires_and_avoids_rematches() {
+    let config = MatchConfig::default();
+    let mut recent = RecentPairs::default();
+    let now = 10_000;
+
+    // similarity 0.6 is below the initial threshold but passes after a long wait
+    let mut queue = vec![queued("a", vec![1.0, 0.0], now), queued("b", vec![0.6, 0.8], now)];
+    assert!(pick(&mut queue, &recent, &config, now).pairs.is_empty());
+    assert_eq!(queue.len(), 2);
+    let later = now + 120;
+    let round = pick(&mut queue, &recent, &config, later);
+    assert_eq!(round.pairs.len(), 1);
+    assert!(queue.is_empty());
+
+    // the same two don't meet again during the cooldown, even when identical
+    recent.record("a", "b", later);
+    let mut queue = vec![queued("a", vec![1.0, 0.0], later), queued("b", vec![1.0, 0.0], later)];
+    assert!(pick(&mut queue, &recent, &config, later + 10).pairs.is_empty());
+    let cooled = later + config.rematch_cooldown_secs;
+    let mut queue = vec![queued("a", vec![1.0, 0.0], cooled), queued("b", vec![1.0, 0.0], cooled)];
+    assert_eq!(pick(&mut queue, &recent, &config, cooled).pairs.len(), 1);
+
+    // entries past the TTL leave the queue
+    let mut queue = vec![queued("c", vec![1.0, 0.0], now), queued("d", vec![0.0, 1.0], now + 500)];
+    let round = pick(&mut queue, &recent, &config, now + config.queue_ttl_secs + 1);
+    assert_eq!(round.expired.iter().map(|u| u.id.as_str()).collect::<Vec<_>>(), vec!["c"]);
+    assert_eq!(queue.len(), 1);
+}
+
+#[test]
+fn test_age_range_parsing_and_compatibility() {
+    assert_eq!("18-25".parse::<AgeRange>().unwrap(), AgeRange { min: 18, max: 25 });
+    assert_eq!(" 30+ ".parse::<AgeRange>().unwrap(), AgeRange { min: 30, max: AgeRange::OLDEST });
+    assert_eq!("27".parse::<AgeRange>().unwrap(), AgeRange { min: 27, max: 27 });
+    for invalid in ["", "25-18", "abc", "18-", "18-200"] {
+        let error = invalid.parse::<AgeRange>().unwrap_err();
+        assert_eq!(error.status(), warp::http::StatusCode::BAD_REQUEST, "{invalid:?}");
+    }
+
+    let young = AgeRange { min: 18, max: 25 };
+    let older = AgeRange { min: 30, max: AgeRange::OLDEST };
+    assert!(AgeRange::compatible(Some(&young), Some(&AgeRange { min: 25, max: 29 })));
+    assert!(!AgeRange::compatible(Some(&young), Some(&older)));
+    assert!(!AgeRange::compatible(Some(&young), None));
+    assert!(AgeRange::compatible(None, None));
+}
+
+#[test]
+fn test_matchmaker_enforces_age_ranges_and_blocks_and_weighs_interests() {
+    let config = MatchConfig::default();
+    let recent = RecentPairs::default();
+    let now = 10_000;
+
+    let interests = |list: &[&str]| list.iter().map(|i| i.to_string()).collect::<Vec<_>>();
+    assert_eq!(interest_overlap(&interests(&["Chess", "go "]), &interests(&["go", "chess"])), Some(1.0));
+    assert_eq!(interest_overlap(&interests(&["chess"]), &interests(&["jazz"])), Some(0.0));
+    assert_eq!(interest_overlap(&[], &[]), None);
+
+    // identical descriptions never meet across disjoint age ranges
+    let mut a = queued("a", vec![1.0, 0.0], now);
+    let mut b = queued("b", vec![1.0, 0.0], now);
+    a.age_range = Some(AgeRange { min: 18, max: 25 });
+    b.age_range = Some(AgeRange { min: 30, max: 40 });
+    let mut queue = vec![a.clone(), b.clone()];
+    assert!(pick(&mut queue, &recent, &config, now).pairs.is_empty());
+
+    // nor do users who blocked each other
+    let mut blocker = queued("blocker", vec![1.0, 0.0], now);
+    blocker.blocked.insert("blocked".to_string());
+    let mut queue = vec![blocker, queued("blocked", vec![1.0, 0.0], now)];
+    assert!(pick(&mut queue, &recent, &config, now).pairs.is_empty());
+
+    // c's description is closer to a's, but d shares a's interests
+    a.age_range = None;
+    a.interests = interests(&["chess", "jazz"]);
+    let mut c = queued("c", vec![0.9, 0.436], now);
+    c.interests = interests(&["football"]);
+    let mut d = queued("d", vec![0.8, 0.6], now);
+    d.interests = interests(&["Jazz", "chess"]);
+    let mut queue = vec![a, c, d];
+    let round = pick(&mut queue, &recent, &config, now);
+    assert_eq!(round.pairs.len(), 1);
+    let (x, y) = &round.pairs[0];
+    let mut ids = [x.id.as_str(), y.id.as_str()];
+    ids.sort();
+    assert_eq!(ids, ["a", "d"]);
+}
+
+#[tokio::test]
+async fn test_restore_reloads_active_sessions_and_waiting_users() {
+    set_store(Arc::new(MemoryStore::new()));
+
+    let live = ChatSession::new("restore-a".to_string(), "restore-b".to_string());
+    let mut ended = ChatSession::new("restore-a".to_string(), "restore-c".to_string());
+    ended.active = false;
+    ended.ended_at = Some(ended.created_at + 60);
+    storage::save_session(&live).await.unwrap();
+    storage::save_session(&ended).await.unwrap();
+
+    let now = chrono::Utc::now().timestamp();
+    let user = |id: &str, created_at: i64, waiting: bool| Point {
+        id: id.to_string(),
+        vector: Some(vec![1.0, 0.0]),
+        payload: json!({"description": "", "interests": [], "age_range": null, "created_at": created_at, "waiting": waiting})
+            .as_object()
+            .cloned()
+            .unwrap(),
+        score: None,
+    };
+    store()
+        .upsert(CHAT_USERS_COLLECTION, vec![
+            user("0195a000-0000-7000-8000-0000000000a1", now - 30, true),
+            user("0195a000-0000-7000-8000-0000000000a2", now - 30, false),
+            user("0195a000-0000-7000-8000-0000000000a3", now - 100_000, true),
+        ])
+        .await
+        .unwrap();
+
+    restore().await.unwrap();
+
+    let sessions = ACTIVE_SESSIONS.lock().await;
+    assert!(sessions.get(&live.id).is_some_and(|s| s.has_participant("restore-b")));
+    assert!(!sessions.contains_key(&ended.id));
+
+    // only users still waiting and inside the TTL come back, with their embeddings
+    let waiting = WAITING_USERS.lock().await;
+    assert_eq!(waiting.len(), 1);
+    assert_eq!(waiting[0].id, "0195a000-0000-7000-8000-0000000000a1");
+    assert_eq!(waiting[0].embedding, Some(vec![1.0, 0.0]));
+}
@@ -0,0 +1,58 @@
+use i144::repo::memory::cosine;
+use i144::util::embed::{CachedEmbedder, EmbedFuture, Embedder, HashEmbedder};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_hash_embedder_is_deterministic() {
+    let embedder = HashEmbedder::new(128);
+    let a = embedder.embed("Quiet cafe near the lagoon".to_string()).await.unwrap();
+    let b = embedder.embed("quiet cafe near the lagoon".to_string()).await.unwrap();
+    let other = embedder.embed("football pitch booking".to_string()).await.unwrap();
+
+    assert_eq!(a.len(), embedder.dimension());
+    assert_eq!(a, b);
+    assert!(cosine(&a, &b) > 0.99);
+    assert!(cosine(&a, &other) < cosine(&a, &embedder.vector("a cafe by the lagoon")));
+}
+
+/// counts how many texts reach the provider
+struct Counting {
+    hash: HashEmbedder,
+    texts: AtomicUsize,
+}
+
+impl Embedder for Counting {
+    fn dimension(&self) -> usize {
+        self.hash.dimension()
+    }
+
+    fn embed(&self, text: String) -> EmbedFuture<'_> {
+        self.texts.fetch_add(1, Ordering::SeqCst);
+        self.hash.embed(text)
+    }
+}
+
+#[tokio::test]
+async fn test_cached_embedder_skips_seen_texts() {
+    let counting = Arc::new(Counting { hash: HashEmbedder::new(32), texts: AtomicUsize::new(0) });
+    let cached = CachedEmbedder::new("counting", counting.clone(), 2);
+
+    let batch = cached
+        .embed_batch(vec!["red bike".to_string(), "blue car".to_string(), "red bike".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(batch.len(), 3);
+    assert_eq!(batch[0], batch[2]);
+    assert_eq!(counting.texts.load(Ordering::SeqCst), 2);
+
+    assert_eq!(cached.embed("red bike".to_string()).await.unwrap(), batch[0]);
+    assert_eq!(counting.texts.load(Ordering::SeqCst), 2);
+
+    // "blue car" is now the least recently used and makes room for "green van"
+    cached.embed("green van".to_string()).await.unwrap();
+    cached.embed("red bike".to_string()).await.unwrap();
+    assert_eq!(counting.texts.load(Ordering::SeqCst), 3);
+    cached.embed("blue car".to_string()).await.unwrap();
+    assert_eq!(counting.texts.load(Ordering::SeqCst), 4);
+}
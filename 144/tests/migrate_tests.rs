@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use i144::repo::memory::MemoryStore;
+use i144::repo::migrate::{migrate, MIGRATIONS};
+use i144::repo::schema::{COLLECTIONS, COUNTER_COLLECTION};
+use i144::repo::{keys, set_store, store, FieldSchema, Filter};
+use i144::util::embed::{set_embedder, HashEmbedder};
+
+#[tokio::test]
+async fn test_migrate_is_idempotent_and_only_drops_when_forced() {
+    let memory = Arc::new(MemoryStore::new());
+    set_store(memory.clone());
+    set_embedder(Arc::new(HashEmbedder::new(64)));
+
+    let first = migrate(false).await.unwrap();
+    assert_eq!(first.created.len(), COLLECTIONS.len());
+    assert_eq!(first.version, MIGRATIONS.len() as u32);
+    assert_eq!(memory.indexes("i").get(keys::TENANT), Some(&FieldSchema::Keyword));
+    assert_eq!(store().count(COUNTER_COLLECTION, &Filter::new()).await.unwrap(), 1);
+
+    let second = migrate(false).await.unwrap();
+    assert!(second.created.is_empty() && second.recreated.is_empty() && second.applied.is_empty());
+    assert_eq!(second.version, first.version);
+
+    // switching to an embedder with another dimension needs --force
+    set_embedder(Arc::new(HashEmbedder::new(32)));
+    assert!(migrate(false).await.is_err());
+    assert_eq!(store().collection_size("i").await.unwrap(), Some(64));
+
+    let forced = migrate(true).await.unwrap();
+    assert!(forced.recreated.contains(&"i".to_string()));
+    assert!(!forced.recreated.contains(&COUNTER_COLLECTION.to_string()));
+    assert_eq!(store().collection_size("i").await.unwrap(), Some(32));
+    assert_eq!(store().count(COUNTER_COLLECTION, &Filter::new()).await.unwrap(), 1);
+}
@@ -0,0 +1,97 @@
+use i144::repo::{
+    keys,
+    memory::filter_matches,
+    models::{ChatGroup, Listing, ListingKind, Position, User, Zone},
+    Condition, Filter, Point, Range,
+};
+use serde_json::{json, Value};
+
+const KNOWN_KEYS: &[&str] = &[
+    keys::TENANT, keys::NAME, keys::TEXT, keys::LOCATION, keys::IMAGES, keys::LISTING_IMAGES,
+    keys::POSITION, keys::USER, keys::ZONE, keys::PRICE, keys::MEMBERS, keys::EXTRA,
+    keys::GOOGLE_ID, keys::EMAIL, keys::AGE, keys::GENDER, keys::LAST_LOGIN,
+];
+
+fn assert_known_keys(value: Value) {
+    for key in value.as_object().unwrap().keys() {
+        assert!(KNOWN_KEYS.contains(&key.as_str()), "unknown payload key {}", key);
+    }
+}
+
+#[test]
+fn test_models_use_known_keys() {
+    let position = Position { lat: 1.0, lng: 2.0 };
+    assert_known_keys(serde_json::to_value(Zone {
+        name: "zone".into(),
+        description: "a zone".into(),
+        location_url: String::new(),
+        images: vec![],
+        position,
+        creator: Some("user-1".into()),
+        members: 3,
+    }).unwrap());
+    assert_known_keys(serde_json::to_value(User {
+        google_id: Some("g".into()),
+        email: Some("a@b.c".into()),
+        age: Some(30),
+        gender: Some("f".into()),
+        last_login: Some("now".into()),
+        ..Default::default()
+    }).unwrap());
+    assert_known_keys(serde_json::to_value(ChatGroup {
+        name: "group".into(),
+        text: "text".into(),
+        link: "https://example.com".into(),
+        image: None,
+        user: "user-1".into(),
+        zone: None,
+        extra: None,
+    }).unwrap());
+
+    let listing = serde_json::to_value(Listing {
+        description: "bike".into(),
+        price: 10.0,
+        user: "user-1".into(),
+        zone: None,
+        images: vec![],
+        location_url: String::new(),
+        position: Some(position),
+        kind: ListingKind::Service,
+    }).unwrap();
+    assert_eq!(listing[keys::TENANT], "s");
+    assert_known_keys(listing.clone());
+    assert_eq!(serde_json::from_value::<Listing>(listing).unwrap().kind, ListingKind::Service);
+}
+
+#[test]
+fn test_filter_json() {
+    let filter = Filter::new()
+        .must(Condition::matches(keys::ZONE, "zone-1"))
+        .must(Condition::any(keys::TENANT, ["p", "s"]))
+        .must(Condition::range(keys::PRICE, Range { gte: Some(1.0), ..Default::default() }))
+        .must_not(Condition::has_id(["point-1"]));
+
+    assert_eq!(serde_json::to_value(filter).unwrap(), json!({
+        "must": [
+            {"key": "z", "match": {"value": "zone-1"}},
+            {"key": "s", "match": {"any": ["p", "s"]}},
+            {"key": "c", "range": {"gte": 1.0}}
+        ],
+        "must_not": [{"has_id": ["point-1"]}]
+    }));
+}
+
+#[test]
+fn test_memory_is_null_matches_explicit_null_only() {
+    let point = |payload: Value| Point {
+        id: "point-1".to_string(),
+        vector: None,
+        payload: payload.as_object().cloned().unwrap_or_default(),
+        score: None,
+    };
+    let filter = Filter::new().must(Condition::is_null(keys::ZONE));
+
+    assert!(filter_matches(&filter, &point(json!({"z": null}))));
+    assert!(!filter_matches(&filter, &point(json!({}))));
+    assert!(!filter_matches(&filter, &point(json!({"z": "zone-1"}))));
+}
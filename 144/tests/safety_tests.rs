@@ -0,0 +1,90 @@
+use futures::future::BoxFuture;
+use i144::repo::memory::MemoryStore;
+use i144::repo::set_store;
+use i144::util::safety::{
+    flagged, review, screen, set_safety, Action, Category, Classifier, ContentKind, Finding, PiiClassifier, Policy,
+    Safety, WordListClassifier,
+};
+use i144::util::AppResult;
+use std::sync::Arc;
+
+fn local() -> Safety {
+    Safety::new(
+        vec![Arc::new(PiiClassifier::new()), Arc::new(WordListClassifier::with_defaults(["creep".to_string()]))],
+        Policy::default(),
+    )
+}
+
+#[tokio::test]
+async fn test_safety_masks_pii_warns_and_rejects_per_kind() {
+    let safety = local();
+
+    let verdict = safety.check(ContentKind::ChatMessage, "mail me at jo@example.com or call +44 7700 900123").await;
+    assert_eq!(verdict.action, Action::Mask);
+    assert_eq!(verdict.text, "mail me at ************** or call *** **** ******");
+    let labels: Vec<&str> = verdict.findings.iter().map(|f| f.label.as_str()).collect();
+    assert_eq!(labels, vec!["email", "phone_number"]);
+
+    // a valid card number is told apart from a phone number, prices aren't either
+    let card = safety.check(ContentKind::Listing, "pay to 4111 1111 1111 1111, bike for 1 200").await;
+    assert_eq!(card.findings.iter().map(|f| f.label.as_str()).collect::<Vec<_>>(), vec!["card_number"]);
+    assert_eq!(safety.check(ContentKind::Listing, "bike for 1 200, Sunday at 10").await.action, Action::Allow);
+    let price = "land for 150 000 000, viewing 2024-01-15 10:30 or 05.06.2024 10:30";
+    assert_eq!(safety.check(ContentKind::Listing, price).await.text, price);
+    let phones = safety.check(ContentKind::Listing, "call (020) 7946 0958 or 07700900123").await;
+    assert_eq!(phones.text, "call ***** **** **** or ***********");
+
+    // swearing is a warning in chat but masked in a listing; abuse is always rejected
+    assert_eq!(safety.check(ContentKind::ChatMessage, "that's SHIT luck").await.action, Action::Warn);
+    assert_eq!(safety.check(ContentKind::Listing, "shit bike").await.text, "**** bike");
+    assert_eq!(safety.check(ContentKind::ChatMessage, "just kill yourself").await.action, Action::Reject);
+    assert_eq!(safety.check(ContentKind::ChatMessage, "what a creep").await.action, Action::Reject);
+    assert_eq!(safety.check(ContentKind::ChatMessage, "shitake mushrooms").await.action, Action::Allow);
+
+    let policy = Policy::parse("listing.pii=allow, chat_message.profanity=reject").unwrap();
+    assert_eq!(policy.action(ContentKind::Listing, Category::Pii), Action::Allow);
+    assert_eq!(policy.action(ContentKind::ChatMessage, Category::Profanity), Action::Reject);
+    assert_eq!(policy.action(ContentKind::Zone, Category::Abuse), Action::Reject);
+    assert_eq!(policy.action(ContentKind::ChatGroup, Category::Profanity), Action::Reject);
+    assert!(Policy::parse("listing.pii").is_err());
+    assert!(Policy::parse("listing.spam=mask").is_err());
+}
+
+/// flags every text as a whole, like an LLM check
+struct Everything;
+
+impl Classifier for Everything {
+    fn classify<'a>(&'a self, _text: &'a str) -> BoxFuture<'a, AppResult<Vec<Finding>>> {
+        Box::pin(futures::future::ready(Ok(vec![Finding {
+            classifier: "everything".to_string(),
+            category: Category::Pii,
+            label: "suspicious".to_string(),
+            span: None,
+        }])))
+    }
+}
+
+#[tokio::test]
+async fn test_screened_content_is_recorded_for_review() {
+    set_store(Arc::new(MemoryStore::new()));
+    set_safety(Arc::new(Safety::new(vec![Arc::new(Everything)], Policy::default())));
+
+    // a finding without a span can't be masked, so it only warns
+    let verdict = screen(ContentKind::ChatMessage, "author", "hello there").await.unwrap();
+    assert_eq!(verdict.action, Action::Warn);
+    assert_eq!(verdict.text, "hello there");
+    assert!(verdict.notice().is_some_and(|notice| notice.contains("suspicious")));
+
+    set_safety(Arc::new(local()));
+    let rejected = screen(ContentKind::Zone, "author", "damn fucker").await.unwrap_err();
+    assert_eq!(rejected.status(), warp::http::StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(screen(ContentKind::Zone, "author", "Old harbour").await.unwrap().action, Action::Allow);
+
+    let queue = flagged(false, 10).await.unwrap();
+    assert_eq!(queue.len(), 2);
+    let zone = queue.iter().find(|f| f.kind == ContentKind::Zone).unwrap();
+    assert_eq!((zone.text.as_str(), zone.action), ("damn fucker", Action::Reject));
+    review("moderator", &zone.id).await.unwrap();
+    assert_eq!(flagged(false, 10).await.unwrap().len(), 1);
+    assert_eq!(flagged(true, 10).await.unwrap()[0].reviewed_by.as_deref(), Some("moderator"));
+}
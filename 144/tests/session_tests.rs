@@ -0,0 +1,40 @@
+use i144::util::session::{sign, verify, Claims, TokenKind};
+
+const SECRET: &[u8] = b"test-session-secret";
+const NOW: i64 = 1_700_000_000;
+
+fn claims(typ: TokenKind, exp: i64) -> Claims {
+    Claims {
+        sub: "user-1".to_string(),
+        sid: "session-1".to_string(),
+        exp,
+        typ,
+    }
+}
+
+#[test]
+fn test_token_round_trip() {
+    let token = sign(&claims(TokenKind::Access, NOW + 60), SECRET).unwrap();
+    let verified = verify(&token, SECRET, TokenKind::Access, NOW).unwrap();
+    assert_eq!(verified.sub, "user-1");
+    assert_eq!(verified.sid, "session-1");
+}
+
+#[test]
+fn test_rejects_bad_tokens() {
+    let token = sign(&claims(TokenKind::Access, NOW + 60), SECRET).unwrap();
+    assert!(verify(&token, b"other-secret", TokenKind::Access, NOW).is_err());
+    assert!(verify(&token, SECRET, TokenKind::Refresh, NOW).is_err());
+    assert!(verify(&token, SECRET, TokenKind::Access, NOW + 60).is_err());
+    assert!(verify("user-1", SECRET, TokenKind::Access, NOW).is_err());
+
+    // swapping in another user's claims breaks the signature
+    let other = sign(&Claims { sub: "user-2".to_string(), ..claims(TokenKind::Access, NOW + 60) }, SECRET).unwrap();
+    let forged = format!(
+        "{}.{}",
+        other.split('.').next().unwrap(),
+        token.split('.').nth(1).unwrap()
+    );
+    let err = verify(&forged, SECRET, TokenKind::Access, NOW).unwrap_err();
+    assert_eq!(err.status(), warp::http::StatusCode::UNAUTHORIZED);
+}
@@ -1,5 +1,5 @@
 import axios from 'axios';
-import type { User } from '../stores/userStore';
+import { accessToken, type User } from '../stores/userStore';
 
 // API base URL
 const API_URL = 'http://localhost:8000';
@@ -112,7 +112,11 @@ const userService = {
    */
   joinZone: async (zoneId: string): Promise<boolean> => {
     try {
-      await axios.post(`${API_URL}/user/join_zone`, { zone_id: zoneId });
+      await axios.post(
+        `${API_URL}/user/join_zone`,
+        { zone_id: zoneId },
+        { headers: { Authorization: `Bearer ${accessToken()}` } }
+      );
       return true;
     } catch (error) {
       console.error('Error joining zone:', error);
@@ -125,7 +129,11 @@ const userService = {
    */
   leaveZone: async (zoneId: string): Promise<boolean> => {
     try {
-      await axios.post(`${API_URL}/user/leave_zone`, { zone_id: zoneId });
+      await axios.post(
+        `${API_URL}/user/leave_zone`,
+        { zone_id: zoneId },
+        { headers: { Authorization: `Bearer ${accessToken()}` } }
+      );
       return true;
     } catch (error) {
       console.error('Error leaving zone:', error);
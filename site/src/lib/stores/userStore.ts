@@ -96,6 +96,8 @@ const createUserStore = () => {
           if (typeof window !== 'undefined') {
             localStorage.setItem('user', JSON.stringify(user));
             localStorage.setItem('user_id', response.data.user_id);
+            localStorage.setItem('access_token', response.data.access_token);
+            localStorage.setItem('refresh_token', response.data.refresh_token);
           }
           
           update(state => ({
@@ -167,20 +169,95 @@ const createUserStore = () => {
       }
     },
 
-    // Logout user
-    logout: () => {
-      if (typeof window !== 'undefined') {
-        localStorage.removeItem('user');
-        localStorage.removeItem('user_id');
+    // Logout user, revoking the session on the server first
+    logout: async () => {
+      const token = accessToken();
+      if (token) {
+        try {
+          await axios.post(`${API_URL}/auth/logout`, null, {
+            headers: { Authorization: `Bearer ${token}` }
+          });
+        } catch (error) {
+          // the token may already be expired or revoked; clear local state regardless
+          console.error('Failed to revoke session', error);
+        }
       }
-      
+      clearStorage();
       set(initialState);
     }
   };
 };
 
+const clearStorage = () => {
+  if (typeof window !== 'undefined') {
+    localStorage.removeItem('user');
+    localStorage.removeItem('user_id');
+    localStorage.removeItem('access_token');
+    localStorage.removeItem('refresh_token');
+  }
+};
+
 export const userStore = createUserStore();
 
+// Bearer token issued by the backend on login
+export const accessToken = (): string | null =>
+  typeof window !== 'undefined' ? localStorage.getItem('access_token') : null;
+
+// One refresh at a time, shared by every request that got a 401 meanwhile
+let refreshing: Promise<string | null> | null = null;
+
+// Exchanges the refresh token for a new token pair; signs out locally when that fails
+export const refreshAccessToken = (): Promise<string | null> => {
+  if (!refreshing) {
+    refreshing = (async () => {
+      const refreshToken = typeof window !== 'undefined' ? localStorage.getItem('refresh_token') : null;
+      if (!refreshToken) return null;
+      try {
+        const response = await axios.post(`${API_URL}/auth/refresh`, { refresh_token: refreshToken });
+        localStorage.setItem('access_token', response.data.access_token);
+        localStorage.setItem('refresh_token', response.data.refresh_token);
+        return response.data.access_token as string;
+      } catch (error) {
+        console.error('Failed to refresh session', error);
+        // cleared first, so logout doesn't send the dead token back to the server
+        clearStorage();
+        userStore.logout();
+        return null;
+      }
+    })().finally(() => {
+      refreshing = null;
+    });
+  }
+  return refreshing;
+};
+
+// fetch with the bearer token, retried once with a refreshed token on a 401
+export const authFetch = async (input: RequestInfo | URL, init: RequestInit = {}): Promise<Response> => {
+  const withToken = (token: string | null): RequestInit => {
+    const headers = new Headers(init.headers);
+    if (token) headers.set('Authorization', `Bearer ${token}`);
+    return { ...init, headers };
+  };
+  const response = await fetch(input, withToken(accessToken()));
+  if (response.status !== 401) return response;
+  const token = await refreshAccessToken();
+  return token ? fetch(input, withToken(token)) : response;
+};
+
+// The same retry for axios requests that carry the bearer token
+axios.interceptors.response.use(undefined, async (error) => {
+  const config = error.config;
+  const authorized = config?.headers?.Authorization ?? config?.headers?.authorization;
+  if (error.response?.status !== 401 || !authorized || config._retried) {
+    throw error;
+  }
+  const token = await refreshAccessToken();
+  if (!token) throw error;
+  config._retried = true;
+  config.headers.Authorization = `Bearer ${token}`;
+  return axios.request(config);
+});
+
 // Derived stores for convenience
 export const isAuthenticated = derived(
   userStore,